@@ -1,13 +1,17 @@
 use clap::{Arg, Command};
 use ethers::{
-    types::U256,
+    types::{Address, U256},
     utils::{format_units, hex},
 };
-use osanwelib::{db, generated::TransactionPb, keys, tx};
+use osanwelib::{agent, db, generated::TransactionPb, keys, multisig, profile, signer, tx};
 use prost::Message;
 use rpassword::read_password;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
 
 pub fn get_matches() -> clap::ArgMatches {
     Command::new(env!("CARGO_PKG_NAME"))
@@ -36,6 +40,186 @@ pub fn get_matches() -> clap::ArgMatches {
                 .help("Set the wallet password if it hasn't been set yet")
                 .value_parser(clap::value_parser!(String)),
         )
+        .arg(
+            Arg::new("change-password")
+                .long("change-password")
+                .help("Change the wallet password (the current one is taken from --password or prompted)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("new-password")
+                .long("new-password")
+                .value_name("PASSWORD")
+                .help("New password for --change-password")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("passphrase")
+                .long("passphrase")
+                .value_name("PASSPHRASE")
+                .help("Optional BIP-39 passphrase used together with the recovery phrase")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Restore the wallet from a BIP-39 recovery phrase into a fresh database")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("import-keystore")
+                .long("import-keystore")
+                .value_name("FILE_PATH")
+                .help("Import a V3 JSON keystore (geth, MetaMask) as the wallet key into a fresh database")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("export-keystore")
+                .long("export-keystore")
+                .value_name("FILE_PATH")
+                .help("Export the account key (see --from) to a new V3 JSON keystore file")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("export-private-key")
+                .long("export-private-key")
+                .help("Print the raw private key of the account (see --from) after a confirmation")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("backup-shares")
+                .long("backup-shares")
+                .value_names(["THRESHOLD", "SHARES"])
+                .help("Split the wallet seed (or key) into SHARES text shares, any THRESHOLD of which restore the wallet")
+                .num_args(2)
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(
+            Arg::new("restore-shares")
+                .long("restore-shares")
+                .help("Restore the wallet from backup shares read from stdin into a fresh database")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keystore-password")
+                .long("keystore-password")
+                .value_name("PASSWORD")
+                .help("Password of the V3 keystore for --import-keystore and --export-keystore")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("accounts")
+                .long("accounts")
+                .help("List the wallet accounts")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("new-account")
+                .long("new-account")
+                .value_name("LABEL")
+                .help("Derive a new account from the wallet seed and label it")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .value_name("ACCOUNT")
+                .help("Account label or index to use for --send, --withdraw and --balance (default: main)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("unlock")
+                .long("unlock")
+                .value_name("SECONDS")
+                .help("Start an agent that keeps the wallet unlocked for SECONDS (default: 900), so --send and --withdraw do not ask for the password")
+                .num_args(0..=1)
+                .default_missing_value("900")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("lock")
+                .long("lock")
+                .help("Stop the unlock agent and forget the decrypted keys")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            // Внутрішній режим: процес агента, який запускає --unlock
+            Arg::new("run-agent")
+                .long("run-agent")
+                .value_name("SECONDS")
+                .hide(true)
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("create-multisig")
+                .long("create-multisig")
+                .value_names(["LABEL", "THRESHOLD", "SIGNERS"])
+                .help("Create a THRESHOLD-of-n multisig account from comma-separated SIGNERS addresses")
+                .num_args(3)
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("multisig")
+                .long("multisig")
+                .value_name("ACCOUNT")
+                .help("Propose --send or --withdraw from a multisig account (label or address); the transaction is signed by --signer/--from and saved as a .osnms file for the other signers")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cosign")
+                .long("cosign")
+                .value_name("FILE_PATH")
+                .help("Add the signature of --signer/--from to a multisig transaction file (.osnms)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("signer")
+                .long("signer")
+                .value_name("SIGNER")
+                .help("Signer for --send and --withdraw: local, agent, exec:<COMMAND> or grpc:<URL> (default: agent if unlocked, otherwise local). For other signers than local --from names the key")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("network")
+                .long("network")
+                .value_name("NETWORK_ID")
+                .help("Osanwe network the transaction is signed for (default: OSANWE_NETWORK_ID, the profile network or osanwe-dev)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("data-dir")
+                .long("data-dir")
+                .value_name("DIR")
+                .help("Directory with the wallet profiles (default: OSANWE_DATA_DIR, $XDG_DATA_HOME/osanwe or ~/.local/share/osanwe)")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Wallet profile with its own database and server (default: OSANWE_PROFILE or default)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("profiles")
+                .long("profiles")
+                .help("List the wallet profiles in the data directory")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("set-server")
+                .long("set-server")
+                .value_name("URL")
+                .help("Save the server URL the profile submits transactions to")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("set-network")
+                .long("set-network")
+                .value_name("NETWORK_ID")
+                .help("Save the Osanwe network the profile signs transactions for")
+                .value_parser(clap::value_parser!(String)),
+        )
         .arg(
             Arg::new("list-assets")
                 .short('l')
@@ -51,6 +235,13 @@ pub fn get_matches() -> clap::ArgMatches {
                 .value_names(["AMOUNT", "CURRENCY_ID", "RECIPIENT"])
                 .help("Send tokens to the recipient. Example: --send 345.5 16842752 0x..."),
         )
+        .arg(
+            Arg::new("withdraw")
+                .long("withdraw")
+                .num_args(3)
+                .value_names(["AMOUNT", "CURRENCY_ID", "DESTINATION_ADDRESS"])
+                .help("Withdraw tokens to an address on the external blockchain. Example: --withdraw 10 16842753 0x..."),
+        )
         .arg(
             Arg::new("replenishing")
                 .long("replenishing")
@@ -70,6 +261,65 @@ pub fn get_matches() -> clap::ArgMatches {
                 .value_name("WALLET_ADDRESS")
                 .help("Show balance for the given wallet address. If no address is provided, shows your own wallet's balance.")
         )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .num_args(0..=1)
+                .value_name("WALLET_ADDRESS")
+                .help("Show the transaction history of the wallet address, newest first. If no address is provided, shows your own wallet's history.")
+        )
+        .arg(
+            Arg::new("direction")
+                .long("direction")
+                .value_name("in|out")
+                .help("Only incoming or outgoing transactions in --history")
+                .value_parser(["in", "out"]),
+        )
+        .arg(
+            Arg::new("currency")
+                .long("currency")
+                .value_name("CURRENCY_ID")
+                .help("Only transactions in this currency in --history")
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .value_name("TYPE")
+                .help("Only transactions of this type in --history")
+                .value_parser(["replenish", "transfer", "withdraw"]),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("TIME")
+                .help("Only transactions at or after this time in --history: YYYY-MM-DD (UTC) or RFC 3339"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("TIME")
+                .help("Only transactions before this time in --history: YYYY-MM-DD (UTC) or RFC 3339"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_name("COUNT")
+                .help("Transactions per --history page (default: 20)")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("cursor")
+                .long("cursor")
+                .value_name("CURSOR")
+                .help("Continue --history after the page that printed this cursor"),
+        )
+        .arg(
+            Arg::new("rebuild-balances")
+                .long("rebuild-balances")
+                .help("Recompute the stored balances from the transaction history and report any that disagreed")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("import")
                 .long("import")
@@ -86,31 +336,92 @@ fn main() {
 
     let matches = get_matches();
 
+    let data_dir =
+        match profile::data_dir(matches.get_one::<PathBuf>("data-dir").map(PathBuf::as_path)) {
+            Ok(data_dir) => data_dir,
+            Err(e) => {
+                eprintln!("Error resolving the data directory: {}", e);
+                return;
+            }
+        };
+    let profile_name =
+        profile::profile_name(matches.get_one::<String>("profile").map(String::as_str));
+
+    if matches.get_flag("profiles") {
+        list_profiles(&data_dir, &profile_name);
+        return;
+    }
+
+    // Профіль має бути активним до першого звернення до бази, зокрема в процесі агента
+    match profile::open(&data_dir, &profile_name) {
+        Ok(active) if is_legacy_database_left(&active) => return,
+        Ok(active) => profile::activate(active),
+        Err(e) => {
+            eprintln!("Error opening profile '{}': {}", profile_name, e);
+            return;
+        }
+    }
+    if matches.contains_id("set-server") || matches.contains_id("set-network") {
+        update_profile(&matches);
+    }
+
+    if let Some(&timeout) = matches.get_one::<u64>("run-agent") {
+        run_agent(timeout);
+        return;
+    }
+
     if let Err(e) = db::check_and_create_database() {
         eprintln!("Error checking or creating database: {:?}", e);
     }
 
+    let passphrase = matches.get_one::<String>("passphrase").map(String::as_str);
+    let from = matches.get_one::<String>("from").map(String::as_str);
+    let signer_spec = matches.get_one::<String>("signer").map(String::as_str);
+    let multisig_account = matches.get_one::<String>("multisig").map(String::as_str);
+    let network_id = matches
+        .get_one::<String>("network")
+        .cloned()
+        .unwrap_or_else(tx::network_id);
+
+    // Відновлення з фрази чи keystore має відбутися до створення нового гаманця
+    if matches.get_flag("restore") {
+        restore_wallet(&matches, passphrase);
+        return;
+    }
+    if let Some(file_path) = matches.get_one::<String>("import-keystore") {
+        import_keystore(&matches, file_path);
+        return;
+    }
+    if matches.get_flag("restore-shares") {
+        restore_shares(&matches);
+        return;
+    }
+
+    if matches.get_flag("lock") {
+        match agent::lock() {
+            Ok(true) => println!("Wallet locked."),
+            Ok(false) => println!("No unlock agent is running."),
+            Err(e) => eprintln!("Error stopping the unlock agent: {}", e),
+        }
+    }
+
     if let Some(new_password) = matches.get_one::<String>("set-password") {
         if !db::is_password_set() {
-            if let Err(e) = db::set_password(new_password.as_bytes()) {
-                eprintln!("Error saving password: {:?}", e);
-            } else {
-                println!("Password has been successfully set.");
-            }
+            create_wallet(new_password, passphrase);
         } else {
-            println!("Password is already set. Cannot change it.");
+            println!("Password is already set. Use --change-password to change it.");
         }
     }
 
+    if matches.get_flag("change-password") && db::is_password_set() {
+        change_password(&matches);
+    }
+
     // Використовуємо нову функцію is_password_set, яка повертає bool
     if !db::is_password_set() {
         println!("Password is not set. Please set a new password:");
         if let Some(password) = prompt_for_password() {
-            if let Err(e) = db::set_password(password.as_bytes()) {
-                eprintln!("Error saving password: {:?}", e);
-            } else {
-                println!("Password has been successfully set.");
-            }
+            create_wallet(&password, passphrase);
         }
     }
 
@@ -130,15 +441,113 @@ fn main() {
             });
 
         if let Some(password) = password {
-            match db::is_password_correct(password.as_bytes()) {
-                Ok(true) => {
-                    match keys::get_wallet_address(password.as_bytes()) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => {
+                    match keys::get_wallet_address(&property_key) {
                         // Виклик правильного методу
                         Ok(address) => println!("Wallet Address: {}", address),
                         Err(e) => eprintln!("Error retrieving wallet address: {:?}", e),
                     }
                 }
-                Ok(false) => eprintln!("Incorrect password."),
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if let Some(label) = matches.get_one::<String>("new-account") {
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => match keys::create_account(label, &property_key) {
+                    Ok(account) => println!(
+                        "Account created: {}\t{}\t{}",
+                        account.account_index, account.label, account.address
+                    ),
+                    Err(e) => eprintln!("Error creating account: {}", e),
+                },
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if let Some(&timeout) = matches.get_one::<u64>("unlock") {
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                // Агент виводить ключ сам, у власному процесі
+                Ok(Some(_)) => start_agent(&password, timeout, &data_dir, &profile_name),
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if let Some(values) = matches.get_many::<String>("create-multisig") {
+        let values: Vec<&String> = values.collect();
+        create_multisig(values[0], values[1], values[2]);
+    }
+
+    if matches.get_flag("accounts") {
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => match keys::list_accounts(&property_key) {
+                    Ok(accounts) => {
+                        for account in accounts {
+                            println!(
+                                "{}\t{}\t{}",
+                                account.account_index, account.label, account.address
+                            );
+                        }
+                        match multisig::list_accounts() {
+                            Ok(accounts) => {
+                                for account in accounts {
+                                    println!(
+                                        "-\t{}\t{}\t{}-of-{} multisig: {}",
+                                        account.label,
+                                        account.address,
+                                        account.threshold,
+                                        account.signers.len(),
+                                        account.signers.join(",")
+                                    );
+                                }
+                            }
+                            Err(e) => eprintln!("Error retrieving multisig accounts: {:?}", e),
+                        }
+                    }
+                    Err(e) => eprintln!("Error retrieving accounts: {:?}", e),
+                },
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if let Some(file_path) = matches.get_one::<String>("export-keystore") {
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => export_keystore(&matches, file_path, from, &property_key),
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if matches.get_flag("export-private-key") {
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => export_private_key(from, &property_key),
+                Ok(None) => eprintln!("Incorrect password."),
+                Err(e) => eprintln!("Error checking password: {:?}", e),
+            }
+        }
+    }
+
+    if let Some(values) = matches.get_many::<u8>("backup-shares") {
+        let values: Vec<u8> = values.copied().collect();
+        if let Some(password) = get_or_prompt_password(&matches) {
+            match db::unlock(password.as_bytes()) {
+                Ok(Some(property_key)) => backup_shares(values[0], values[1], &property_key),
+                Ok(None) => eprintln!("Incorrect password."),
                 Err(e) => eprintln!("Error checking password: {:?}", e),
             }
         }
@@ -175,60 +584,134 @@ fn main() {
         let currency_id_str = &values[1];
         let recipient = &values[2];
 
-        // Отримання пароля
-        let password = matches.get_one::<String>("password").cloned().or_else(|| {
-            println!("Enter password:");
-            match read_password() {
-                Ok(password) => Some(password),
-                Err(e) => {
-                    eprintln!("Error reading password: {:?}", e);
-                    None
-                }
+        // Спробуємо конвертувати currency_id_str у u32
+        let currency_id: u32 = match currency_id_str.parse() {
+            Ok(val) => val,
+            Err(_) => {
+                eprintln!(
+                    "Invalid currency_id: '{}'. Must be a valid u32.",
+                    currency_id_str
+                );
+                return;
             }
-        });
+        };
 
-        if let Some(password) = password {
-            // Перевірка пароля
-            match db::is_password_correct(password.as_bytes()) {
-                Ok(true) => {
-                    // Спробуємо конвертувати currency_id_str у u32
-                    let currency_id: u32 = match currency_id_str.parse() {
-                        Ok(val) => val,
-                        Err(_) => {
-                            eprintln!(
-                                "Invalid currency_id: '{}'. Must be a valid u32.",
-                                currency_id_str
-                            );
-                            return;
-                        }
-                    };
-
-                    // Зберігаємо сума як текст, а currency_id як u32
-                    println!("Send request received:");
-                    println!("  Amount (string): {}", amount_str);
-                    println!("  Currency ID (u32): {}", currency_id);
-                    println!("  Recipient: {}", recipient);
-
-                    match tx::send_money(&password, &amount_str, currency_id, &recipient) {
-                        Ok(transaction) => match tx::store_transaction(&transaction) {
-                            Ok(_) => {
-                                match save_transaction_as_json(&transaction) {
-                                    Ok(_) => println!("Ok"),
-                                    Err(e) => println!("Err {}", e),
-                                }
-                                println!("Ok");
-                            }
+        if let Some(signer) = resolve_signer(&matches, signer_spec, from) {
+            // Зберігаємо сума як текст, а currency_id як u32
+            println!("Send request received:");
+            println!("  Amount (string): {}", amount_str);
+            println!("  Currency ID (u32): {}", currency_id);
+            println!("  Recipient: {}", recipient);
+
+            if let Some(name) = multisig_account {
+                propose_multisig(
+                    name,
+                    signer.as_ref(),
+                    &network_id,
+                    tx::TX_TYPE_TRANSFER,
+                    amount_str,
+                    currency_id,
+                    recipient,
+                );
+                return;
+            }
+
+            match db::Store::open().and_then(|mut store| {
+                tx::send_money(
+                    &mut store,
+                    signer.as_ref(),
+                    &network_id,
+                    amount_str,
+                    currency_id,
+                    recipient,
+                )
+            }) {
+                // Транзакція вже збережена в базі — лишається надіслати її на сервер
+                Ok(transaction) => match tx::submit_transaction(&transaction) {
+                    Ok(_) => {
+                        match save_transaction_as_json(&transaction) {
+                            Ok(_) => println!("Ok"),
                             Err(e) => println!("Err {}", e),
-                        },
-                        Err(e) => println!("Err {}", e),
-                    };
-                }
-                Ok(false) => eprintln!("Incorrect password."),
-                Err(e) => eprintln!("Error checking password: {:?}", e),
+                        }
+                        println!("Ok");
+                    }
+                    Err(e) => println!("Err {}", e),
+                },
+                Err(e) => println!("Err {}", e),
+            };
+        }
+    }
+
+    // Логіка для --withdraw amount currency_id destination_address
+    if let Some(values) = matches.get_many::<String>("withdraw") {
+        let values: Vec<&String> = values.collect();
+        if values.len() != 3 {
+            eprintln!(
+                "--withdraw requires exactly 3 arguments: AMOUNT CURRENCY_ID DESTINATION_ADDRESS"
+            );
+            return;
+        }
+
+        let amount_str = &values[0];
+        let currency_id_str = &values[1];
+        let destination = &values[2];
+
+        let currency_id: u32 = match currency_id_str.parse() {
+            Ok(val) => val,
+            Err(_) => {
+                eprintln!(
+                    "Invalid currency_id: '{}'. Must be a valid u32.",
+                    currency_id_str
+                );
+                return;
             }
+        };
+
+        if let Some(signer) = resolve_signer(&matches, signer_spec, from) {
+            println!("Withdrawal request received:");
+            println!("  Amount: {}", amount_str);
+            println!("  Currency ID: {}", currency_id);
+            println!("  Destination: {}", destination);
+
+            if let Some(name) = multisig_account {
+                propose_multisig(
+                    name,
+                    signer.as_ref(),
+                    &network_id,
+                    tx::TX_TYPE_WITHDRAW,
+                    amount_str,
+                    currency_id,
+                    destination,
+                );
+                return;
+            }
+
+            match db::Store::open().and_then(|mut store| {
+                tx::withdraw(
+                    &mut store,
+                    signer.as_ref(),
+                    &network_id,
+                    amount_str,
+                    currency_id,
+                    destination,
+                )
+            }) {
+                Ok(transaction) => match tx::submit_transaction(&transaction) {
+                    Ok(_) => match save_transaction_as_json(&transaction) {
+                        Ok(_) => println!("Ok"),
+                        Err(e) => println!("Err {}", e),
+                    },
+                    Err(e) => println!("Err {}", e),
+                },
+                Err(e) => println!("Err {}", e),
+            };
         }
     }
 
+    if let Some(file_path) = matches.get_one::<String>("cosign") {
+        cosign_multisig(&matches, file_path, signer_spec, from);
+    }
+
     // Нова логіка для --replenishing
     if let Some(values) = matches.get_many::<String>("replenishing") {
         let values: Vec<&String> = values.collect();
@@ -261,12 +744,14 @@ fn main() {
         println!("  Source Transaction Hash: {}", source_transaction_hash);
 
         match tx::replenishing(
-            &recipient_address,
-            &amount_str,
+            recipient_address,
+            amount_str,
             currency_id,
-            &source_transaction_hash,
+            source_transaction_hash,
         ) {
-            Ok(transaction) => match tx::store_transaction(&transaction) {
+            Ok(transaction) => match db::Store::open()
+                .and_then(|mut store| tx::store_transaction(&mut store, &transaction))
+            {
                 Ok(_) => {
                     match save_transaction_as_json(&transaction) {
                         Ok(_) => println!("Ok"),
@@ -289,48 +774,48 @@ fn main() {
             addr.clone()
         } else {
             println!("No wallet address provided. Showing your own wallet balance requires the password:");
-            let password = match get_or_prompt_password(&matches) {
-                Some(p) => p,
-                None => {
-                    eprintln!("Cannot read password. Aborting balance check.");
-                    return;
-                }
-            };
-            // Перевіряємо пароль і дістаємо адресу з БД
-            match db::is_password_correct(password.as_bytes()) {
-                Ok(true) => match keys::get_wallet_address(password.as_bytes()) {
-                    Ok(addr) => addr,
-                    Err(e) => {
-                        eprintln!("Error retrieving your wallet address: {:?}", e);
-                        return;
-                    }
-                },
-                Ok(false) => {
-                    eprintln!("Incorrect password.");
-                    return;
-                }
-                Err(e) => {
-                    eprintln!("Error checking password: {:?}", e);
-                    return;
-                }
+            match own_wallet_address(&matches, from) {
+                Some(address) => address,
+                None => return,
             }
         };
 
-        // Отримуємо 32 байти балансу з локальної БД
+        // Отримуємо баланси по кожній валюті з локальної БД
         match db::get_wallet_balance(&address) {
-            Ok(balance_bytes) => {
-                let big_balance = U256::from_big_endian(&balance_bytes);
-
-                // Форматуємо з урахуванням 18 дец. знаків (наприклад, якщо це wei)
-                match format_units(big_balance, 18) {
-                    Ok(value) => println!("Balance of {}:\n{}", address, value),
-                    Err(e) => eprintln!("Error formatting balance: {:?}", e),
+            Ok(balances) => {
+                println!("Balance of {}:", address);
+                if balances.is_empty() {
+                    println!("0");
+                }
+                for (currency_id, big_balance) in balances {
+                    print_asset_balance(currency_id, big_balance);
                 }
             }
             Err(e) => eprintln!("Error retrieving balance: {:?}", e),
         }
     }
 
+    if matches.get_flag("rebuild-balances") {
+        rebuild_balances();
+    }
+
+    // --history [address?] з фільтрами
+    if matches.contains_id("history") {
+        let address = match matches.get_one::<String>("history") {
+            Some(address) => address.clone(),
+            None => {
+                println!(
+                    "No wallet address provided. Showing your own history requires the password:"
+                );
+                match own_wallet_address(&matches, from) {
+                    Some(address) => address,
+                    None => return,
+                }
+            }
+        };
+        show_history(&matches, address);
+    }
+
     // Логіка для --import <file_path>
     if let Some(file_path) = matches.get_one::<String>("import") {
         println!("Import transaction from file: {}", file_path);
@@ -339,7 +824,9 @@ fn main() {
                 println!("File content:\n{}", content);
                 // Use `?` safely now that main() returns `Result`
                 match tx::json_to_txpb(&content) {
-                    Ok(tx_db) => match tx::store_transaction(&tx_db) {
+                    Ok(tx_db) => match db::Store::open()
+                        .and_then(|mut store| tx::store_transaction(&mut store, &tx_db))
+                    {
                         Ok(_) => (),
                         Err(e) => {
                             eprintln!("Error checking password: {:?}", e);
@@ -359,6 +846,827 @@ fn main() {
     greet();
 }
 
+// Встановлює пароль і створює гаманець з нової BIP-39 фрази, яку показує користувачу
+fn create_wallet(password: &str, passphrase: Option<&str>) {
+    let property_key = match db::set_password(password.as_bytes()) {
+        Ok(property_key) => property_key,
+        Err(e) => {
+            eprintln!("Error saving password: {:?}", e);
+            return;
+        }
+    };
+    println!("Password has been successfully set.");
+
+    match keys::generate_save_keypair(&property_key, passphrase) {
+        Ok((address, phrase)) => {
+            println!("Wallet Address: {:?}", address);
+            println!("Write down the recovery phrase and keep it offline.");
+            println!(
+                "It is the only way to restore the wallet if {} is lost:",
+                db::database_path().display()
+            );
+            println!("{}", phrase.as_str());
+        }
+        Err(e) => eprintln!("Error creating wallet: {:?}", e),
+    }
+}
+
+// Відновлює гаманець з BIP-39 фрази; база даних не повинна містити іншого гаманця
+fn restore_wallet(matches: &clap::ArgMatches, passphrase: Option<&str>) {
+    if !is_database_fresh() {
+        return;
+    }
+
+    println!("Enter the recovery phrase:");
+    let phrase = match read_password() {
+        Ok(phrase) => phrase,
+        Err(e) => {
+            eprintln!("Error reading recovery phrase: {:?}", e);
+            return;
+        }
+    };
+
+    // Перевіряємо фразу до того, як щось записати в базу даних
+    if let Err(e) = keys::keypair_from_mnemonic(&phrase, passphrase) {
+        eprintln!("Invalid recovery phrase: {}", e);
+        return;
+    }
+
+    let Some(property_key) = set_new_password(matches) else {
+        return;
+    };
+
+    match keys::restore_keypair(&phrase, passphrase, &property_key) {
+        Ok(address) => println!("Wallet restored: {:?}", address),
+        Err(e) => eprintln!("Error restoring wallet: {:?}", e),
+    }
+}
+
+// Імпортує ключ з V3 keystore у свіжу базу даних
+fn import_keystore(matches: &clap::ArgMatches, file_path: &str) {
+    if !is_database_fresh() {
+        return;
+    }
+
+    let keystore_password = match matches.get_one::<String>("keystore-password") {
+        Some(password) => password.clone(),
+        None => {
+            println!("Enter keystore password:");
+            match read_password() {
+                Ok(password) => password,
+                Err(e) => {
+                    eprintln!("Error reading keystore password: {:?}", e);
+                    return;
+                }
+            }
+        }
+    };
+
+    // Перевіряємо keystore до того, як щось записати в базу даних
+    if let Err(e) = keys::decrypt_keystore(Path::new(file_path), &keystore_password) {
+        eprintln!("Cannot decrypt keystore {}: {}", file_path, e);
+        return;
+    }
+
+    let Some(property_key) = set_new_password(matches) else {
+        return;
+    };
+
+    match keys::import_keystore(Path::new(file_path), &keystore_password, &property_key) {
+        Ok(address) => println!("Wallet imported: {:?}", address),
+        Err(e) => eprintln!("Error importing keystore: {:?}", e),
+    }
+}
+
+// Відновлює гаманець з частин резервної копії (по одній у рядку stdin) у свіжу базу даних
+fn restore_shares(matches: &clap::ArgMatches) {
+    if !is_database_fresh() {
+        return;
+    }
+
+    // Поріг відомий з першої частини; частину з помилкою можна ввести ще раз
+    let mut shares: Vec<keys::BackupShare> = Vec::new();
+    let mut line = String::new();
+    loop {
+        match shares.first() {
+            Some(first) if shares.len() >= first.threshold as usize => break,
+            Some(first) => eprintln!(
+                "Enter backup share {} of {}:",
+                shares.len() + 1,
+                first.threshold
+            ),
+            None => eprintln!("Enter backup share 1:"),
+        }
+
+        line.clear();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                eprintln!("Not enough backup shares.");
+                return;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Error reading backup share: {:?}", e);
+                return;
+            }
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match keys::BackupShare::parse(&line) {
+            Ok(share) => shares.push(share),
+            Err(e) => eprintln!("Invalid backup share: {}", e),
+        }
+    }
+
+    // Перевіряємо частини до того, як щось записати в базу даних
+    if let Err(e) = keys::check_backup(&shares) {
+        eprintln!("Cannot restore the wallet from these shares: {}", e);
+        return;
+    }
+
+    let Some(property_key) = set_new_password(matches) else {
+        return;
+    };
+
+    match keys::restore_backup(&shares, &property_key) {
+        Ok(address) => println!("Wallet restored: {:?}", address),
+        Err(e) => eprintln!("Error restoring wallet: {:?}", e),
+    }
+}
+
+// Експортує ключ рахунку в новий V3 keystore під окремим паролем
+fn export_keystore(
+    matches: &clap::ArgMatches,
+    file_path: &str,
+    from: Option<&str>,
+    property_key: &db::PropertyKey,
+) {
+    let account = match keys::resolve_account(from, property_key) {
+        Ok(account) => account,
+        Err(e) => {
+            eprintln!("Error retrieving account: {}", e);
+            return;
+        }
+    };
+
+    let keystore_password = match matches.get_one::<String>("keystore-password") {
+        Some(password) => password.clone(),
+        None => {
+            println!("Choose a password for the keystore.");
+            match prompt_for_password() {
+                Some(password) => password,
+                None => return,
+            }
+        }
+    };
+
+    match keys::export_keystore(
+        Path::new(file_path),
+        account.account_index,
+        &keystore_password,
+        property_key,
+    ) {
+        Ok(address) => println!(
+            "Account {} ({:?}) exported to {}",
+            account.label, address, file_path
+        ),
+        Err(e) => eprintln!("Error exporting keystore: {}", e),
+    }
+}
+
+// Підписувач для --send і --withdraw. Пароль потрібен лише локальному ключу:
+// агент розблокування і зовнішні підписувачі обходяться без нього
+fn resolve_signer(
+    matches: &clap::ArgMatches,
+    signer_spec: Option<&str>,
+    from: Option<&str>,
+) -> Option<Box<dyn signer::Signer>> {
+    let spec = signer::resolve_spec(signer_spec);
+    let result = if spec == signer::LOCAL {
+        let password = get_or_prompt_password(matches)?;
+        match db::unlock(password.as_bytes()) {
+            Ok(Some(property_key)) => signer::from_spec(Some(spec), from, Some(property_key)),
+            Ok(None) => {
+                eprintln!("Incorrect password.");
+                return None;
+            }
+            Err(e) => {
+                eprintln!("Error checking password: {:?}", e);
+                return None;
+            }
+        }
+    } else {
+        signer::from_spec(Some(spec), from, None)
+    };
+
+    match result {
+        Ok(signer) => Some(signer),
+        Err(e) => {
+            eprintln!("Error creating signer: {}", e);
+            None
+        }
+    }
+}
+
+// Створює мультипідписний рахунок; підписантів можна вказати в будь-якому порядку
+fn create_multisig(label: &str, threshold: &str, signers: &str) {
+    let threshold: u32 = match threshold.parse() {
+        Ok(threshold) => threshold,
+        Err(_) => {
+            eprintln!("Invalid threshold: '{}'. Must be a valid u32.", threshold);
+            return;
+        }
+    };
+    let signers = match signers
+        .split(',')
+        .map(|signer| signer.trim().parse::<Address>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(signers) => signers,
+        Err(e) => {
+            eprintln!("Invalid signer address: {}", e);
+            return;
+        }
+    };
+
+    match multisig::create_account(label, threshold, &signers) {
+        Ok(account) => println!(
+            "Multisig account created: {}\t{}\t{}-of-{}",
+            account.label,
+            account.address,
+            account.threshold,
+            account.signers.len()
+        ),
+        Err(e) => eprintln!("Error creating multisig account: {}", e),
+    }
+}
+
+// Формує транзакцію мультипідписного рахунку з першим підписом і зберігає її у файл .osnms
+#[allow(clippy::too_many_arguments)]
+fn propose_multisig(
+    name: &str,
+    signer: &dyn signer::Signer,
+    network_id: &str,
+    transaction_type: u32,
+    amount_str: &str,
+    currency_id: u32,
+    recipient: &str,
+) {
+    let proposed = multisig::resolve_account(name).and_then(|account| {
+        tx::propose_multisig(
+            &db::Store::open()?,
+            &account,
+            signer,
+            network_id,
+            transaction_type,
+            amount_str,
+            currency_id,
+            recipient,
+        )
+    });
+    let transaction = match proposed {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            eprintln!("Error proposing multisig transaction: {}", e);
+            return;
+        }
+    };
+
+    let file_path = hex::encode(&transaction.transaction_hash) + ".osnms";
+    match save_multisig_transaction(&transaction, &file_path) {
+        Ok(()) => report_multisig_progress(&transaction, 1, &file_path),
+        Err(e) => eprintln!("Error saving multisig transaction: {}", e),
+    }
+}
+
+// Показує підписанту, що саме він підписує, і додає його підпис у той самий файл
+fn cosign_multisig(
+    matches: &clap::ArgMatches,
+    file_path: &str,
+    signer_spec: Option<&str>,
+    from: Option<&str>,
+) {
+    let mut transaction =
+        match import_transaction(file_path).and_then(|json| tx::json_to_txpb(&json)) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                eprintln!("Error reading multisig transaction: {}", e);
+                return;
+            }
+        };
+
+    let tx_db = tx::to_transaction_db(&transaction);
+    println!("Multisig transaction {}:", tx_db.transaction_hash);
+    println!("  Type: {}", tx_db.transaction_type);
+    println!("  Network: {}", tx_db.network_id.as_deref().unwrap_or(""));
+    println!("  From: {}", tx_db.sender_address.as_deref().unwrap_or(""));
+    println!("  To: {}", tx_db.recipient_address);
+    print!("  Amount: ");
+    print_asset_balance(
+        transaction.currency_id,
+        U256::from_big_endian(&transaction.amount),
+    );
+
+    let Some(signer) = resolve_signer(matches, signer_spec, from) else {
+        return;
+    };
+    let signed = match tx::cosign_multisig(&mut transaction, signer.as_ref()) {
+        Ok(signed) => signed,
+        Err(e) => {
+            eprintln!("Error signing multisig transaction: {}", e);
+            return;
+        }
+    };
+    match save_multisig_transaction(&transaction, file_path) {
+        Ok(()) => report_multisig_progress(&transaction, signed, file_path),
+        Err(e) => eprintln!("Error saving multisig transaction: {}", e),
+    }
+}
+
+fn report_multisig_progress(transaction: &TransactionPb, signed: usize, file_path: &str) {
+    let threshold = transaction
+        .multisig
+        .as_ref()
+        .map_or(0, |multisig| multisig.threshold as usize);
+    println!(
+        "{} of {} required signatures collected in {}.",
+        signed, threshold, file_path
+    );
+    if signed >= threshold {
+        println!(
+            "The transaction is ready: submit it with --import {}",
+            file_path
+        );
+    } else {
+        println!("Pass the file to the other signers to --cosign it.");
+    }
+}
+
+fn save_multisig_transaction(
+    transaction: &TransactionPb,
+    file_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tx_json = tx::tx_to_json(&tx::to_transaction_db(transaction))?;
+    std::fs::write(file_path, tx_json)?;
+    Ok(())
+}
+
+// Виводить профілі каталогу даних; активний позначено зірочкою
+fn list_profiles(data_dir: &Path, active_name: &str) {
+    match profile::list(data_dir) {
+        Ok(profiles) if profiles.is_empty() => {
+            println!("No profiles in {} yet.", data_dir.display())
+        }
+        Ok(profiles) => {
+            for profile in profiles {
+                let marker = if profile.name == active_name {
+                    "*"
+                } else {
+                    " "
+                };
+                let wallet = if profile.db_path().exists() {
+                    profile.db_path().display().to_string()
+                } else {
+                    "-".to_string()
+                };
+                println!(
+                    "{} {}\t{}\t{}\t{}",
+                    marker,
+                    profile.name,
+                    profile.settings.server_url,
+                    profile.settings.network_id.as_deref().unwrap_or("-"),
+                    wallet
+                );
+            }
+        }
+        Err(e) => eprintln!("Error listing profiles: {}", e),
+    }
+}
+
+// Зберігає сервер і мережу активного профілю з --set-server і --set-network
+fn update_profile(matches: &clap::ArgMatches) {
+    let Some(mut active) = profile::active() else {
+        return;
+    };
+    if let Some(server_url) = matches.get_one::<String>("set-server") {
+        active.settings.server_url = server_url.clone();
+    }
+    if let Some(network_id) = matches.get_one::<String>("set-network") {
+        active.settings.network_id = Some(network_id.clone());
+    }
+    match active.save_settings() {
+        Ok(()) => {
+            println!(
+                "Profile '{}' uses server {} and network {}.",
+                active.name,
+                active.settings.server_url,
+                active
+                    .settings
+                    .network_id
+                    .as_deref()
+                    .unwrap_or(tx::DEFAULT_NETWORK_ID)
+            );
+            profile::activate(active);
+        }
+        Err(e) => eprintln!("Error saving profile '{}': {}", active.name, e),
+    }
+}
+
+// Раніше база лежала в поточному каталозі: не даємо непомітно почати з порожнього гаманця
+fn is_legacy_database_left(active: &profile::Profile) -> bool {
+    if !Path::new(db::DB_PATH).exists() || active.db_path().exists() {
+        return false;
+    }
+    eprintln!(
+        "{} in the current directory is not used anymore. Move it to {} to keep this wallet in profile '{}', or run from another directory to start a new one.",
+        db::DB_PATH,
+        active.db_path().display(),
+        active.name
+    );
+    true
+}
+
+// Запускає агента розблокування окремим процесом. Пароль передається через stdin,
+// а не в аргументах, щоб його не було видно в списку процесів. Агент працює з тим самим профілем
+fn start_agent(password: &str, timeout: u64, data_dir: &Path, profile_name: &str) {
+    let spawned = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .arg("--run-agent")
+            .arg(timeout.to_string())
+            .arg("--data-dir")
+            .arg(data_dir)
+            .arg("--profile")
+            .arg(profile_name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // Власна група процесів: агент переживе Ctrl+C у терміналі
+            .process_group(0)
+            .spawn()
+    });
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Error starting the unlock agent: {}", e);
+            return;
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", password);
+    }
+
+    // Агент відповідає одним рядком, коли ключі розшифровані і сокет готовий
+    let mut status = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        let _ = BufReader::new(stdout).read_line(&mut status);
+    }
+    match status.trim() {
+        "ok" => println!(
+            "Wallet unlocked for {} seconds (agent socket: {}).",
+            timeout,
+            agent::socket_path().display()
+        ),
+        "" => eprintln!("The unlock agent exited unexpectedly."),
+        error => eprintln!("Error starting the unlock agent: {}", error),
+    }
+}
+
+// Процес агента: розшифровує ключі паролем зі stdin і обслуговує сокет до тайм-ауту або --lock
+fn run_agent(timeout: u64) {
+    let mut password = String::new();
+    if let Err(e) = std::io::stdin().read_line(&mut password) {
+        println!("{}", e);
+        return;
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    // Ключ властивостей потрібен лише на час розшифрування ключів рахунків
+    let started = db::unlock(password.as_bytes())
+        .and_then(|property_key| property_key.ok_or_else(|| "Incorrect password".into()))
+        .and_then(|property_key| agent::Keyring::unlock(&property_key))
+        .and_then(|keyring| {
+            let listener = agent::bind(&agent::socket_path())?;
+            Ok((keyring, listener))
+        });
+    let (keyring, listener) = match started {
+        Ok(started) => started,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    println!("ok");
+    if let Err(e) = agent::serve(listener, keyring, Duration::from_secs(timeout)) {
+        log::error!("Unlock agent stopped: {}", e);
+    }
+}
+
+// Виводить сирий приватний ключ рахунку, лише якщо користувач повторив його мітку
+fn export_private_key(from: Option<&str>, property_key: &db::PropertyKey) {
+    let account = match keys::resolve_account(from, property_key) {
+        Ok(account) => account,
+        Err(e) => {
+            eprintln!("Error retrieving account: {}", e);
+            return;
+        }
+    };
+
+    eprintln!("WARNING: anyone who sees this key can spend the funds of the account.");
+    eprintln!("Prefer --export-keystore unless the raw key is really needed.");
+    eprint!("Type the account label '{}' to continue: ", account.label);
+    let _ = std::io::stderr().flush();
+
+    let mut confirmation = String::new();
+    if std::io::stdin().read_line(&mut confirmation).is_err()
+        || confirmation.trim() != account.label
+    {
+        eprintln!("Export cancelled.");
+        return;
+    }
+
+    match keys::export_private_key(account.account_index, property_key) {
+        Ok(private_key) => println!("{}", private_key.as_str()),
+        Err(e) => eprintln!("Error exporting private key: {}", e),
+    }
+}
+
+// Друкує частини резервної копії гаманця — кожну треба зберігати окремо
+fn backup_shares(threshold: u8, count: u8, property_key: &db::PropertyKey) {
+    match keys::split_backup(threshold, count, property_key) {
+        Ok(shares) => {
+            eprintln!(
+                "Any {} of these {} shares restore the wallet with --restore-shares; fewer reveal nothing.",
+                threshold, count
+            );
+            eprintln!("Keep each share in a separate place.");
+            for share in shares {
+                println!("{}", share.to_text().as_str());
+            }
+        }
+        Err(e) => eprintln!("Error creating backup shares: {}", e),
+    }
+}
+
+// Перевіряє поточний пароль і перешифровує сховище новим
+fn change_password(matches: &clap::ArgMatches) {
+    let Some(old_password) = get_or_prompt_password(matches) else {
+        return;
+    };
+    let property_key = match db::unlock(old_password.as_bytes()) {
+        Ok(Some(property_key)) => property_key,
+        Ok(None) => {
+            eprintln!("Incorrect password.");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error checking password: {:?}", e);
+            return;
+        }
+    };
+
+    let new_password = match matches.get_one::<String>("new-password") {
+        Some(password) => password.clone(),
+        None => match prompt_for_password() {
+            Some(password) => password,
+            None => return,
+        },
+    };
+
+    match db::change_password(&property_key, new_password.as_bytes()) {
+        Ok(()) => println!("Password has been successfully changed."),
+        Err(e) => eprintln!("Error changing password: {}", e),
+    }
+}
+
+// Відновлювати чи імпортувати гаманець можна лише в базу даних без іншого гаманця
+fn is_database_fresh() -> bool {
+    if db::is_password_set() {
+        eprintln!(
+            "{} already contains a wallet. Use a fresh database or another --profile.",
+            db::database_path().display()
+        );
+        return false;
+    }
+    true
+}
+
+// Пароль нового гаманця: з --set-password або з консолі. Повертає ключ властивостей для запису гаманця
+fn set_new_password(matches: &clap::ArgMatches) -> Option<db::PropertyKey> {
+    let password = match matches.get_one::<String>("set-password") {
+        Some(password) => password.clone(),
+        None => prompt_for_password()?,
+    };
+    match db::set_password(password.as_bytes()) {
+        Ok(property_key) => Some(property_key),
+        Err(e) => {
+            eprintln!("Error saving password: {:?}", e);
+            None
+        }
+    }
+}
+
+// Адреса рахунку --from (за замовчуванням main) після перевірки пароля
+fn own_wallet_address(matches: &clap::ArgMatches, from: Option<&str>) -> Option<String> {
+    let password = match get_or_prompt_password(matches) {
+        Some(p) => p,
+        None => {
+            eprintln!("Cannot read password. Aborting.");
+            return None;
+        }
+    };
+    // Перевіряємо пароль і дістаємо адресу з БД
+    match db::unlock(password.as_bytes()) {
+        Ok(Some(property_key)) => match keys::resolve_account(from, &property_key) {
+            Ok(account) => Some(account.address),
+            Err(e) => {
+                eprintln!("Error retrieving your wallet address: {:?}", e);
+                None
+            }
+        },
+        Ok(None) => {
+            eprintln!("Incorrect password.");
+            None
+        }
+        Err(e) => {
+            eprintln!("Error checking password: {:?}", e);
+            None
+        }
+    }
+}
+
+// Таблиця транзакцій адреси за фільтрами --direction, --currency, --type, --since, --until
+// і сторінками по --limit; наступна сторінка — з --cursor, який виводиться під таблицею
+fn show_history(matches: &clap::ArgMatches, address: String) {
+    let filter = match history_filter(matches, address) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Invalid history filter: {}", e);
+            return;
+        }
+    };
+    let cursor = match matches
+        .get_one::<String>("cursor")
+        .map(|cursor| cursor.parse::<db::HistoryCursor>())
+        .transpose()
+    {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let limit = *matches.get_one::<usize>("limit").unwrap_or(&20);
+    let page = match db::get_transaction_history(&filter, cursor.as_ref(), limit) {
+        Ok(page) => page,
+        Err(e) => {
+            eprintln!("Error retrieving transaction history: {}", e);
+            return;
+        }
+    };
+
+    let address = filter.address.unwrap_or_default();
+    println!("History of {}:", address);
+    if page.transactions.is_empty() {
+        println!("No transactions.");
+        return;
+    }
+    println!(
+        "{:<19}  {:<9}  {:>30}  {:<6}  {:<42}  HASH",
+        "TIME (UTC)", "TYPE", "AMOUNT", "ASSET", "COUNTERPARTY"
+    );
+    for tx_db in &page.transactions {
+        let outgoing = tx_db.sender_address.as_deref() == Some(address.as_str());
+        let counterparty = if outgoing {
+            tx_db.recipient_address.as_str()
+        } else {
+            // Поповнення приходить із зовнішнього блокчейну, відправника в Osanwe немає
+            tx_db.sender_address.as_deref().unwrap_or("(deposit)")
+        };
+        let (amount, symbol) = history_amount(tx_db);
+        println!(
+            "{:<19}  {:<9}  {:>30}  {:<6}  {:<42}  {}",
+            format_timestamp(tx_db.timestamp),
+            transaction_type_name(tx_db.transaction_type),
+            format!("{}{}", if outgoing { "-" } else { "+" }, amount),
+            symbol,
+            counterparty,
+            tx_db.transaction_hash
+        );
+    }
+    if let Some(next_cursor) = page.next_cursor {
+        println!("More transactions: --cursor {}", next_cursor);
+    }
+}
+
+fn history_filter(
+    matches: &clap::ArgMatches,
+    address: String,
+) -> Result<db::HistoryFilter, Box<dyn std::error::Error>> {
+    // У базі адреси в нижньому регістрі, а користувач може ввести EIP-55
+    let address = address
+        .trim()
+        .parse::<Address>()
+        .map_err(|e| format!("Invalid address '{}': {}", address, e))?;
+    Ok(db::HistoryFilter {
+        address: Some(format!("{:?}", address)),
+        direction: matches
+            .get_one::<String>("direction")
+            .map(|direction| direction.parse())
+            .transpose()?,
+        currency_id: matches.get_one::<u32>("currency").copied(),
+        transaction_type: matches
+            .get_one::<String>("type")
+            .map(|name| parse_transaction_type(name))
+            .transpose()?,
+        since: matches
+            .get_one::<String>("since")
+            .map(|date| parse_history_time(date))
+            .transpose()?,
+        until: matches
+            .get_one::<String>("until")
+            .map(|date| parse_history_time(date))
+            .transpose()?,
+    })
+}
+
+fn parse_transaction_type(name: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    match name {
+        "replenish" => Ok(tx::TX_TYPE_REPLENISH),
+        "transfer" => Ok(tx::TX_TYPE_TRANSFER),
+        "withdraw" => Ok(tx::TX_TYPE_WITHDRAW),
+        other => Err(format!(
+            "Unknown transaction type '{}': use replenish, transfer or withdraw",
+            other
+        )
+        .into()),
+    }
+}
+
+fn transaction_type_name(transaction_type: u32) -> String {
+    match transaction_type {
+        tx::TX_TYPE_REPLENISH => "replenish".to_owned(),
+        tx::TX_TYPE_TRANSFER => "transfer".to_owned(),
+        tx::TX_TYPE_WITHDRAW => "withdraw".to_owned(),
+        other => other.to_string(),
+    }
+}
+
+// Дата YYYY-MM-DD (початок доби UTC) або час RFC 3339 → мілісекунди Unix, як `timestamp` транзакцій
+fn parse_history_time(value: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let datetime = match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => date.and_time(chrono::NaiveTime::MIN).and_utc(),
+        Err(_) => chrono::DateTime::parse_from_rfc3339(value)
+            .map_err(|_| format!("Invalid time '{}': use YYYY-MM-DD or RFC 3339", value))?
+            .to_utc(),
+    };
+    u64::try_from(datetime.timestamp_millis())
+        .map_err(|_| format!("Time '{}' is before 1970", value).into())
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|datetime| datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+// Сума у звичних одиницях валюти і її символ; невідома валюта — у мінімальних одиницях
+fn history_amount(tx_db: &tx::TransactionDb) -> (String, String) {
+    let amount =
+        U256::from_str_radix(tx_db.amount.trim_start_matches("0x"), 16).unwrap_or_default();
+    match db::get_cryptoasset_by_id(tx_db.currency_id) {
+        Ok(Some(asset)) => match format_units(amount, asset.decimals) {
+            Ok(value) => (value, asset.symbol),
+            Err(_) => (amount.to_string(), asset.symbol),
+        },
+        _ => (amount.to_string(), tx_db.currency_id.to_string()),
+    }
+}
+
+// Перераховує таблицю балансів з історії транзакцій і показує, що в ній було не так
+fn rebuild_balances() {
+    match db::rebuild_balances() {
+        Ok(mismatches) if mismatches.is_empty() => {
+            println!("Balances verified: they agree with the transaction history.")
+        }
+        Ok(mismatches) => {
+            println!("Balances that disagreed with the transaction history (minimal units):");
+            for mismatch in &mismatches {
+                println!(
+                    "  {}\t{}\tstored {}\trecomputed {}",
+                    mismatch.address, mismatch.currency_id, mismatch.stored, mismatch.recomputed
+                );
+            }
+            println!("{} balance(s) rebuilt.", mismatches.len());
+        }
+        Err(e) => eprintln!("Error rebuilding balances: {}", e),
+    }
+}
+
 // Допоміжна функція - отримати пароль або прочитати з консолі, якщо не переданий
 fn get_or_prompt_password(matches: &clap::ArgMatches) -> Option<String> {
     if let Some(pass) = matches.get_one::<String>("password") {
@@ -374,6 +1682,30 @@ fn get_or_prompt_password(matches: &clap::ArgMatches) -> Option<String> {
     }
 }
 
+// Виводить один рядок балансу: id валюти, її символ і суму
+fn print_asset_balance(currency_id: u32, big_balance: U256) {
+    let asset = match db::get_cryptoasset_by_id(currency_id) {
+        Ok(Some(asset)) => asset,
+        Ok(None) => {
+            eprintln!(
+                "Unknown currency_id {} with balance {}",
+                currency_id, big_balance
+            );
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error retrieving crypto asset {}: {:?}", currency_id, e);
+            return;
+        }
+    };
+
+    // Форматуємо з урахуванням десяткових знаків конкретної валюти
+    match format_units(big_balance, asset.decimals) {
+        Ok(value) => println!("{}\t{}\t{}", currency_id, asset.symbol, value),
+        Err(e) => eprintln!("Error formatting balance: {:?}", e),
+    }
+}
+
 // Функція імпорту транзакції з файлу (поки що лише зчитує весь файл як текст і повертає)
 fn import_transaction(file_path: &str) -> Result<String, Box<dyn std::error::Error>> {
     let mut file = File::open(file_path)?;
@@ -383,7 +1715,7 @@ fn import_transaction(file_path: &str) -> Result<String, Box<dyn std::error::Err
 }
 
 fn save_transaction_as_json(transaction: &TransactionPb) -> Result<(), Box<dyn std::error::Error>> {
-    let tx_db = tx::to_transaction_db(&transaction);
+    let tx_db = tx::to_transaction_db(transaction);
     let tx_json = tx::tx_to_json(&tx_db)?;
 
     let file_path = hex::encode(transaction.transaction_hash.clone()) + ".osnjs";
@@ -1,7 +1,7 @@
 fn main() {
     tonic_build::configure()
         .compile_protos(
-            &["proto/transaction_pb.proto"], // Шлях до .proto файлу
+            &["proto/transaction_pb.proto", "proto/signer.proto"], // Шляхи до .proto файлів
             &["proto/"], // Директорія з .proto файлами
         )
         .expect("Failed to compile Protobuf files");
@@ -0,0 +1,471 @@
+use crate::db::{Account, PropertyKey};
+use crate::keys;
+use crate::signer::{check_signature, ExternalResponse, Signer};
+use ethers::types::transaction::eip712::TypedData;
+use ethers::types::{Address, H256};
+use hex::{decode, encode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Скільки агент тримає ключі, якщо при розблокуванні не вказано інше (15 хвилин)
+pub const DEFAULT_TIMEOUT_SECS: u64 = 900;
+
+/// Змінна оточення, що задає шлях до сокета агента
+pub const SOCKET_ENV: &str = "OSANWE_AGENT_SOCK";
+
+/// Скільки клієнт чекає на відповідь агента
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Як часто агент без клієнтів перевіряє, чи не сплив час
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Запит до агента — один рядок JSON. Підпис і адреса мають той самий формат, що й у
+/// протоколі [`crate::signer::ExternalSigner`]; відповідь — [`ExternalResponse`].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum AgentRequest {
+    Address {
+        key_id: String,
+    },
+    SignDigest {
+        key_id: String,
+        digest: String,
+    },
+    /// Перевірка, що агент живий
+    Status,
+    /// Забути ключі і завершитись
+    Lock,
+}
+
+/// Шлях до сокета: `OSANWE_AGENT_SOCK`, інакше `$XDG_RUNTIME_DIR/osanwe/agent.sock`,
+/// інакше `<tmp>/osanwe-<uid>/agent.sock`. В активного профілю свій агент:
+/// `agent-<профіль>.sock` у тому самому каталозі.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("osanwe"),
+        None => std::env::temp_dir().join(format!("osanwe-{}", current_uid())),
+    };
+    match crate::profile::active() {
+        Some(profile) => dir.join(format!("agent-{}.sock", profile.name)),
+        None => dir.join("agent.sock"),
+    }
+}
+
+/// Рахунок разом з розшифрованим ключем.
+struct UnlockedAccount {
+    account: Account,
+    private_key: Zeroizing<Vec<u8>>,
+}
+
+/// Розшифровані ключі всіх рахунків гаманця. Пам'ять ключів затирається при Drop.
+pub struct Keyring {
+    accounts: Vec<UnlockedAccount>,
+}
+
+impl Keyring {
+    /// Розшифровує ключі всіх рахунків ключем властивостей гаманця (див. [`crate::db::unlock`]).
+    pub fn unlock(property_key: &PropertyKey) -> Result<Self, Box<dyn Error>> {
+        let mut accounts = Vec::new();
+        for account in keys::list_accounts(property_key)? {
+            let private_key = keys::load_private_key(account.account_index, property_key)?;
+            accounts.push(UnlockedAccount {
+                account,
+                private_key,
+            });
+        }
+        Ok(Keyring { accounts })
+    }
+
+    /// Рахунок за міткою або індексом — як у [`keys::resolve_account`].
+    fn find(&self, key_id: &str) -> Result<&UnlockedAccount, Box<dyn Error>> {
+        self.accounts
+            .iter()
+            .find(|UnlockedAccount { account, .. }| {
+                account.label == key_id || account.account_index.to_string() == key_id
+            })
+            .ok_or_else(|| format!("Unknown account: {}", key_id).into())
+    }
+
+    fn handle(&self, request: &AgentRequest) -> Result<ExternalResponse, Box<dyn Error>> {
+        match request {
+            AgentRequest::Address { key_id } => Ok(ExternalResponse {
+                address: Some(self.find(key_id)?.account.address.clone()),
+                ..Default::default()
+            }),
+            AgentRequest::SignDigest { key_id, digest } => {
+                let digest = decode(digest.trim_start_matches("0x"))?;
+                if digest.len() != 32 {
+                    return Err("Digest must be 32 bytes".into());
+                }
+                let private_key = &self.find(key_id)?.private_key;
+                let signature =
+                    keys::sign_digest_with_private_key(private_key, H256::from_slice(&digest))?;
+                Ok(ExternalResponse {
+                    signature: Some(format!("0x{}", encode(signature))),
+                    ..Default::default()
+                })
+            }
+            AgentRequest::Status | AgentRequest::Lock => Ok(ExternalResponse::default()),
+        }
+    }
+}
+
+/// Створює сокет агента. Каталог сокета доступний лише власнику, сам сокет — 0600.
+/// Сокет, що лишився від завершеного агента, видаляється; живий агент — помилка.
+pub fn bind(path: &Path) -> Result<UnixListener, Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        ensure_private_dir(dir)?;
+    }
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err("The wallet is already unlocked; use --lock first".into());
+        }
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Обслуговує запити, доки не надійде `lock` або не спливе `timeout`, після чого
+/// видаляє сокет. Ключі затираються разом з `keyring`.
+pub fn serve(
+    listener: UnixListener,
+    keyring: Keyring,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let path = listener.local_addr()?.as_pathname().map(Path::to_path_buf);
+    let deadline = Instant::now() + timeout;
+    listener.set_nonblocking(true)?;
+
+    while Instant::now() < deadline {
+        match listener.accept() {
+            Ok((stream, _)) => match handle_connection(stream, &keyring) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => log::warn!("Agent request failed: {}", e),
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    drop(keyring);
+    if let Some(path) = path {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// Обробляє одне з'єднання; `true` — агент треба зупинити.
+fn handle_connection(stream: UnixStream, keyring: &Keyring) -> Result<bool, Box<dyn Error>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    // Права на сокет — перша лінія захисту, облікові дані співрозмовника — друга
+    let peer_uid = peer_uid(&stream)?;
+    if peer_uid != current_uid() {
+        write_response(
+            &stream,
+            &ExternalResponse {
+                error: Some("Permission denied".into()),
+                ..Default::default()
+            },
+        )?;
+        return Err(format!("Rejected a connection from uid {}", peer_uid).into());
+    }
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let (response, stop) = match serde_json::from_str::<AgentRequest>(&line) {
+        Ok(request) => {
+            let response = keyring
+                .handle(&request)
+                .unwrap_or_else(|e| ExternalResponse {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                });
+            (response, request == AgentRequest::Lock)
+        }
+        Err(e) => (
+            ExternalResponse {
+                error: Some(format!("Invalid request: {}", e)),
+                ..Default::default()
+            },
+            false,
+        ),
+    };
+    write_response(&stream, &response)?;
+    Ok(stop)
+}
+
+fn write_response(
+    mut stream: &UnixStream,
+    response: &ExternalResponse,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(stream, "{}", serde_json::to_string(response)?)?;
+    Ok(())
+}
+
+/// Надсилає запит агенту на `path` і повертає його відповідь.
+pub fn request(path: &Path, request: &AgentRequest) -> Result<ExternalResponse, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(path).map_err(|e| {
+        format!(
+            "Cannot connect to the unlock agent at {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: ExternalResponse =
+        serde_json::from_str(&line).map_err(|e| format!("Invalid unlock agent response: {}", e))?;
+
+    match response.error {
+        Some(error) => Err(format!("Unlock agent refused: {}", error).into()),
+        None => Ok(response),
+    }
+}
+
+/// Чи запущено агента на стандартному сокеті.
+pub fn is_running() -> bool {
+    request(&socket_path(), &AgentRequest::Status).is_ok()
+}
+
+/// Зупиняє агента; `false` — агента не було.
+pub fn lock() -> Result<bool, Box<dyn Error>> {
+    let path = socket_path();
+    if !is_running() {
+        return Ok(false);
+    }
+    request(&path, &AgentRequest::Lock)?;
+    Ok(true)
+}
+
+/// Підписувач, що звертається до агента розблокування.
+pub struct AgentSigner {
+    path: PathBuf,
+    key_id: String,
+}
+
+impl AgentSigner {
+    /// `key_id` — мітка або індекс рахунку.
+    pub fn new(key_id: &str) -> Result<Self, Box<dyn Error>> {
+        Self::with_socket(socket_path(), key_id)
+    }
+
+    pub fn with_socket(path: PathBuf, key_id: &str) -> Result<Self, Box<dyn Error>> {
+        request(&path, &AgentRequest::Status)
+            .map_err(|_| "The wallet is locked: no unlock agent is running (see --unlock)")?;
+        Ok(AgentSigner {
+            path,
+            key_id: key_id.to_owned(),
+        })
+    }
+}
+
+impl Signer for AgentSigner {
+    fn address(&self) -> Result<Address, Box<dyn Error>> {
+        let response = request(
+            &self.path,
+            &AgentRequest::Address {
+                key_id: self.key_id.clone(),
+            },
+        )?;
+        let address = response
+            .address
+            .ok_or("Unlock agent response has no address")?;
+        Ok(address.parse()?)
+    }
+
+    fn sign_digest(&self, digest: H256, _: &TypedData) -> Result<Vec<u8>, Box<dyn Error>> {
+        let response = request(
+            &self.path,
+            &AgentRequest::SignDigest {
+                key_id: self.key_id.clone(),
+                digest: format!("0x{}", encode(digest)),
+            },
+        )?;
+        let signature = response
+            .signature
+            .ok_or("Unlock agent response has no signature")?;
+        check_signature(decode(signature.trim_start_matches("0x"))?)
+    }
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: geteuid не має передумов і завжди успішна
+    unsafe { libc::geteuid() }
+}
+
+/// Створює каталог з правами 0700 або перевіряє, що наявний належить нам і закритий для інших.
+fn ensure_private_dir(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    let metadata = fs::metadata(dir)?;
+    if metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(format!(
+            "Agent directory {} must be owned by the current user and not accessible to others",
+            dir.display()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// uid процесу на іншому кінці сокета.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> Result<u32, Box<dyn Error>> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: буфер і його довжина відповідають SO_PEERCRED
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(credentials.uid)
+}
+
+/// uid процесу на іншому кінці сокета.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> Result<u32, Box<dyn Error>> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    // SAFETY: getpeereid лише записує у передані змінні
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::core::k256::ecdsa::SigningKey;
+    use ethers::utils::keccak256;
+
+    fn test_keyring() -> (Keyring, Address) {
+        let private_key = [0x11u8; 32];
+        let signing_key = SigningKey::from_bytes((&private_key).into()).unwrap();
+        let address = ethers::utils::secret_key_to_address(&signing_key);
+        let account = Account {
+            account_index: 0,
+            label: keys::MAIN_ACCOUNT.to_string(),
+            address: format!("{:?}", address),
+        };
+        let keyring = Keyring {
+            accounts: vec![UnlockedAccount {
+                account,
+                private_key: Zeroizing::new(private_key.to_vec()),
+            }],
+        };
+        (keyring, address)
+    }
+
+    fn test_socket() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("osanwe-agent-{}", uuid::Uuid::new_v4()))
+            .join("agent.sock")
+    }
+
+    #[test]
+    fn test_agent_signs_until_locked() {
+        let (keyring, address) = test_keyring();
+        let path = test_socket();
+        let listener = bind(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(
+            fs::metadata(path.parent().unwrap()).unwrap().mode() & 0o777,
+            0o700
+        );
+
+        let server = std::thread::spawn(move || {
+            serve(listener, keyring, Duration::from_secs(60)).map_err(|e| e.to_string())
+        });
+
+        // Другий агент на тому ж сокеті не запускається
+        assert!(bind(&path).is_err());
+
+        let signer = AgentSigner::with_socket(path.clone(), "0").unwrap();
+        assert_eq!(signer.address().unwrap(), address);
+        let digest = H256::from(keccak256(b"osanwe"));
+        // Агент підписує власним ключем і дані EIP-712 не перевіряє
+        let typed_data = crate::tx::typed_data(&crate::generated::TransactionPb {
+            transaction_type: crate::tx::TX_TYPE_TRANSFER,
+            sender_address: address.as_bytes().to_vec(),
+            recipient_address: vec![0xDD; 20],
+            signature_version: crate::tx::SIGNATURE_VERSION,
+            network_id: "osanwe-test".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        let signature = signer.sign_digest(digest, &typed_data).unwrap();
+        assert_eq!(
+            keys::recover_digest_signer(digest, &signature).unwrap(),
+            address.as_bytes()
+        );
+
+        let unknown = AgentSigner::with_socket(path.clone(), "treasury").unwrap();
+        let error = unknown.address().unwrap_err().to_string();
+        assert!(error.contains("Unknown account"), "{}", error);
+
+        request(&path, &AgentRequest::Lock).unwrap();
+        server.join().unwrap().unwrap();
+        assert!(!path.exists());
+        assert!(AgentSigner::with_socket(path.clone(), "main").is_err());
+
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_agent_forgets_keys_after_timeout() {
+        let (keyring, _) = test_keyring();
+        let path = test_socket();
+        let listener = bind(&path).unwrap();
+
+        serve(listener, keyring, Duration::from_millis(200)).unwrap();
+        assert!(!path.exists());
+
+        // Сокет, що лишився від аварійно завершеного агента, не заважає новому
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        drop(bind(&path).unwrap());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+}
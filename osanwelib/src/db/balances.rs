@@ -0,0 +1,191 @@
+use crate::tx::{TransactionDb, TX_TYPE_WITHDRAW};
+use ethers::types::{I256, U256};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+/// Рядок таблиці `balances`, що розійшовся з перерахунком із `transactions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceMismatch {
+    pub address: String,
+    pub currency_id: u32,
+    /// Що було в таблиці (у мінімальних одиницях)
+    pub stored: I256,
+    /// Що дає історія транзакцій
+    pub recomputed: I256,
+}
+
+/// Адреси, чиї баланси змінює транзакція, і знак зміни. Отримувач виведення (тип 3) —
+/// адреса у зовнішньому блокчейні, тож йому нічого не зараховується.
+fn balance_changes<'a>(
+    transaction_type: u32,
+    sender_address: Option<&'a str>,
+    recipient_address: &'a str,
+) -> impl Iterator<Item = (&'a str, bool)> {
+    let credit = (transaction_type != TX_TYPE_WITHDRAW).then_some((recipient_address, true));
+    credit
+        .into_iter()
+        .chain(sender_address.map(|sender| (sender, false)))
+}
+
+/// Баланс зі знаком: у базі може бути лише частина історії чужої адреси
+/// (наприклад, імпортований переказ без її поповнень), і тоді він від'ємний.
+fn apply_change(
+    balance: I256,
+    amount: I256,
+    credit: bool,
+    currency_id: u32,
+) -> Result<I256, Box<dyn Error>> {
+    let updated = if credit {
+        balance.checked_add(amount)
+    } else {
+        balance.checked_sub(amount)
+    };
+    updated.ok_or_else(|| format!("Balance overflow for currency {}", currency_id).into())
+}
+
+fn parse_amount(amount_hex: &str) -> Result<I256, Box<dyn Error>> {
+    let amount = U256::from_str_radix(amount_hex.trim_start_matches("0x"), 16)?;
+    Ok(I256::try_from(amount).map_err(|_| "Transaction amount is out of range")?)
+}
+
+/// Баланс зберігається так само, як `amount` транзакції: 32 байти hex (доповняльний код).
+fn format_balance(balance: I256) -> String {
+    format!("0x{:064x}", balance.into_raw())
+}
+
+fn parse_balance(balance_hex: &str) -> Result<I256, Box<dyn Error>> {
+    Ok(I256::from_raw(U256::from_str_radix(
+        balance_hex.trim_start_matches("0x"),
+        16,
+    )?))
+}
+
+/// Оновлює `balances` для щойно записаної транзакції. Викликається в тій самій
+/// транзакції SQLite, що й вставка в `transactions`.
+pub(super) fn apply_transaction(
+    conn: &Connection,
+    tx_db: &TransactionDb,
+) -> Result<(), Box<dyn Error>> {
+    let amount = parse_amount(&tx_db.amount)?;
+    for (address, credit) in balance_changes(
+        tx_db.transaction_type,
+        tx_db.sender_address.as_deref(),
+        &tx_db.recipient_address,
+    ) {
+        let balance = conn
+            .query_row(
+                "SELECT amount FROM balances WHERE address = ?1 AND currency_id = ?2",
+                params![address, tx_db.currency_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .map(|hex| parse_balance(&hex))
+            .transpose()?
+            .unwrap_or_default();
+        let balance = apply_change(balance, amount, credit, tx_db.currency_id)?;
+
+        conn.execute(
+            "INSERT INTO balances (address, currency_id, amount) VALUES (?1, ?2, ?3)
+             ON CONFLICT (address, currency_id) DO UPDATE SET amount = excluded.amount",
+            params![address, tx_db.currency_id, format_balance(balance)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Ненульові баланси адреси за валютами. Від'ємний баланс означає неповну історію
+/// адреси в цій базі, і його не можна показувати як суму.
+pub(super) fn wallet_balance(
+    conn: &Connection,
+    wallet_address: &str,
+) -> Result<BTreeMap<u32, U256>, Box<dyn Error>> {
+    let mut stmt = conn.prepare("SELECT currency_id, amount FROM balances WHERE address = ?1")?;
+    let rows: Vec<(u32, String)> = stmt
+        .query_map(params![wallet_address], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut balances = BTreeMap::new();
+    for (currency_id, balance_hex) in rows {
+        let balance = parse_balance(&balance_hex)?;
+        if balance.is_negative() {
+            return Err(format!("Underflow in subtraction for currency {}", currency_id).into());
+        }
+        if !balance.is_zero() {
+            balances.insert(currency_id, balance.into_raw());
+        }
+    }
+    Ok(balances)
+}
+
+/// Перераховує `balances` з усієї таблиці `transactions` і замінює нею збережені рядки.
+/// Повертає рядки, що не збіглися з перерахунком (порожній список — таблиці узгоджені).
+pub(super) fn rebuild(conn: &Connection) -> Result<Vec<BalanceMismatch>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT transaction_type, currency_id, amount, sender_address, recipient_address
+         FROM transactions",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut recomputed: BTreeMap<(String, u32), I256> = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let transaction_type: u32 = row.get(0)?;
+        let currency_id: u32 = row.get(1)?;
+        let amount = parse_amount(&row.get::<_, String>(2)?)?;
+        let sender_address: Option<String> = row.get(3)?;
+        let recipient_address: String = row.get(4)?;
+
+        for (address, credit) in balance_changes(
+            transaction_type,
+            sender_address.as_deref(),
+            &recipient_address,
+        ) {
+            let balance = recomputed
+                .entry((address.to_owned(), currency_id))
+                .or_default();
+            *balance = apply_change(*balance, amount, credit, currency_id)?;
+        }
+    }
+    recomputed.retain(|_, balance| !balance.is_zero());
+
+    let mut stmt = conn.prepare("SELECT address, currency_id, amount FROM balances")?;
+    let mut stored: BTreeMap<(String, u32), I256> = BTreeMap::new();
+    for row in stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, u32>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })? {
+        let (address, currency_id, balance_hex) = row?;
+        let balance = parse_balance(&balance_hex)?;
+        if !balance.is_zero() {
+            stored.insert((address, currency_id), balance);
+        }
+    }
+
+    let keys: BTreeSet<&(String, u32)> = stored.keys().chain(recomputed.keys()).collect();
+    let mismatches = keys
+        .into_iter()
+        .filter_map(|key| {
+            let stored_balance = stored.get(key).copied().unwrap_or_default();
+            let recomputed_balance = recomputed.get(key).copied().unwrap_or_default();
+            (stored_balance != recomputed_balance).then(|| BalanceMismatch {
+                address: key.0.clone(),
+                currency_id: key.1,
+                stored: stored_balance,
+                recomputed: recomputed_balance,
+            })
+        })
+        .collect();
+
+    conn.execute("DELETE FROM balances", [])?;
+    for ((address, currency_id), balance) in &recomputed {
+        conn.execute(
+            "INSERT INTO balances (address, currency_id, amount) VALUES (?1, ?2, ?3)",
+            params![address, currency_id, format_balance(*balance)],
+        )?;
+    }
+    Ok(mismatches)
+}
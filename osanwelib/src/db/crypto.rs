@@ -0,0 +1,293 @@
+use aes::Aes256;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Cbc};
+use hex::{decode, encode};
+use rand::Rng; // Для генерації випадкового IV і солі
+use sha3::{Digest, Keccak256};
+use std::error::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+// AES-256 CBC
+type Aes256Cbc = Cbc<Aes256, Pkcs7>;
+
+/// Назва KDF у таблиці `kdf_params`
+pub const KDF_ARGON2ID: &str = "argon2id";
+
+/// Формати значень у `properties`:
+/// - без префікса — AES-256-CBC, ключ = Keccak256(пароль) без солі;
+/// - `v3$` — AES-256-GCM, ключ з Argon2id, назва властивості — асоційовані дані.
+///
+/// Нові значення пишуться лише у `v3$`, CBC без префікса читається для міграції старих баз.
+const V3_PREFIX: &str = "v3$";
+const GCM_NONCE_LEN: usize = 12;
+
+/// Рекомендації OWASP для Argon2id: 19 MiB пам'яті, 2 проходи, 1 потік
+const DEFAULT_M_COST: u32 = 19 * 1024;
+const DEFAULT_T_COST: u32 = 2;
+const DEFAULT_P_COST: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// Параметри Argon2id, збережені в БД поруч із зашифрованими властивостями.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub salt: Vec<u8>,
+    /// Пам'ять, KiB
+    pub m_cost: u32,
+    /// Кількість проходів
+    pub t_cost: u32,
+    /// Паралелізм
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    /// Нові параметри з випадковою сіллю. Вартість можна підняти змінними оточення
+    /// `OSANWE_ARGON2_M_COST` (KiB), `OSANWE_ARGON2_T_COST` і `OSANWE_ARGON2_P_COST`.
+    pub fn generate() -> Result<Self, Box<dyn Error>> {
+        let mut salt = vec![0u8; SALT_LEN];
+        rand::thread_rng().fill(&mut salt[..]);
+
+        let params = KdfParams {
+            salt,
+            m_cost: cost_from_env("OSANWE_ARGON2_M_COST", DEFAULT_M_COST)?,
+            t_cost: cost_from_env("OSANWE_ARGON2_T_COST", DEFAULT_T_COST)?,
+            p_cost: cost_from_env("OSANWE_ARGON2_P_COST", DEFAULT_P_COST)?,
+        };
+        // Перевіряємо параметри одразу, а не при першому розблокуванні
+        params.argon2()?;
+        Ok(params)
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, Box<dyn Error>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+fn cost_from_env(name: &str, default: u32) -> Result<u32, Box<dyn Error>> {
+    match std::env::var(name) {
+        Ok(value) => Ok(value
+            .parse()
+            .map_err(|_| format!("{} must be a positive integer", name))?),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Ключ шифрування властивостей разом із форматом, у якому він шифрує.
+///
+/// Argon2id навмисно повільний, тож ключ виводиться один раз на команду чи розблокування
+/// (див. `db::unlock`) і передається далі. Пам'ять ключа затирається при Drop.
+pub enum PropertyKey {
+    /// Keccak256(пароль) — бази, створені до появи `kdf_params`
+    Legacy([u8; 32]),
+    Argon2id([u8; 32]),
+}
+
+impl PropertyKey {
+    /// `params == None` означає стару базу без `kdf_params`.
+    pub fn derive(external_key: &[u8], params: Option<&KdfParams>) -> Result<Self, Box<dyn Error>> {
+        // Ключ пишеться одразу в PropertyKey, щоб не лишати незатертих проміжних копій
+        let Some(params) = params else {
+            let mut digest = Keccak256::digest(external_key);
+            let mut property_key = PropertyKey::Legacy([0u8; 32]);
+            property_key.bytes_mut().copy_from_slice(&digest);
+            digest.zeroize();
+            return Ok(property_key);
+        };
+
+        let mut property_key = PropertyKey::Argon2id([0u8; 32]);
+        params
+            .argon2()?
+            .hash_password_into(external_key, &params.salt, property_key.bytes_mut())
+            .map_err(|e| format!("Argon2 error: {}", e))?;
+        Ok(property_key)
+    }
+
+    /// Шифрує значення властивості `name`. Назва входить в автентифіковані дані, тож
+    /// значення не можна непомітно підмінити або переставити під інший ключ.
+    pub fn encrypt(&self, name: &str, data: &[u8]) -> Result<String, Box<dyn Error>> {
+        match self {
+            // Стару базу лишаємо в старому форматі, доки її не мігрує розблокування
+            PropertyKey::Legacy(key) => Ok(encrypt_cbc(key, data)?),
+            PropertyKey::Argon2id(key) => {
+                let mut nonce = [0u8; GCM_NONCE_LEN];
+                rand::thread_rng().fill(&mut nonce);
+                let cipher = Aes256Gcm::new(key.into());
+                let ciphertext = cipher
+                    .encrypt(
+                        Nonce::from_slice(&nonce),
+                        Payload {
+                            msg: data,
+                            aad: name.as_bytes(),
+                        },
+                    )
+                    .map_err(|_| "Encryption error")?;
+
+                let mut full_data = nonce.to_vec();
+                full_data.extend_from_slice(&ciphertext);
+                Ok(format!("{}{}", V3_PREFIX, encode(full_data)))
+            }
+        }
+    }
+
+    /// Розшифроване значення затирається в пам'яті, щойно його відпускають.
+    pub fn decrypt(&self, name: &str, value: &str) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+        match self {
+            PropertyKey::Argon2id(key) => {
+                if let Some(data) = value.strip_prefix(V3_PREFIX) {
+                    decrypt_gcm(key, name, data)
+                } else {
+                    Err("Encrypted value format does not match the database KDF".into())
+                }
+            }
+            PropertyKey::Legacy(key) => {
+                if value.starts_with(V3_PREFIX) {
+                    return Err("Encrypted value format does not match the database KDF".into());
+                }
+                decrypt_cbc(key, value)
+            }
+        }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8; 32] {
+        match self {
+            PropertyKey::Legacy(key) | PropertyKey::Argon2id(key) => key,
+        }
+    }
+
+    #[cfg(test)]
+    fn bytes(&self) -> &[u8; 32] {
+        match self {
+            PropertyKey::Legacy(key) | PropertyKey::Argon2id(key) => key,
+        }
+    }
+}
+
+impl Drop for PropertyKey {
+    fn drop(&mut self) {
+        match self {
+            PropertyKey::Legacy(key) | PropertyKey::Argon2id(key) => key.zeroize(),
+        }
+    }
+}
+
+fn decrypt_gcm(
+    key: &[u8; 32],
+    name: &str,
+    data: &str,
+) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let encrypted_data = decode(data).map_err(|e| format!("Hex decode error: {}", e))?;
+    if encrypted_data.len() < GCM_NONCE_LEN {
+        return Err("Невірний формат даних".into());
+    }
+
+    let (nonce, ciphertext) = encrypted_data.split_at(GCM_NONCE_LEN);
+    let cipher = Aes256Gcm::new(key.into());
+    // Помилка тут — або неправильний пароль, або пошкоджене чи підмінене значення
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| "Decryption failed: wrong password or tampered value".into())
+}
+
+fn encrypt_cbc(key: &[u8; 32], data: &[u8]) -> Result<String, Box<dyn Error>> {
+    let mut iv = [0u8; 16];
+    rand::thread_rng().fill(&mut iv);
+    let cipher = Aes256Cbc::new_from_slices(key, &iv)?;
+
+    let mut full_data = iv.to_vec();
+    full_data.append(&mut cipher.encrypt_vec(data));
+    Ok(encode(full_data))
+}
+
+fn decrypt_cbc(key: &[u8; 32], data: &str) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let encrypted_data = decode(data).map_err(|e| format!("Hex decode error: {}", e))?;
+    if encrypted_data.len() < 16 {
+        return Err("Невірний формат даних".into());
+    }
+
+    let iv = &encrypted_data[0..16]; // Витягуємо IV
+    let ciphertext = &encrypted_data[16..]; // Витягуємо шифротекст
+
+    let cipher = Aes256Cbc::new_from_slices(key, iv)?;
+    let decrypted_data = cipher
+        .decrypt_vec(ciphertext)
+        .map_err(|e| format!("Decryption error: {}", e))?;
+
+    Ok(Zeroizing::new(decrypted_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheap_params() -> KdfParams {
+        KdfParams {
+            salt: vec![7; SALT_LEN],
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    #[test]
+    fn test_argon2id_depends_on_password_and_salt() {
+        let params = cheap_params();
+        let key = PropertyKey::derive(b"password", Some(&params)).unwrap();
+        let value = key.encrypt("name", b"secret").unwrap();
+        assert!(value.starts_with(V3_PREFIX));
+        assert_eq!(*key.decrypt("name", &value).unwrap(), b"secret");
+
+        let wrong_password = PropertyKey::derive(b"Password", Some(&params)).unwrap();
+        assert_ne!(wrong_password.bytes(), key.bytes());
+        assert!(wrong_password.decrypt("name", &value).is_err());
+
+        let other_salt = KdfParams {
+            salt: vec![8; SALT_LEN],
+            ..cheap_params()
+        };
+        let salted = PropertyKey::derive(b"password", Some(&other_salt)).unwrap();
+        assert_ne!(salted.bytes(), key.bytes());
+    }
+
+    #[test]
+    fn test_tampered_or_moved_value_is_rejected() {
+        let key = PropertyKey::derive(b"password", Some(&cheap_params())).unwrap();
+        let value = key.encrypt("priv_key", b"secret").unwrap();
+
+        // Значення, перенесене під іншу назву, не розшифровується
+        assert!(key.decrypt("wallet", &value).is_err());
+
+        // Будь-який змінений біт шифротексту виявляється
+        let mut bytes = decode(value.strip_prefix(V3_PREFIX).unwrap()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!("{}{}", V3_PREFIX, encode(bytes));
+        assert!(key.decrypt("priv_key", &tampered).is_err());
+    }
+
+    #[test]
+    fn test_legacy_cbc_is_still_readable() {
+        let legacy = PropertyKey::derive(b"password", None).unwrap();
+        let value = legacy.encrypt("name", b"secret").unwrap();
+        assert!(!value.starts_with(V3_PREFIX));
+        assert_eq!(*legacy.decrypt("name", &value).unwrap(), b"secret");
+
+        let key = PropertyKey::derive(b"password", Some(&cheap_params())).unwrap();
+
+        // Старе значення не можна розшифрувати новим ключем і навпаки
+        assert!(key.decrypt("name", &value).is_err());
+        assert!(legacy
+            .decrypt("name", &key.encrypt("name", b"secret").unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use super::{transaction_from_row, TRANSACTION_COLUMNS};
+use crate::tx::{TransactionDb, TX_TYPE_WITHDRAW};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Напрямок транзакції відносно адреси з [`HistoryFilter::address`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Зарахування на адресу (отримувач виведення до них не належить, див. `balances`)
+    Incoming,
+    /// Списання з адреси
+    Outgoing,
+}
+
+impl FromStr for Direction {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in" | "incoming" => Ok(Direction::Incoming),
+            "out" | "outgoing" => Ok(Direction::Outgoing),
+            other => Err(format!("Unknown direction '{}': use in or out", other).into()),
+        }
+    }
+}
+
+/// Умови вибірки історії; порожній фільтр — усі транзакції бази.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryFilter {
+    /// Транзакції, у яких адреса — відправник або отримувач
+    pub address: Option<String>,
+    /// Лише вхідні чи лише вихідні; потребує `address`
+    pub direction: Option<Direction>,
+    pub currency_id: Option<u32>,
+    pub transaction_type: Option<u32>,
+    /// Не раніше за цей `timestamp` (мілісекунди Unix, включно)
+    pub since: Option<u64>,
+    /// Раніше за цей `timestamp` (мілісекунди Unix, не включно)
+    pub until: Option<u64>,
+}
+
+/// Позиція в історії: сторінка продовжується з транзакцій, старіших за цю.
+///
+/// Текстовий вигляд `<timestamp>:<transaction_hash>` передається між викликами як є.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryCursor {
+    timestamp: u64,
+    transaction_hash: String,
+}
+
+impl fmt::Display for HistoryCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.timestamp, self.transaction_hash)
+    }
+}
+
+impl FromStr for HistoryCursor {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timestamp, transaction_hash) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid history cursor: '{}'", s))?;
+        Ok(HistoryCursor {
+            timestamp: timestamp
+                .parse()
+                .map_err(|_| format!("Invalid history cursor: '{}'", s))?,
+            transaction_hash: transaction_hash.to_owned(),
+        })
+    }
+}
+
+/// Одна сторінка історії, від новіших транзакцій до старіших.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryPage {
+    pub transactions: Vec<TransactionDb>,
+    /// Курсор наступної сторінки; `None`, якщо це остання
+    pub next_cursor: Option<HistoryCursor>,
+}
+
+/// Транзакції за `filter`, не більше `limit`, починаючи після `cursor`.
+/// Порядок — `timestamp` і хеш за спаданням, тож курсор стабільний і при однакових часах.
+pub(super) fn query(
+    conn: &Connection,
+    filter: &HistoryFilter,
+    cursor: Option<&HistoryCursor>,
+    limit: usize,
+) -> Result<HistoryPage, Box<dyn Error>> {
+    if limit == 0 {
+        return Err("History page limit must be positive".into());
+    }
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    let mut bind = |value: Value| {
+        values.push(value);
+        format!("?{}", values.len())
+    };
+
+    match (&filter.address, filter.direction) {
+        (Some(address), direction) => {
+            let address = bind(Value::Text(address.clone()));
+            let incoming = format!(
+                "(recipient_address = {} AND transaction_type <> {})",
+                address, TX_TYPE_WITHDRAW
+            );
+            let outgoing = format!("sender_address = {}", address);
+            conditions.push(match direction {
+                Some(Direction::Incoming) => incoming,
+                Some(Direction::Outgoing) => outgoing,
+                None => format!("({} OR {})", incoming, outgoing),
+            });
+        }
+        (None, Some(_)) => return Err("A direction filter needs an address".into()),
+        (None, None) => {}
+    }
+    if let Some(currency_id) = filter.currency_id {
+        conditions.push(format!("currency_id = {}", bind(currency_id.into())));
+    }
+    if let Some(transaction_type) = filter.transaction_type {
+        conditions.push(format!(
+            "transaction_type = {}",
+            bind(transaction_type.into())
+        ));
+    }
+    if let Some(since) = filter.since {
+        conditions.push(format!("timestamp >= {}", bind((since as i64).into())));
+    }
+    if let Some(until) = filter.until {
+        conditions.push(format!("timestamp < {}", bind((until as i64).into())));
+    }
+    if let Some(cursor) = cursor {
+        let timestamp = bind((cursor.timestamp as i64).into());
+        let transaction_hash = bind(Value::Text(cursor.transaction_hash.clone()));
+        conditions.push(format!(
+            "(timestamp < {0} OR (timestamp = {0} AND transaction_hash < {1}))",
+            timestamp, transaction_hash
+        ));
+    }
+    // Один зайвий рядок показує, чи є наступна сторінка
+    let limit_param = bind((limit as i64 + 1).into());
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM transactions {} ORDER BY timestamp DESC, transaction_hash DESC LIMIT {}",
+        TRANSACTION_COLUMNS, where_clause, limit_param
+    ))?;
+    let mut transactions = stmt
+        .query_map(params_from_iter(values), transaction_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if transactions.len() > limit {
+        transactions.truncate(limit);
+        transactions.last().map(|last| HistoryCursor {
+            timestamp: last.timestamp,
+            transaction_hash: last.transaction_hash.clone(),
+        })
+    } else {
+        None
+    };
+    Ok(HistoryPage {
+        transactions,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx::{TX_TYPE_REPLENISH, TX_TYPE_TRANSFER};
+
+    const WALLET: &str = "0x00000000000000000000000000000000000000aa";
+    const SHOP: &str = "0x00000000000000000000000000000000000000bb";
+
+    fn transaction(
+        hash: u8,
+        transaction_type: u32,
+        timestamp: u64,
+        sender: Option<&str>,
+        recipient: &str,
+    ) -> TransactionDb {
+        TransactionDb {
+            transaction_hash: format!("0x{}", hex::encode([hash; 32])),
+            transaction_type,
+            currency_id: if hash.is_multiple_of(2) {
+                16842752
+            } else {
+                16973825
+            },
+            amount: format!("0x{:064x}", 1),
+            timestamp,
+            sender_address: sender.map(str::to_owned),
+            sender_output_index: sender.map(|_| hash as u32),
+            recipient_address: recipient.to_owned(),
+            sender_signature: None,
+            source_transaction_hash: None,
+            signature_version: None,
+            network_id: None,
+            multisig: None,
+        }
+    }
+
+    fn hashes(page: &HistoryPage) -> Vec<u8> {
+        page.transactions
+            .iter()
+            .map(|tx_db| hex::decode(&tx_db.transaction_hash[2..]).unwrap()[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_history_filters_and_pages() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        super::super::create_database(&mut conn).unwrap();
+        for tx_db in [
+            transaction(1, TX_TYPE_REPLENISH, 1000, None, WALLET),
+            transaction(2, TX_TYPE_REPLENISH, 2000, None, WALLET),
+            transaction(3, TX_TYPE_TRANSFER, 3000, Some(WALLET), SHOP),
+            // Той самий час, що й у попередньої: порядок між ними задає хеш
+            transaction(4, TX_TYPE_TRANSFER, 3000, Some(SHOP), WALLET),
+            transaction(5, TX_TYPE_WITHDRAW, 5000, Some(WALLET), WALLET),
+            transaction(6, TX_TYPE_REPLENISH, 6000, None, SHOP),
+        ] {
+            super::super::insert_transaction(&conn, &tx_db).unwrap();
+        }
+
+        let all = |filter: &HistoryFilter| query(&conn, filter, None, 100).unwrap();
+        let wallet = HistoryFilter {
+            address: Some(WALLET.to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(hashes(&all(&wallet)), vec![5, 4, 3, 2, 1]);
+
+        // Виведення на власну адресу — лише вихідна транзакція
+        let incoming = HistoryFilter {
+            direction: Some(Direction::Incoming),
+            ..wallet.clone()
+        };
+        assert_eq!(hashes(&all(&incoming)), vec![4, 2, 1]);
+        let outgoing = HistoryFilter {
+            direction: Some(Direction::Outgoing),
+            ..wallet.clone()
+        };
+        assert_eq!(hashes(&all(&outgoing)), vec![5, 3]);
+
+        let filtered = HistoryFilter {
+            currency_id: Some(16842752),
+            transaction_type: Some(TX_TYPE_REPLENISH),
+            since: Some(2000),
+            until: Some(6000),
+            ..Default::default()
+        };
+        assert_eq!(hashes(&all(&filtered)), vec![2]);
+        assert!(query(
+            &conn,
+            &HistoryFilter {
+                direction: Some(Direction::Outgoing),
+                ..Default::default()
+            },
+            None,
+            10
+        )
+        .is_err());
+
+        // Сторінки по дві без пропусків і повторів, курсор переживає текстовий вигляд
+        let mut pages = Vec::new();
+        let mut cursor: Option<HistoryCursor> = None;
+        loop {
+            let page = query(&conn, &HistoryFilter::default(), cursor.as_ref(), 2).unwrap();
+            pages.push(hashes(&page));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next.to_string().parse().unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec![6, 5], vec![4, 3], vec![2, 1]]);
+    }
+}
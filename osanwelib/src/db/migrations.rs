@@ -0,0 +1,228 @@
+use rusqlite::{Connection, Result as SqlResult, TransactionBehavior};
+use std::error::Error;
+
+/// Крок міграції на Rust — для того, що SQL не порахує
+type Backfill = fn(&Connection) -> Result<(), Box<dyn Error>>;
+
+/// Одна міграція схеми: після неї `PRAGMA user_version` бази дорівнює `version`.
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+    /// Виконується після `sql` у тій самій транзакції
+    backfill: Option<Backfill>,
+}
+
+/// Довідник `CryptoAssets`; його ж без решти схеми читає [`super::builtin_cryptoassets`].
+pub(super) const CRYPTO_ASSETS_SQL: &str = include_str!("migrations/0002_crypto_assets.sql");
+
+/// Усі міграції за зростанням версії. SQL вбудовано в бінарник, тож схему можна
+/// створити на будь-якій машині. Застосовані міграції не змінюються — лише додаються нові.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("migrations/0001_initial.sql"),
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        name: "crypto_assets",
+        sql: CRYPTO_ASSETS_SQL,
+        backfill: None,
+    },
+    Migration {
+        version: 3,
+        name: "signature_envelope",
+        sql: include_str!("migrations/0003_signature_envelope.sql"),
+        backfill: None,
+    },
+    Migration {
+        version: 4,
+        name: "multisig",
+        sql: include_str!("migrations/0004_multisig.sql"),
+        backfill: None,
+    },
+    Migration {
+        version: 5,
+        name: "balances",
+        sql: include_str!("migrations/0005_balances.sql"),
+        // Суми U256 у hex SQLite не складе
+        backfill: Some(|conn| super::balances::rebuild(conn).map(drop)),
+    },
+    Migration {
+        version: 6,
+        name: "history_indexes",
+        sql: include_str!("migrations/0006_history_indexes.sql"),
+        backfill: None,
+    },
+];
+
+/// Остання міграція, яку бази до нумерації міграцій проходять повністю: її SQL
+/// ідемпотентний. Наступні лише позначаються, якщо їхні стовпці вже є.
+const LEGACY_BASELINE: u32 = 2;
+
+/// Версія схеми, яку знає ця збірка.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Доводить схему бази до останньої версії. Кожна міграція виконується в окремій
+/// транзакції разом з оновленням `user_version`, тож перервана міграція не лишає
+/// базу в проміжному стані.
+pub fn migrate(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let current = user_version(conn)?;
+    if current > latest_version() {
+        return Err(format!(
+            "Database schema version {} is newer than this build supports ({})",
+            current,
+            latest_version()
+        )
+        .into());
+    }
+    let legacy = if current == 0 {
+        legacy_version(conn)?
+    } else {
+        0
+    };
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        // IMMEDIATE одразу бере блокування на запис: два процеси не застосують одну міграцію двічі
+        let db_tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // Інший процес міг застосувати її, поки ми чекали на блокування
+        if user_version(&db_tx)? >= migration.version {
+            continue;
+        }
+        if migration.version <= LEGACY_BASELINE || migration.version > legacy {
+            db_tx.execute_batch(migration.sql)?;
+            if let Some(backfill) = migration.backfill {
+                backfill(&db_tx)?;
+            }
+        }
+        db_tx.pragma_update(None, "user_version", migration.version)?;
+        db_tx.commit()?;
+        log::info!(
+            "Database schema migrated to version {} ({}).",
+            migration.version,
+            migration.name
+        );
+    }
+    Ok(())
+}
+
+fn user_version(conn: &Connection) -> SqlResult<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Версія схеми бази, створеної до нумерації міграцій (`user_version` = 0): тоді
+/// стовпці `transactions` додавались перевірками на льоту. Для нової бази — 0.
+fn legacy_version(conn: &Connection) -> SqlResult<u32> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('transactions')")?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<SqlResult<Vec<_>>>()?;
+
+    let has = |column: &str| columns.iter().any(|name| name == column);
+    Ok(if columns.is_empty() {
+        0
+    } else if has("multisig") {
+        4
+    } else if has("network_id") {
+        3
+    } else {
+        LEGACY_BASELINE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<SqlResult<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_database_gets_the_latest_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert!(columns(&conn, "transactions").contains(&"multisig".to_string()));
+
+        let assets: i64 = conn
+            .query_row(r#"SELECT COUNT(*) FROM "CryptoAssets""#, [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(assets > 0);
+
+        // Повторний запуск нічого не робить
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+
+        // Базу з новішою схемою ця збірка не чіпає
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+
+    #[test]
+    fn test_database_from_before_versioning_is_upgraded() {
+        // Так виглядала база після появи конверта підпису, але до мультипідпису
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE properties (property_key TEXT PRIMARY KEY, property_value TEXT NOT NULL);
+             INSERT INTO properties VALUES ('wallet', 'v3$...');
+             CREATE TABLE transactions (
+                 transaction_hash TEXT PRIMARY KEY,
+                 transaction_type INTEGER NOT NULL,
+                 currency_id INTEGER NOT NULL,
+                 amount TEXT NOT NULL,
+                 timestamp INTEGER NOT NULL,
+                 sender_address TEXT,
+                 sender_output_index INTEGER,
+                 recipient_address TEXT NOT NULL,
+                 sender_signature TEXT,
+                 source_transaction_hash TEXT,
+                 signature_version INTEGER,
+                 network_id TEXT
+             );
+             INSERT INTO transactions (transaction_hash, transaction_type, currency_id, amount,
+                 timestamp, recipient_address, network_id)
+             VALUES ('0x01', 1, 16842752, '0x01', 1700000000, '0xrecipient', 'osanwe-mainnet');
+             CREATE TABLE \"CryptoAssets\" (id INTEGER PRIMARY KEY, symbol TEXT NOT NULL);",
+        )
+        .unwrap();
+        assert_eq!(legacy_version(&conn).unwrap(), 3);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert!(columns(&conn, "transactions").contains(&"multisig".to_string()));
+        assert!(columns(&conn, "CryptoAssets").contains(&"decimals".to_string()));
+        assert!(columns(&conn, "accounts").contains(&"label".to_string()));
+
+        // Дані нікуди не зникли
+        let network_id: String = conn
+            .query_row(
+                "SELECT network_id FROM transactions WHERE transaction_hash = '0x01'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(network_id, "osanwe-mainnet");
+        assert_eq!(
+            super::super::balances::wallet_balance(&conn, "0xrecipient").unwrap()[&16842752],
+            1.into()
+        );
+        let wallet: i64 = conn
+            .query_row("SELECT COUNT(*) FROM properties", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(wallet, 1);
+    }
+}
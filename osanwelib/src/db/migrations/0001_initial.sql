@@ -0,0 +1,47 @@
+-- Схема, з якої почалася нумерація міграцій. IF NOT EXISTS — бо бази, створені раніше,
+-- частину цих таблиць уже мають.
+
+-- Зашифровані властивості гаманця (ключі, seed, перевірна фраза пароля)
+CREATE TABLE IF NOT EXISTS properties (
+    property_key   TEXT PRIMARY KEY,
+    property_value TEXT NOT NULL
+);
+
+-- Параметри KDF не секретні: без них не вивести ключ, яким зашифровано properties
+CREATE TABLE IF NOT EXISTS kdf_params (
+    id        INTEGER PRIMARY KEY CHECK (id = 1),
+    algorithm TEXT NOT NULL,
+    salt      BLOB NOT NULL,
+    m_cost    INTEGER NOT NULL,
+    t_cost    INTEGER NOT NULL,
+    p_cost    INTEGER NOT NULL
+);
+
+-- Рахунки гаманця. Адреси й мітки не секретні, тож зберігаються без шифрування;
+-- приватні ключі рахунків не зберігаються, а виводяться з seed
+CREATE TABLE IF NOT EXISTS accounts (
+    account_index INTEGER PRIMARY KEY,
+    label         TEXT NOT NULL UNIQUE,
+    address       TEXT NOT NULL UNIQUE
+);
+
+-- Мультипідписні рахунки; підписанти зберігаються через кому
+CREATE TABLE IF NOT EXISTS multisig_accounts (
+    label     TEXT PRIMARY KEY,
+    address   TEXT NOT NULL UNIQUE,
+    threshold INTEGER NOT NULL,
+    signers   TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    transaction_hash TEXT PRIMARY KEY,
+    transaction_type INTEGER NOT NULL,
+    currency_id INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    sender_address TEXT,
+    sender_output_index INTEGER,
+    recipient_address TEXT NOT NULL,
+    sender_signature TEXT,
+    source_transaction_hash TEXT
+);
@@ -0,0 +1,108 @@
+-- Довідник криптоактивів. Стара таблиця (без decimals) нічого, крім довідника, не містить,
+-- тож її просто перестворюємо
+DROP TABLE IF EXISTS "CryptoAssets";
+
+CREATE TABLE "CryptoAssets" (
+  "id" INTEGER PRIMARY KEY,           -- (NET_TYPE << 24) + (CHAIN_CODE << 16) + TOKEN_ID
+  "net_type"   INTEGER NOT NULL,      -- 1 = EVM mainnet, 2 = EVM testnet, 3 = non-EVM main, 4 = non-EVM test, ...
+  "chain_code" INTEGER NOT NULL,      -- конкретна мережа в межах net_type
+  "token_id"   INTEGER NOT NULL,      -- ідентифікатор токена/монети в межах мережі
+  "symbol"     TEXT NOT NULL,         -- умовне скорочення (ETH, BTC, USDT і т. д.)
+  "description" TEXT,
+  "decimals"   INTEGER NOT NULL,      -- кількість десяткових знаків (ETH = 18, USDT = 6, BTC = 8, ...)
+  "contract_address" TEXT,            -- адреса контракту токена (NULL для нативної монети мережі)
+  "evm_chain_id" INTEGER              -- справжній EVM chain id (NULL для non-EVM мереж)
+);
+
+-- Додаємо записи (прикладна вибірка з EVM main/testnet і non-EVM)
+INSERT OR IGNORE INTO "CryptoAssets" (id, net_type, chain_code, token_id, symbol, description, decimals, contract_address, evm_chain_id) VALUES
+
+-- 1) EVM MAINNET (net_type = 1)
+-- 1.1 Ethereum mainnet (chain_code = 1)
+(16842752, 1, 1, 0, 'ETH', 'Ethereum', 18, NULL, 1),
+(16842753, 1, 1, 1, 'USDT', 'ERC20 Tether', 6, '0xdAC17F958D2ee523a2206206994597C13D831ec7', 1),
+(16842754, 1, 1, 2, 'USDC', 'ERC20 USD Coin', 6, '0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48', 1),
+(16842755, 1, 1, 3, 'DAI', 'ERC20 Dai', 18, '0x6B175474E89094C44Da98b954EedeAC495271d0F', 1),
+(16842756, 1, 1, 4, 'WBTC', 'Wrapped BTC (ERC20)', 8, '0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599', 1),
+(16842757, 1, 1, 5, 'LINK', 'Chainlink Token', 18, '0x514910771AF9Ca656af840dff83E8264EcF986CA', 1),
+(16842758, 1, 1, 6, 'SHIB', 'Shiba Inu', 18, '0x95aD61b0a150d79219dCF64E1E6Cc01f0B64C4cE', 1),
+(16842759, 1, 1, 7, 'UNI', 'Uniswap Token', 18, '0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984', 1),
+
+-- 1.2 BNB Chain (chain_code = 2)
+(16908288, 1, 2, 0, 'BNB', 'BNB Chain', 18, NULL, 56),
+(16908289, 1, 2, 1, 'USDT', 'BEP20 Tether', 18, '0x55d398326f99059fF775485246999027B3197955', 56),
+(16908290, 1, 2, 2, 'USDC', 'BEP20 USD Coin', 18, '0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d', 56),
+(16908291, 1, 2, 3, 'BUSD', 'Binance USD (BEP20)', 18, '0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56', 56),
+(16908292, 1, 2, 4, 'CAKE', 'PancakeSwap Token', 18, '0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82', 56),
+
+-- 1.3 Polygon mainnet (chain_code = 3)
+(16973824, 1, 3, 0, 'MATIC', 'Polygon', 18, NULL, 137),
+(16973825, 1, 3, 1, 'USDT', 'USDT for Polygon', 6, '0xc2132D05D31c914a87C6611C10748AEb04B58e8F', 137),
+(16973826, 1, 3, 2, 'USDC', 'USDC for Polygon', 6, '0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359', 137),
+(16973827, 1, 3, 3, 'DAI', 'DAI for Polygon', 18, '0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063', 137),
+
+-- 1.4 Avalanche C-Chain (chain_code = 4)
+(17039360, 1, 4, 0, 'AVAX', 'Avalanche C-Chain', 18, NULL, 43114),
+(17039361, 1, 4, 1, 'USDT', 'USDT for Avalanche', 6, '0x9702230A8Ea53601f5cD2dc00fDBc13d4dF4A8c7', 43114),
+(17039362, 1, 4, 2, 'USDC', 'USDC for Avalanche', 6, '0xB97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E', 43114),
+
+-- 1.5 Arbitrum One (chain_code = 5)
+(17104896, 1, 5, 0, 'ETH', 'Bridged ETH for Arbitrum', 18, NULL, 42161),
+(17104897, 1, 5, 1, 'USDT', 'USDT for Arbitrum', 6, '0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9', 42161),
+(17104898, 1, 5, 2, 'USDC', 'USDC for Arbitrum', 6, '0xaf88d065e77c8cC2239327C5EDb3A432268e5831', 42161),
+
+-- 1.6 Optimism (chain_code = 6)
+(17170432, 1, 6, 0, 'ETH', 'Bridged ETH for Optimism', 18, NULL, 10),
+(17170433, 1, 6, 1, 'USDT', 'USDT for Optimism', 6, '0x94b008aA00579c1307B0EF2c499aD98a8ce58e58', 10),
+(17170434, 1, 6, 2, 'USDC', 'USDC for Optimism', 6, '0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85', 10),
+
+-- 1.7 Fantom (chain_code = 7)
+(17235968, 1, 7, 0, 'FTM', 'Fantom mainnet token', 18, NULL, 250),
+(17235969, 1, 7, 1, 'USDT', 'USDT for Fantom', 6, '0x049d68029688eAbF473097a2fC38ef61633A3C7A', 250),
+(17235970, 1, 7, 2, 'USDC', 'USDC for Fantom', 6, '0x04068DA6C83AFCFA0e13ba15A6696662335D5B75', 250),
+
+-- (Приклад додаткових EVM mainnets)
+(17301504, 1, 8, 0, 'CRO', 'Cronos mainnet', 18, NULL, 25),
+(17367040, 1, 9, 0, 'KCS', 'KuCoin Community Chain mainnet', 18, NULL, 321),
+(17432576, 1, 10, 0, 'XDAI', 'Gnosis Chain (ex xDai)', 18, NULL, 100),
+(17498112, 1, 11, 0, 'ETH', 'Base mainnet of Coinbase', 18, NULL, 8453),
+
+-- 2) EVM TESTNET (net_type = 2)
+-- 2.1 Ethereum testnets
+(33619968, 2, 1, 0, 'ETH', 'Goerli ETH', 18, NULL, 5),
+(33619969, 2, 1, 1, 'USDT', 'Goerli USDT', 6, NULL, 5),
+(33619970, 2, 1, 2, 'USDC', 'Goerli USDC', 6, NULL, 5),
+(33685504, 2, 2, 0, 'ETH', 'Sepolia ETH', 18, NULL, 11155111),
+
+-- 2.2 BNB Testnet (chain_code = 3)
+(33751040, 2, 3, 0, 'BNB', 'BNB testnet', 18, NULL, 97),
+
+-- 2.3 Polygon Mumbai (chain_code = 4)
+(33816576, 2, 4, 0, 'MATIC', 'Polygon Mumbai', 18, NULL, 80001),
+
+-- 2.4 Avalanche Fuji (chain_code = 5)
+(33882112, 2, 5, 0, 'AVAX', 'Avalanche Fuji test', 18, NULL, 43113),
+
+-- 2.5 Arbitrum / Optimism test (chain_code = 6,7)
+(33947648, 2, 6, 0, 'ETH', 'Arbitrum testnet ETH', 18, NULL, 421613),
+(34013184, 2, 7, 0, 'ETH', 'Optimism testnet ETH', 18, NULL, 420),
+
+-- 3) NON-EVM MAINNET (net_type = 3)
+(50331648, 3, 1, 0, 'BTC', 'Bitcoin mainnet', 8, NULL, NULL),
+(50397184, 3, 2, 0, 'TRX', 'Tron mainnet', 6, NULL, NULL),
+(50462720, 3, 3, 0, 'XRP', 'Ripple mainnet', 6, NULL, NULL),
+(50528256, 3, 4, 0, 'SOL', 'Solana mainnet', 9, NULL, NULL),
+(50593792, 3, 5, 0, 'DOT', 'Polkadot mainnet', 10, NULL, NULL),
+(50659328, 3, 6, 0, 'DOGE', 'Dogecoin', 8, NULL, NULL),
+(50724864, 3, 7, 0, 'ADA', 'Cardano', 6, NULL, NULL),
+(50790400, 3, 8, 0, 'XTZ', 'Tezos', 6, NULL, NULL),
+(50855936, 3, 9, 0, 'XLM', 'Stellar', 7, NULL, NULL),
+(50921472, 3, 10, 0, 'NEO', 'NEO mainnet', 0, NULL, NULL),
+(50987008, 3, 11, 0, 'LTC', 'Litecoin', 8, NULL, NULL),
+(51052544, 3, 12, 0, 'BCH', 'Bitcoin Cash', 8, NULL, NULL),
+
+-- 4) NON-EVM TESTNET (net_type = 4)
+(67108864, 4, 1, 0, 'tBTC', 'Bitcoin testnet', 8, NULL, NULL),
+(67174400, 4, 2, 0, 'TRX', 'Tron Shasta testnet', 6, NULL, NULL),
+(67239936, 4, 3, 0, 'SOL', 'Solana devnet/testnet', 9, NULL, NULL);
+
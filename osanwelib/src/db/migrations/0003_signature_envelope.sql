@@ -0,0 +1,3 @@
+-- Версія конверта підпису і мережа, до якої прив'язаний підпис
+ALTER TABLE transactions ADD COLUMN signature_version INTEGER;
+ALTER TABLE transactions ADD COLUMN network_id TEXT;
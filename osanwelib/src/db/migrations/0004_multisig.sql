@@ -0,0 +1,2 @@
+-- Авторизація мультипідписного відправника (JSON tx::MultisigDb)
+ALTER TABLE transactions ADD COLUMN multisig TEXT;
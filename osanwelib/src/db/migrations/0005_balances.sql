@@ -0,0 +1,8 @@
+-- Баланси адрес (amount — 32 байти hex у доповняльному коді), оновлюються разом із transactions.
+-- Наявні транзакції переносить balances::rebuild після цього SQL.
+CREATE TABLE IF NOT EXISTS balances (
+    address TEXT NOT NULL,
+    currency_id INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    PRIMARY KEY (address, currency_id)
+);
@@ -0,0 +1,4 @@
+-- Історія транзакцій (db::history): від новіших до старіших, загалом і для однієї адреси
+CREATE INDEX IF NOT EXISTS transactions_by_time ON transactions (timestamp, transaction_hash);
+CREATE INDEX IF NOT EXISTS transactions_by_sender ON transactions (sender_address, timestamp);
+CREATE INDEX IF NOT EXISTS transactions_by_recipient ON transactions (recipient_address, timestamp);
@@ -1,19 +1,23 @@
-use crate::keys;
-use crate::tx::TransactionDb;
-use aes::Aes256;
-use block_modes::block_padding::Pkcs7;
-use block_modes::{BlockMode, Cbc};
+mod balances;
+mod crypto;
+mod history;
+mod migrations;
+mod store;
+
+use crate::tx::{MultisigDb, TransactionDb};
+pub use balances::BalanceMismatch;
+use crypto::KDF_ARGON2ID;
+pub use crypto::{KdfParams, PropertyKey};
 use ethers::types::U256;
-use hex::{decode, encode};
-use rand::Rng; // Для генерації випадкового IV
-use rusqlite::{params, Connection, Result as SqlResult};
-use sha3::{Digest, Keccak256};
+pub use history::{Direction, HistoryCursor, HistoryFilter, HistoryPage};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult, TransactionBehavior};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs;
 use std::path::PathBuf;
+pub use store::Store;
+use zeroize::{Zeroize, Zeroizing};
 
-// AES-256 CBC
-type Aes256Cbc = Cbc<Aes256, Pkcs7>;
 pub const DB_PATH: &str = "osanwe.db";
 pub const OSANWE_KEY: &str = "osanwe";
 pub const TEST_PHRASE: &str = "interchange of thought";
@@ -26,6 +30,28 @@ pub struct CryptoAsset {
     pub token_id: i32,
     pub symbol: String,
     pub description: Option<String>,
+    pub decimals: u32,
+    pub contract_address: Option<String>,
+    pub evm_chain_id: Option<u64>,
+}
+
+/// Мультипідписний рахунок m-of-n: ключа немає, адреса виводиться з порогу і набору
+/// адрес підписантів (див. `multisig::derive_address`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigAccount {
+    pub label: String,
+    pub address: String,
+    pub threshold: u32,
+    /// Адреси підписантів у порядку зростання
+    pub signers: Vec<String>,
+}
+
+/// Рахунок гаманця: ключ виводиться з одного seed за шляхом `m/44'/60'/0'/0/{account_index}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub account_index: u32,
+    pub label: String,
+    pub address: String,
 }
 
 fn get_db_connection() -> SqlResult<Connection> {
@@ -37,96 +63,92 @@ fn get_db_connection() -> SqlResult<Connection> {
     }
     #[cfg(not(test))]
     {
-        Connection::open(DB_PATH)
+        Connection::open(database_path())
     }
 }
 
-fn create_cipher(external_key: &[u8], iv: Option<&[u8]>) -> (Aes256Cbc, Vec<u8>) {
-    let mut hasher = Keccak256::new();
-    hasher.update(external_key);
-    let sym_key = hasher.finalize();
-    let sym_key = sym_key[..32].to_vec(); // Явно беремо 32 байти для AES-256
-
-    let iv = match iv {
-        Some(iv) => iv.to_vec(), // Використовуємо переданий IV
-        None => {
-            let mut new_iv = [0u8; 16];
-            rand::thread_rng().fill(&mut new_iv);
-            new_iv.to_vec()
-        }
-    };
-
-    let cipher = Aes256Cbc::new_from_slices(&sym_key, &iv).unwrap();
-    (cipher, iv)
+/// Файл бази: база активного профілю (див. `profile::activate`), а без профілю —
+/// `DB_PATH` у поточному каталозі.
+pub fn database_path() -> PathBuf {
+    crate::profile::active()
+        .map(|profile| profile.db_path())
+        .unwrap_or_else(|| PathBuf::from(DB_PATH))
 }
 
-fn encrypt(data: &[u8], external_key: &[u8]) -> Result<String, Box<dyn Error>> {
-    let (cipher, iv) = create_cipher(external_key, None);
-    let mut encrypted_data = cipher.encrypt_vec(data);
-
-    let mut full_data = iv;
-    full_data.append(&mut encrypted_data);
-
-    Ok(encode(full_data))
+/// Відкриває базу (створює, якщо її ще немає) і доводить її схему до останньої версії.
+pub fn check_and_create_database() -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = get_db_connection()?;
+    create_database(&mut conn)
 }
 
-fn decrypt(data: &str, external_key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    let encrypted_data = decode(data).map_err(|e| format!("Hex decode error: {}", e))?;
-    if encrypted_data.len() < 16 {
-        return Err("Невірний формат даних".into());
-    }
-
-    let iv = &encrypted_data[0..16]; // Витягуємо IV
-    let ciphertext = &encrypted_data[16..]; // Витягуємо шифротекст
-
-    let (cipher, _) = create_cipher(external_key, Some(iv)); // Використовуємо той самий IV
-
-    let decrypted_data = cipher
-        .decrypt_vec(ciphertext)
-        .map_err(|e| format!("Decryption error: {}", e))?;
-
-    Ok(decrypted_data)
+/// Застосовує до бази міграції схеми, яких вона ще не має (див. `migrations`).
+pub fn create_database(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    migrations::migrate(conn)
 }
 
-pub fn check_and_create_database() -> Result<(), Box<dyn std::error::Error>> {
-    // У продакшені: якщо файл існує – нічого не робимо, інакше створюємо базу
-    #[cfg(not(test))]
-    {
-        if fs::metadata(DB_PATH).is_ok() {
-            log::info!("Database found, all is well.");
-        } else {
-            let conn = get_db_connection()?;
-            create_database(&conn)?;
-            log::info!("Database created successfully.");
-        }
-        create_cryptoassets_table_if_needed()?;
-    }
+/// Параметри KDF бази; `None` — стара база з ключем Keccak256(пароль).
+pub fn get_kdf_params() -> Result<Option<KdfParams>, Box<dyn Error>> {
+    load_kdf_params(&get_db_connection()?)
+}
 
-    // У тестовому режимі: завжди створюємо in-memory базу з потрібною схемою
-    #[cfg(test)]
-    {
-        let conn = get_db_connection()?;
-        create_database(&conn)?;
-        create_cryptoassets_table_if_needed()?;
+fn load_kdf_params(conn: &Connection) -> Result<Option<KdfParams>, Box<dyn Error>> {
+    let row = conn
+        .query_row(
+            "SELECT algorithm, salt, m_cost, t_cost, p_cost FROM kdf_params WHERE id = 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    KdfParams {
+                        salt: row.get(1)?,
+                        m_cost: row.get(2)?,
+                        t_cost: row.get(3)?,
+                        p_cost: row.get(4)?,
+                    },
+                ))
+            },
+        )
+        .optional()?;
+
+    match row {
+        Some((algorithm, params)) if algorithm == KDF_ARGON2ID => Ok(Some(params)),
+        Some((algorithm, _)) => Err(format!("Unsupported KDF: {}", algorithm).into()),
+        None => Ok(None),
     }
-
-    Ok(())
 }
 
-pub fn create_database(conn: &Connection) -> rusqlite::Result<()> {
+fn store_kdf_params(conn: &Connection, params: &KdfParams) -> SqlResult<()> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS properties (
-                  property_key   TEXT PRIMARY KEY,
-                  property_value TEXT NOT NULL
-                  )",
-        [],
+        "INSERT OR REPLACE INTO kdf_params (id, algorithm, salt, m_cost, t_cost, p_cost)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5)",
+        params![
+            KDF_ARGON2ID,
+            params.salt,
+            params.m_cost,
+            params.t_cost,
+            params.p_cost
+        ],
     )?;
     Ok(())
 }
 
+fn property_key(conn: &Connection, external_key: &[u8]) -> Result<PropertyKey, Box<dyn Error>> {
+    PropertyKey::derive(external_key, load_kdf_params(conn)?.as_ref())
+}
+
 pub fn get_all_cryptoassets() -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
-    let conn = get_db_connection()?;
+    query_cryptoassets(&get_db_connection()?)
+}
+
+/// Вбудований у бінарник довідник `CryptoAssets` без файлу бази гаманця: міграція довідника
+/// виконується в базі SQLite в пам'яті, яка закривається після читання.
+pub fn builtin_cryptoassets() -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(migrations::CRYPTO_ASSETS_SQL)?;
+    query_cryptoassets(&conn)
+}
 
+fn query_cryptoassets(conn: &Connection) -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
     let mut stmt = conn.prepare(
         r#"
         SELECT 
@@ -135,7 +157,10 @@ pub fn get_all_cryptoassets() -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
             chain_code,
             token_id,
             symbol,
-            description
+            description,
+            decimals,
+            contract_address,
+            evm_chain_id
         FROM CryptoAssets
         ORDER BY symbol ASC, id ASC
         "#,
@@ -149,6 +174,9 @@ pub fn get_all_cryptoassets() -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
             token_id: row.get(3)?,
             symbol: row.get(4)?,
             description: row.get(5)?,
+            decimals: row.get(6)?,
+            contract_address: row.get(7)?,
+            evm_chain_id: row.get(8)?,
         })
     })?;
 
@@ -160,10 +188,52 @@ pub fn get_all_cryptoassets() -> Result<Vec<CryptoAsset>, Box<dyn Error>> {
     Ok(assets)
 }
 
-pub fn insert_property(key: &str, value: &str, external_key: &[u8]) -> Result<(), Box<dyn Error>> {
+/// Повертає запис довідника `CryptoAssets` за його id, або `None`, якщо такого немає.
+pub fn get_cryptoasset_by_id(id: u32) -> Result<Option<CryptoAsset>, Box<dyn Error>> {
+    let conn = get_db_connection()?;
+
+    let asset = conn
+        .query_row(
+            "SELECT id, net_type, chain_code, token_id, symbol, description,
+                    decimals, contract_address, evm_chain_id
+             FROM CryptoAssets
+             WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(CryptoAsset {
+                    id: row.get(0)?,
+                    net_type: row.get(1)?,
+                    chain_code: row.get(2)?,
+                    token_id: row.get(3)?,
+                    symbol: row.get(4)?,
+                    description: row.get(5)?,
+                    decimals: row.get(6)?,
+                    contract_address: row.get(7)?,
+                    evm_chain_id: row.get(8)?,
+                })
+            },
+        )
+        .optional()?;
+
+    Ok(asset)
+}
+
+/// Кількість десяткових знаків валюти `currency_id`. Невідома валюта — помилка.
+pub fn get_cryptoasset_decimals(currency_id: u32) -> Result<u32, Box<dyn Error>> {
+    match get_cryptoasset_by_id(currency_id)? {
+        Some(asset) => Ok(asset.decimals),
+        None => Err(format!("Unknown currency_id: {}", currency_id).into()),
+    }
+}
+
+pub fn insert_property(
+    key: &str,
+    value: &str,
+    property_key: &PropertyKey,
+) -> Result<(), Box<dyn Error>> {
     let conn = get_db_connection()?;
 
-    let encrypted_value = encrypt(value.as_bytes(), external_key)?; // Розпакування `Result`
+    let encrypted_value = property_key.encrypt(key, value.as_bytes())?;
 
     conn.execute(
         "INSERT OR REPLACE INTO properties (property_key, property_value) VALUES (?1, ?2)",
@@ -173,15 +243,40 @@ pub fn insert_property(key: &str, value: &str, external_key: &[u8]) -> Result<()
     Ok(())
 }
 
-pub fn get_property_by_key(key: &str, external_key: &[u8]) -> Result<String, Box<dyn Error>> {
-    let conn = get_db_connection()?;
+/// Значення несекретної властивості (адреса гаманця, тестова фраза).
+/// Для ключів і seed використовуйте [`get_secret_property`].
+pub fn get_property_by_key(
+    key: &str,
+    property_key: &PropertyKey,
+) -> Result<String, Box<dyn Error>> {
+    Ok(get_secret_property(key, property_key)?.to_string())
+}
+
+/// Значення секретної властивості; пам'ять затирається, коли значення відпускають.
+pub fn get_secret_property(
+    key: &str,
+    property_key: &PropertyKey,
+) -> Result<Zeroizing<String>, Box<dyn Error>> {
+    read_secret_property(&get_db_connection()?, key, property_key)
+}
+
+fn read_secret_property(
+    conn: &Connection,
+    key: &str,
+    property_key: &PropertyKey,
+) -> Result<Zeroizing<String>, Box<dyn Error>> {
     let mut stmt = conn.prepare("SELECT property_value FROM properties WHERE property_key = ?1")?;
     let encrypted_value: String = stmt.query_row([key], |row| row.get(0))?;
 
-    let decrypted_bytes = decrypt(&encrypted_value, external_key)?; // Використовуємо `?`
-    let decrypted_value = String::from_utf8(decrypted_bytes)?; // Розпакування перед `String::from_utf8()`
+    let mut decrypted_bytes = property_key.decrypt(key, &encrypted_value)?;
+    // Забираємо буфер, не копіюючи його: порожній Zeroizing нічого не затре
+    let decrypted_value =
+        String::from_utf8(std::mem::take(&mut *decrypted_bytes)).map_err(|e| {
+            e.into_bytes().zeroize();
+            "Property value is not valid UTF-8"
+        })?;
 
-    Ok(decrypted_value)
+    Ok(Zeroizing::new(decrypted_value))
 }
 
 pub fn is_password_set() -> bool {
@@ -190,114 +285,186 @@ pub fn is_password_set() -> bool {
             conn.prepare("SELECT COUNT(*) FROM properties WHERE property_key = ?1")
         {
             let count: Result<i64, _> = stmt.query_row([OSANWE_KEY], |row| row.get(0));
-            return count.map_or(false, |c| c > 0);
+            return count.is_ok_and(|c| c > 0);
         }
     }
     false
 }
 
-pub fn is_password_correct(external_key: &[u8]) -> SqlResult<bool> {
-    match get_property_by_key(OSANWE_KEY, external_key) {
-        Ok(test_phrase) => Ok(test_phrase == TEST_PHRASE),
+/// Перевіряє пароль і виводить з нього ключ властивостей; `None` — пароль неправильний.
+/// Ключ виводиться один раз, і його передають далі в усі виклики, що читають властивості.
+/// Стару базу (ключ Keccak256(пароль), значення в AES-CBC) при першому успішному
+/// розблокуванні перешифровує ключем з Argon2id в AES-GCM і повертає вже новий ключ.
+pub fn unlock(external_key: &[u8]) -> Result<Option<PropertyKey>, Box<dyn Error>> {
+    let conn = get_db_connection()?;
+    let property_key = property_key(&conn, external_key)?;
+    match read_secret_property(&conn, OSANWE_KEY, &property_key) {
+        Ok(test_phrase) if test_phrase.as_str() == TEST_PHRASE => {}
+        Ok(_) => return Ok(None),
         Err(e) => {
             log::error!("Failed to decrypt password: {}", e);
-            Ok(false) // Якщо не вдалося розшифрувати, пароль неправильний
+            return Ok(None); // Якщо не вдалося розшифрувати, пароль неправильний
+        }
+    }
+    drop(conn);
+
+    match migrate_legacy_encryption(&property_key, external_key) {
+        Ok(Some(migrated_key)) => Ok(Some(migrated_key)),
+        Ok(None) => Ok(Some(property_key)),
+        Err(e) => {
+            // Старий формат лишається читабельним, тож спробуємо ще раз наступного разу
+            log::error!("Failed to migrate the database encryption: {}", e);
+            Ok(Some(property_key))
         }
     }
 }
 
-pub fn set_password(external_key: &[u8]) -> Result<(), Box<dyn Error>> {
-    let conn = get_db_connection()?;
-    create_database(&conn)?;
-    match insert_property(OSANWE_KEY, TEST_PHRASE, external_key) {
-        Ok(_) =>  log::info!("Password has been successfully set."),
+/// Новий ключ, якщо базу довелося перешифрувати.
+fn migrate_legacy_encryption(
+    property_key: &PropertyKey,
+    external_key: &[u8],
+) -> Result<Option<PropertyKey>, Box<dyn Error>> {
+    // База з параметрами Argon2id уже зберігає всі значення в AES-GCM
+    if load_kdf_params(&get_db_connection()?)?.is_some() {
+        return Ok(None);
+    }
+
+    let migrated_key = rekey(property_key, external_key)?;
+    log::info!("Database encryption has been migrated to Argon2id and AES-GCM.");
+    Ok(Some(migrated_key))
+}
+
+/// Встановлює пароль і повертає ключ властивостей для подальших записів. Ключ гаманця
+/// створюється окремо: `keys::generate_save_keypair` або `keys::restore_keypair`.
+pub fn set_password(external_key: &[u8]) -> Result<PropertyKey, Box<dyn Error>> {
+    let mut conn = get_db_connection()?;
+    create_database(&mut conn)?;
+    // Нова база одразу отримує власну випадкову сіль
+    if load_kdf_params(&conn)?.is_none() {
+        store_kdf_params(&conn, &KdfParams::generate()?)?;
+    }
+    let property_key = property_key(&conn, external_key)?;
+    match insert_property(OSANWE_KEY, TEST_PHRASE, &property_key) {
+        Ok(_) => log::info!("Password has been successfully set."),
         Err(e) => {
             log::error!("Error setting password in database: {}", e);
             return Err(e);
         }
     }
-    let address = keys::generate_save_keypair(external_key);
-    log::info!("Generated Ethereum Address: {:?}", address);
-    Ok(())
+    Ok(property_key)
 }
 
-/// Перевіряє, чи існує таблиця CryptoAssets. Якщо ні - зчитує файл SQL та виконує його.
-/// Вважаємо, що у файлі CryptoAssets.sql є CREATE TABLE та INSERT-и.
-pub fn create_cryptoassets_table_if_needed() -> Result<(), Box<dyn Error>> {
+pub fn insert_account(account: &Account) -> Result<(), Box<dyn Error>> {
     let conn = get_db_connection()?;
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='CryptoAssets';",
-        [],
-        |row| row.get(0),
+    conn.execute(
+        "INSERT INTO accounts (account_index, label, address) VALUES (?1, ?2, ?3)",
+        params![account.account_index, account.label, account.address],
     )?;
-
-    // Якщо count == 0 — таблиці ще немає
-    if count == 0 {
-        // Build the absolute path to CryptoAssets.sql using the crate’s root
-        let sql_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("src")
-            .join("db")
-            .join("CryptoAssets.sql");
-
-        // Optionally check if the file actually exists:
-        if !sql_path.exists() {
-            return Err(format!("SQL file not found at {}", sql_path.display()).into());
-        }
-
-        let sql = fs::read_to_string(sql_path)?;
-        conn.execute_batch(&sql)?;
-        log::info!("Table 'CryptoAssets' created and data inserted from CryptoAssets.sql");
-    } else {
-        log::info!("Table 'CryptoAssets' already exists. No action needed.");
-    }
-
     Ok(())
 }
 
-/// Ensures that the 'transactions' table exists in the database.
-/// If it does not exist, it creates the table by executing the SQL in 'transactions.sql'.
-fn ensure_transactions_table_exists() -> Result<(), Box<dyn Error>> {
+/// Усі рахунки в порядку `account_index`.
+pub fn get_accounts() -> Result<Vec<Account>, Box<dyn Error>> {
     let conn = get_db_connection()?;
+    let mut stmt =
+        conn.prepare("SELECT account_index, label, address FROM accounts ORDER BY account_index")?;
+    let accounts = stmt
+        .query_map([], |row| {
+            Ok(Account {
+                account_index: row.get(0)?,
+                label: row.get(1)?,
+                address: row.get(2)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+    Ok(accounts)
+}
 
-    // Check if the 'transactions' table exists
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='transactions';",
-        [],
-        |row| row.get(0),
+pub fn insert_multisig_account(account: &MultisigAccount) -> Result<(), Box<dyn Error>> {
+    let conn = get_db_connection()?;
+    conn.execute(
+        "INSERT INTO multisig_accounts (label, address, threshold, signers) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            account.label,
+            account.address,
+            account.threshold,
+            account.signers.join(",")
+        ],
     )?;
+    Ok(())
+}
 
-    if count == 0 {
-        // Path to the 'transactions.sql' file
-        let sql_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("src")
-            .join("db")
-            .join("transactions.sql");
-
-        // Verify that the SQL file exists
-        if !sql_path.exists() {
-            return Err(format!(
-                "SQL file for creating 'transactions' table not found at {}",
-                sql_path.display()
-            )
-            .into());
-        }
+/// Усі мультипідписні рахунки в порядку міток.
+pub fn get_multisig_accounts() -> Result<Vec<MultisigAccount>, Box<dyn Error>> {
+    let conn = get_db_connection()?;
+    let mut stmt = conn.prepare(
+        "SELECT label, address, threshold, signers FROM multisig_accounts ORDER BY label",
+    )?;
+    let accounts = stmt
+        .query_map([], |row| {
+            let signers: String = row.get(3)?;
+            Ok(MultisigAccount {
+                label: row.get(0)?,
+                address: row.get(1)?,
+                threshold: row.get(2)?,
+                signers: signers.split(',').map(str::to_owned).collect(),
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+    Ok(accounts)
+}
 
-        // Read and execute the SQL statements from 'transactions.sql'
-        let sql = fs::read_to_string(sql_path)?;
-        conn.execute_batch(&sql)?;
-        log::info!("Table 'transactions' created successfully.");
-    } else {
-        log::info!("Table 'transactions' already exists. No action needed.");
-    }
+/// Змінює пароль: усі значення `properties` розшифровуються ключем поточного пароля
+/// (з [`unlock`]) і шифруються новим в одній транзакції SQLite — або всі, або жодне.
+pub fn change_password(property_key: &PropertyKey, new_key: &[u8]) -> Result<(), Box<dyn Error>> {
+    rekey(property_key, new_key)?;
 
+    log::info!("Password has been successfully changed.");
     Ok(())
 }
 
-pub fn save_transaction(tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
-    ensure_transactions_table_exists()?;
+/// Перешифровує всі властивості ключем з нового пароля і нової солі та повертає цей ключ.
+/// Нові параметри KDF зберігаються в тій самій транзакції, що й перешифровані значення.
+fn rekey(old_property_key: &PropertyKey, new_key: &[u8]) -> Result<PropertyKey, Box<dyn Error>> {
+    let new_params = KdfParams::generate()?;
+    let new_property_key = PropertyKey::derive(new_key, Some(&new_params))?;
 
-    let conn = get_db_connection()?;
+    let mut conn = get_db_connection()?;
+    // IMMEDIATE одразу бере блокування на запис, щоб ніхто не додав властивість між читанням і записом
+    let db_tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    {
+        let mut stmt = db_tx.prepare("SELECT property_key, property_value FROM properties")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        for (key, encrypted_value) in rows {
+            let value = old_property_key
+                .decrypt(&key, &encrypted_value)
+                .map_err(|e| format!("Cannot decrypt property '{}': {}", key, e))?;
+            db_tx.execute(
+                "UPDATE properties SET property_value = ?2 WHERE property_key = ?1",
+                (&key, new_property_key.encrypt(&key, &value)?),
+            )?;
+        }
+    }
+    store_kdf_params(&db_tx, &new_params)?;
+    db_tx.commit()?;
+    Ok(new_property_key)
+}
+
+pub fn save_transaction(tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
+    let mut conn = get_db_connection()?;
+    let db_tx = conn.transaction()?;
+    insert_transaction(&db_tx, tx_db)?;
+    db_tx.commit()?;
+    Ok(())
+}
 
+/// Записує транзакцію й оновлює `balances`; викликати лише всередині транзакції SQLite.
+fn insert_transaction(conn: &Connection, tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
     let mut stmt = conn.prepare(
         "INSERT INTO transactions (
             transaction_hash,
@@ -309,10 +476,20 @@ pub fn save_transaction(tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
             sender_output_index,
             recipient_address,
             sender_signature,
-            source_transaction_hash
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            source_transaction_hash,
+            signature_version,
+            network_id,
+            multisig
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
     )?;
 
+    // Підписи мультипідпису зберігаються одним JSON-документом
+    let multisig = tx_db
+        .multisig
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
     stmt.execute(params![
         &tx_db.transaction_hash,
         tx_db.transaction_type,
@@ -324,18 +501,32 @@ pub fn save_transaction(tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
         &tx_db.recipient_address,
         &tx_db.sender_signature,
         &tx_db.source_transaction_hash,
+        tx_db.signature_version,
+        &tx_db.network_id,
+        multisig,
     ])?;
+    balances::apply_transaction(conn, tx_db)?;
 
     log::info!("Transaction saved successfully.");
     Ok(())
 }
 
 pub fn get_transaction_by_hash(transaction_hash: &str) -> Result<TransactionDb, Box<dyn Error>> {
-    let conn = get_db_connection()?;
+    Ok(select_transaction(&get_db_connection()?, transaction_hash)?)
+}
 
-    let mut stmt = conn.prepare(
-        "SELECT 
-            transaction_hash,
+/// Сторінка історії транзакцій за `filter` (див. [`HistoryFilter`]), від новіших до старіших.
+/// Наступну сторінку повертає виклик з `cursor` = `next_cursor` попередньої.
+pub fn get_transaction_history(
+    filter: &HistoryFilter,
+    cursor: Option<&HistoryCursor>,
+    limit: usize,
+) -> Result<HistoryPage, Box<dyn Error>> {
+    history::query(&get_db_connection()?, filter, cursor, limit)
+}
+
+/// Стовпці `transactions` у порядку, який читає [`transaction_from_row`]
+const TRANSACTION_COLUMNS: &str = "transaction_hash,
             transaction_type,
             currency_id,
             amount,
@@ -344,32 +535,47 @@ pub fn get_transaction_by_hash(transaction_hash: &str) -> Result<TransactionDb,
             sender_output_index,
             recipient_address,
             sender_signature,
-            source_transaction_hash
-         FROM transactions 
-         WHERE transaction_hash = ?1",
-    )?;
+            source_transaction_hash,
+            signature_version,
+            network_id,
+            multisig";
 
-    let tx_db = stmt.query_row(params![transaction_hash], |row| {
-        Ok(TransactionDb {
-            transaction_hash: row.get(0)?,
-            transaction_type: row.get(1)?,
-            currency_id: row.get(2)?,
-            amount: row.get(3)?,
-            timestamp: row.get(4)?,
-            sender_address: row.get::<_, Option<String>>(5)?, // Очікуємо NULL
-            sender_output_index: row.get::<_, Option<i64>>(6)?.map(|v| v as u32),
-            recipient_address: row.get(7)?,
-            sender_signature: row.get::<_, Option<String>>(8)?, // Очікуємо NULL
-            source_transaction_hash: row.get::<_, Option<String>>(9)?, // Очікуємо NULL
-        })
-    })?;
+fn select_transaction(conn: &Connection, transaction_hash: &str) -> SqlResult<TransactionDb> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM transactions WHERE transaction_hash = ?1",
+        TRANSACTION_COLUMNS
+    ))?;
+
+    let tx_db = stmt.query_row(params![transaction_hash], transaction_from_row)?;
 
     Ok(tx_db)
 }
 
-pub fn get_next_sender_output_index(sender_address: &str) -> Result<u32, Box<dyn Error>> {
-    let conn = get_db_connection()?;
+fn transaction_from_row(row: &rusqlite::Row) -> SqlResult<TransactionDb> {
+    Ok(TransactionDb {
+        transaction_hash: row.get(0)?,
+        transaction_type: row.get(1)?,
+        currency_id: row.get(2)?,
+        amount: row.get(3)?,
+        timestamp: row.get(4)?,
+        sender_address: row.get::<_, Option<String>>(5)?, // Очікуємо NULL
+        sender_output_index: row.get::<_, Option<i64>>(6)?.map(|v| v as u32),
+        recipient_address: row.get(7)?,
+        sender_signature: row.get::<_, Option<String>>(8)?, // Очікуємо NULL
+        source_transaction_hash: row.get::<_, Option<String>>(9)?, // Очікуємо NULL
+        signature_version: row.get(10)?,
+        network_id: row.get(11)?,
+        multisig: row
+            .get::<_, Option<String>>(12)?
+            .map(|json| serde_json::from_str::<MultisigDb>(&json))
+            .transpose()
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(12, Type::Text, Box::new(e)))?,
+    })
+}
 
+/// Наступний `sender_output_index` відправника. Щоб два процеси не взяли той самий індекс,
+/// його треба перевіряти в одній транзакції із записом (див. [`Store::insert_outgoing`]).
+fn next_sender_output_index(conn: &Connection, sender_address: &str) -> SqlResult<u32> {
     let max_index: Option<i64> = conn.query_row(
         "SELECT MAX(sender_output_index) FROM transactions WHERE sender_address = ?1",
         params![sender_address],
@@ -382,44 +588,30 @@ pub fn get_next_sender_output_index(sender_address: &str) -> Result<u32, Box<dyn
     Ok(next_index as u32)
 }
 
-pub fn get_wallet_balance(wallet_address: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let conn = get_db_connection()?;
-
-    // 1) Всі вхідні amount
-    let mut stmt = conn.prepare("SELECT amount FROM transactions WHERE recipient_address = ?1")?;
-    let incoming_amounts: Vec<String> = stmt
-        .query_map(params![wallet_address], |row| row.get(0))?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    // 2) Всі вихідні amount
-    let mut stmt = conn.prepare("SELECT amount FROM transactions WHERE sender_address = ?1")?;
-    let outgoing_amounts: Vec<String> = stmt
-        .query_map(params![wallet_address], |row| row.get(0))?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    // Використовуємо U256 із crates `ethers`.
-    let mut total = U256::zero();
-
-    // Додаємо все, що прийшло
-    for amount_hex in incoming_amounts {
-        // Відрізаємо `0x`, якщо воно є
-        let s = amount_hex.trim_start_matches("0x");
-        let val = U256::from_str_radix(s, 16)?; // Парсимо шістнадцятковий рядок
-        total = total.checked_add(val).ok_or("Overflow in addition")?;
-    }
+/// Повертає баланс гаманця окремо для кожної криптовалюти:
+/// `currency_id` (id з таблиці `CryptoAssets`) → сума в мінімальних одиницях.
+/// Валюти з нульовим балансом у мапу не потрапляють.
+pub fn get_wallet_balance(wallet_address: &str) -> Result<BTreeMap<u32, U256>, Box<dyn Error>> {
+    balances::wallet_balance(&get_db_connection()?, wallet_address)
+}
 
-    // Віднімаємо все, що пішло
-    for amount_hex in outgoing_amounts {
-        let s = amount_hex.trim_start_matches("0x");
-        let val = U256::from_str_radix(s, 16)?;
-        total = total.checked_sub(val).ok_or("Underflow in subtraction")?;
-    }
+/// Баланс гаманця в одній конкретній криптовалюті.
+pub fn get_wallet_currency_balance(
+    wallet_address: &str,
+    currency_id: u32,
+) -> Result<U256, Box<dyn Error>> {
+    let balances = get_wallet_balance(wallet_address)?;
+    Ok(balances.get(&currency_id).copied().unwrap_or_default())
+}
 
-    // Повертаємо 32 байти в Big-Endian (зазвичай), або хочемо зберегти little?
-    // U256 має методи для виводу байтів:
-    let mut buf = [0u8; 32];
-    total.to_big_endian(&mut buf);
-    Ok(buf.to_vec())
+/// Перераховує таблицю `balances` з історії транзакцій і записує результат.
+/// Повертає рядки, які до перерахунку з нею не збігалися.
+pub fn rebuild_balances() -> Result<Vec<BalanceMismatch>, Box<dyn Error>> {
+    let mut conn = get_db_connection()?;
+    let db_tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mismatches = balances::rebuild(&db_tx)?;
+    db_tx.commit()?;
+    Ok(mismatches)
 }
 
 #[cfg(test)]
@@ -430,7 +622,6 @@ mod tests {
     use crate::tx::to_transaction_db;
     use uuid::Uuid;
 
-
     #[test]
     fn test_conversion_to_transaction_db() {
         let pb = TransactionPb {
@@ -444,6 +635,9 @@ mod tests {
             recipient_address: vec![0xDD; 20],
             sender_signature: vec![0xEE; 65],
             source_transaction_hash: vec![0xFF; 32],
+            signature_version: 1,
+            network_id: "osanwe-test".to_owned(),
+            multisig: None,
         };
         let db = to_transaction_db(&pb);
 
@@ -470,6 +664,9 @@ mod tests {
             recipient_address: vec![0xDD; 20],
             sender_signature: Vec::new(),        // Відсутній підпис
             source_transaction_hash: Vec::new(), // Відсутній хеш
+            signature_version: 0,
+            network_id: String::new(),
+            multisig: None,
         };
         let db = to_transaction_db(&pb);
 
@@ -493,6 +690,9 @@ mod tests {
             recipient_address: "0x".to_owned() + &"DD".repeat(20),
             sender_signature: Some("0x".to_owned() + &"EE".repeat(65)),
             source_transaction_hash: Some("0x".to_owned() + &"FF".repeat(32)),
+            signature_version: Some(1),
+            network_id: Some("osanwe-test".to_owned()),
+            multisig: None,
         };
         let pb = from_transaction_db(&db).unwrap();
 
@@ -516,6 +716,9 @@ mod tests {
             recipient_address: "0x".to_owned() + &"DD".repeat(20),
             sender_signature: None,
             source_transaction_hash: None,
+            signature_version: None,
+            network_id: None,
+            multisig: None,
         };
         let pb = from_transaction_db(&db).unwrap();
 
@@ -526,6 +729,89 @@ mod tests {
         assert!(pb.source_transaction_hash.is_empty());
     }
 
+    #[test]
+    fn test_builtin_cryptoassets_need_no_database() {
+        let assets = builtin_cryptoassets().unwrap();
+        let eth = assets.iter().find(|asset| asset.id == 16842752).unwrap();
+        assert_eq!((eth.symbol.as_str(), eth.decimals), ("ETH", 18));
+        assert_eq!(eth.evm_chain_id, Some(1));
+    }
+
+    #[test]
+    fn test_change_password_reencrypts_all_properties() {
+        let unique_db_uri = format!("file:{}?mode=memory&cache=shared", Uuid::new_v4());
+        std::env::set_var("TEST_DB_URI", unique_db_uri);
+        let _persistent_conn = Connection::open(std::env::var("TEST_DB_URI").unwrap())
+            .expect("Failed to open persistent connection");
+
+        check_and_create_database().unwrap();
+        let old_key = set_password(b"old").unwrap();
+        insert_property("priv_key", "secret", &old_key).unwrap();
+
+        // Неправильний старий пароль не дає ключа, тож і змінити пароль не дасть
+        assert!(unlock(b"wrong").unwrap().is_none());
+
+        change_password(&old_key, b"new").unwrap();
+        assert!(unlock(b"old").unwrap().is_none());
+        let new_key = unlock(b"new").unwrap().unwrap();
+        assert_eq!(get_property_by_key("priv_key", &new_key).unwrap(), "secret");
+        assert_eq!(
+            get_secret_property("priv_key", &new_key).unwrap().as_str(),
+            "secret"
+        );
+        // Ключ, виведений зі старого пароля до зміни, більше нічого не розшифровує
+        assert!(get_property_by_key("priv_key", &old_key).is_err());
+
+        // Зміна пароля генерує нову сіль
+        let salt = get_kdf_params().unwrap().unwrap().salt;
+        change_password(&new_key, b"newer").unwrap();
+        assert_ne!(get_kdf_params().unwrap().unwrap().salt, salt);
+    }
+
+    #[test]
+    fn test_legacy_database_is_migrated_on_unlock() {
+        let unique_db_uri = format!("file:{}?mode=memory&cache=shared", Uuid::new_v4());
+        std::env::set_var("TEST_DB_URI", unique_db_uri);
+        let _persistent_conn = Connection::open(std::env::var("TEST_DB_URI").unwrap())
+            .expect("Failed to open persistent connection");
+
+        // Стара база: жодних kdf_params, значення зашифровані Keccak256(пароль)
+        let mut conn = get_db_connection().unwrap();
+        create_database(&mut conn).unwrap();
+        let legacy = PropertyKey::derive(b"pw", None).unwrap();
+        for (key, value) in [(OSANWE_KEY, TEST_PHRASE), ("priv_key", "secret")] {
+            conn.execute(
+                "INSERT INTO properties (property_key, property_value) VALUES (?1, ?2)",
+                (key, legacy.encrypt(key, value.as_bytes()).unwrap()),
+            )
+            .unwrap();
+        }
+
+        assert!(unlock(b"wrong").unwrap().is_none());
+        assert!(get_kdf_params().unwrap().is_none());
+
+        let property_key = unlock(b"pw").unwrap().expect("Password must be accepted");
+        let params = get_kdf_params()
+            .unwrap()
+            .expect("KDF params must be stored");
+        assert_eq!(params.salt.len(), 16);
+
+        let stored: String = conn
+            .query_row(
+                "SELECT property_value FROM properties WHERE property_key = 'priv_key'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(stored.starts_with("v3$"));
+        // unlock повертає вже новий ключ, а не той, що пасував до старої бази
+        assert_eq!(
+            get_property_by_key("priv_key", &property_key).unwrap(),
+            "secret"
+        );
+        assert!(get_property_by_key("priv_key", &legacy).is_err());
+    }
+
     #[test]
     fn test_save_and_fetch_transaction_with_missing_fields() {
         // Генеруємо унікальний URI для in‑memory бази:
@@ -533,7 +819,7 @@ mod tests {
         std::env::set_var("TEST_DB_URI", unique_db_uri);
 
         // Відкриваємо постійне з’єднання і зберігаємо його в змінній, щоб база існувала протягом усього тесту
-        let _persistent_conn = Connection::open(&std::env::var("TEST_DB_URI").unwrap())
+        let _persistent_conn = Connection::open(std::env::var("TEST_DB_URI").unwrap())
             .expect("Failed to open persistent connection");
 
         check_and_create_database().unwrap();
@@ -543,7 +829,7 @@ mod tests {
                 .to_string(),
             transaction_type: 2,
             currency_id: 200,
-            amount: "0xabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdef"
+            amount: "0x00000000abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefab"
                 .to_string(),
             timestamp: 1700000001,
             sender_address: None,
@@ -551,6 +837,9 @@ mod tests {
             recipient_address: "0xrecipientaddressabcdef123456".to_string(),
             sender_signature: None,
             source_transaction_hash: None,
+            signature_version: None,
+            network_id: None,
+            multisig: None,
         };
 
         save_transaction(&tx_db).unwrap();
@@ -579,7 +868,7 @@ mod tests {
         std::env::set_var("TEST_DB_URI", unique_db_uri);
 
         // Відкриваємо постійне з’єднання і зберігаємо його в змінній, щоб база існувала протягом усього тесту
-        let _persistent_conn = Connection::open(&std::env::var("TEST_DB_URI").unwrap())
+        let _persistent_conn = Connection::open(std::env::var("TEST_DB_URI").unwrap())
             .expect("Failed to open persistent connection");
         // Створюємо базу і таблицю
         check_and_create_database().unwrap();
@@ -593,5 +882,102 @@ mod tests {
         // Наприклад, перевіримо, що там є щонайменше один запис із символом ETH
         let contains_eth = assets.iter().any(|a| a.symbol == "ETH");
         assert!(contains_eth, "There must be an ETH entry in the list");
+
+        // ERC20 USDT має 6 десяткових знаків, контракт і chain id Ethereum mainnet
+        let usdt = assets
+            .iter()
+            .find(|a| a.id == 16842753)
+            .expect("USDT must exist");
+        assert_eq!(usdt.decimals, 6);
+        assert_eq!(usdt.evm_chain_id, Some(1));
+        assert!(usdt.contract_address.is_some());
+        assert_eq!(get_cryptoasset_decimals(50331648).unwrap(), 8); // BTC
+        assert!(get_cryptoasset_decimals(1).is_err());
+    }
+
+    #[test]
+    fn test_wallet_balance_is_kept_per_currency() {
+        let unique_db_uri = format!("file:{}?mode=memory&cache=shared", Uuid::new_v4());
+        std::env::set_var("TEST_DB_URI", unique_db_uri);
+        let _persistent_conn = Connection::open(std::env::var("TEST_DB_URI").unwrap())
+            .expect("Failed to open persistent connection");
+        check_and_create_database().unwrap();
+
+        let wallet = "0x".to_owned() + &"cc".repeat(20);
+        let other = "0x".to_owned() + &"dd".repeat(20);
+        let tx = |hash: u8, currency_id: u32, value: u64, sender: Option<&str>, recipient: &str| {
+            TransactionDb {
+                transaction_hash: "0x".to_owned() + &format!("{:02x}", hash).repeat(32),
+                transaction_type: if sender.is_some() { 2 } else { 1 },
+                currency_id,
+                amount: format!("0x{:064x}", value),
+                timestamp: 1700000000,
+                sender_address: sender.map(str::to_owned),
+                sender_output_index: sender.map(|_| hash as u32),
+                recipient_address: recipient.to_owned(),
+                sender_signature: None,
+                source_transaction_hash: None,
+                signature_version: None,
+                network_id: None,
+                multisig: None,
+            }
+        };
+
+        save_transaction(&tx(1, 16842752, 500, None, &wallet)).unwrap();
+        save_transaction(&tx(2, 16973825, 70, None, &wallet)).unwrap();
+        save_transaction(&tx(3, 16842752, 200, Some(&wallet), &other)).unwrap();
+        save_transaction(&tx(4, 16973825, 70, Some(&wallet), &other)).unwrap();
+
+        // ETH і USDT (Polygon) рахуються окремо, повністю витрачена валюта зникає зі списку
+        let balances = get_wallet_balance(&wallet).unwrap();
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances.get(&16842752), Some(&U256::from(300)));
+        assert_eq!(
+            get_wallet_currency_balance(&wallet, 16973825).unwrap(),
+            U256::zero()
+        );
+
+        let other_balances = get_wallet_balance(&other).unwrap();
+        assert_eq!(other_balances.get(&16842752), Some(&U256::from(200)));
+        assert_eq!(other_balances.get(&16973825), Some(&U256::from(70)));
+
+        // Виведення (тип 3) спалює баланс відправника і нічого не зараховує
+        // адресі призначення, навіть якщо вона збігається з гаманцем Osanwe
+        let mut withdrawal = tx(5, 16842752, 100, Some(&other), &wallet);
+        withdrawal.transaction_type = 3;
+        save_transaction(&withdrawal).unwrap();
+        assert_eq!(
+            get_wallet_currency_balance(&other, 16842752).unwrap(),
+            U256::from(100)
+        );
+        assert_eq!(
+            get_wallet_currency_balance(&wallet, 16842752).unwrap(),
+            U256::from(300)
+        );
+
+        // Перерахунок з історії збігається з балансами, оновленими при вставці
+        assert!(rebuild_balances().unwrap().is_empty());
+
+        // Розбіжність перерахунок знаходить і виправляє
+        _persistent_conn
+            .execute(
+                "UPDATE balances SET amount = ?1 WHERE address = ?2 AND currency_id = 16842752",
+                params![format!("0x{:064x}", 1), &wallet],
+            )
+            .unwrap();
+        let mismatches = rebuild_balances().unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].address, wallet);
+        assert_eq!(mismatches[0].stored, 1.into());
+        assert_eq!(mismatches[0].recomputed, 300.into());
+        assert_eq!(
+            get_wallet_currency_balance(&wallet, 16842752).unwrap(),
+            U256::from(300)
+        );
+        assert!(rebuild_balances().unwrap().is_empty());
+
+        let usdt = get_cryptoasset_by_id(16973825).unwrap().unwrap();
+        assert_eq!(usdt.symbol, "USDT");
+        assert!(get_cryptoasset_by_id(1).unwrap().is_none());
     }
 }
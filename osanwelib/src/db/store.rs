@@ -0,0 +1,289 @@
+use super::balances::wallet_balance;
+use super::{get_db_connection, insert_transaction, next_sender_output_index, select_transaction};
+use crate::tx::TransactionDb;
+use ethers::types::U256;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+/// Скільки чекати, поки інший процес (CLI, десктоп, агент) відпустить блокування бази
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Скільки разів `insert_outgoing` підписує заново, якщо база змінилась під час підпису
+const OUTGOING_ATTEMPTS: usize = 5;
+
+/// Сховище транзакцій гаманця, що володіє одним з'єднанням з базою.
+///
+/// Перевірка балансу та індексу перед записом виконується в одній IMMEDIATE-транзакції
+/// SQLite разом із записом, тож два процеси з тією самою базою не витратять ті самі
+/// кошти двічі і не візьмуть однаковий `sender_output_index`.
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Відкриває базу активного профілю (див. `database_path`).
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        Self::with_connection(get_db_connection()?)
+    }
+
+    /// Відкриває базу за шляхом `path`.
+    pub fn open_at(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // У WAL читачі не чекають на запис і навпаки; база в пам'яті лишається в режимі memory
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Ok(Store { conn })
+    }
+
+    /// Транзакція за хешем, якщо вона є.
+    pub fn transaction_by_hash(
+        &self,
+        transaction_hash: &str,
+    ) -> Result<Option<TransactionDb>, Box<dyn Error>> {
+        Ok(select_transaction(&self.conn, transaction_hash).optional()?)
+    }
+
+    /// Баланс гаманця за валютами (див. `get_wallet_balance`).
+    pub fn wallet_balance(
+        &self,
+        wallet_address: &str,
+    ) -> Result<BTreeMap<u32, U256>, Box<dyn Error>> {
+        wallet_balance(&self.conn, wallet_address)
+    }
+
+    /// Баланс гаманця в одній валюті.
+    pub fn wallet_currency_balance(
+        &self,
+        wallet_address: &str,
+        currency_id: u32,
+    ) -> Result<U256, Box<dyn Error>> {
+        currency_balance(&self.conn, wallet_address, currency_id)
+    }
+
+    /// Наступний `sender_output_index` відправника. Лише для транзакцій, які зберігаються
+    /// пізніше (пропозиції мультипідпису); власні перекази беруть індекс у [`Self::insert_outgoing`].
+    pub fn next_sender_output_index(&self, sender_address: &str) -> Result<u32, Box<dyn Error>> {
+        Ok(next_sender_output_index(&self.conn, sender_address)?)
+    }
+
+    /// Зберігає транзакцію, якщо транзакції з таким хешем ще немає.
+    /// Повертає `false`, якщо вона вже була збережена.
+    pub fn save_transaction(&mut self, tx_db: &TransactionDb) -> Result<bool, Box<dyn Error>> {
+        let db_tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if select_transaction(&db_tx, &tx_db.transaction_hash)
+            .optional()?
+            .is_some()
+        {
+            return Ok(false);
+        }
+        insert_transaction(&db_tx, tx_db)?;
+        db_tx.commit()?;
+        Ok(true)
+    }
+
+    /// Формує і зберігає вихідну транзакцію `sender` у валюті `currency_id`.
+    ///
+    /// Баланс відправника і наступний `sender_output_index` читаються без блокування й
+    /// передаються в `build` (той формує і підписує транзакцію — зовнішній підписувач може
+    /// чекати на людину). Потім в одній IMMEDIATE-транзакції перевіряється, що індекс досі
+    /// наступний, а баланс досі покриває суму, і транзакція записується. Якщо інший процес
+    /// тим часом щось списав, усе повторюється з новими балансом та індексом.
+    pub fn insert_outgoing<F>(
+        &mut self,
+        sender_address: &str,
+        currency_id: u32,
+        mut build: F,
+    ) -> Result<TransactionDb, Box<dyn Error>>
+    where
+        F: FnMut(U256, u32) -> Result<TransactionDb, Box<dyn Error>>,
+    {
+        for _ in 0..OUTGOING_ATTEMPTS {
+            let balance = currency_balance(&self.conn, sender_address, currency_id)?;
+            let sender_output_index = next_sender_output_index(&self.conn, sender_address)?;
+
+            let tx_db = build(balance, sender_output_index)?;
+            if tx_db.sender_address.as_deref() != Some(sender_address)
+                || tx_db.sender_output_index != Some(sender_output_index)
+                || tx_db.currency_id != currency_id
+            {
+                return Err(
+                    "Outgoing transaction does not match the allocated sender and index".into(),
+                );
+            }
+            let amount = U256::from_str_radix(tx_db.amount.trim_start_matches("0x"), 16)?;
+            if amount > balance {
+                return Err(format!("Insufficient funds of currency {}", currency_id).into());
+            }
+
+            let db_tx = self
+                .conn
+                .transaction_with_behavior(TransactionBehavior::Immediate)?;
+            if next_sender_output_index(&db_tx, sender_address)? != sender_output_index
+                || currency_balance(&db_tx, sender_address, currency_id)? < amount
+            {
+                // Підпис уже не дійсний для стану бази: без запису відпускаємо блокування
+                continue;
+            }
+            insert_transaction(&db_tx, &tx_db)?;
+            db_tx.commit()?;
+            return Ok(tx_db);
+        }
+        Err(format!(
+            "Failed to save the outgoing transaction: the wallet changed during {} attempts",
+            OUTGOING_ATTEMPTS
+        )
+        .into())
+    }
+}
+
+fn currency_balance(
+    conn: &Connection,
+    wallet_address: &str,
+    currency_id: u32,
+) -> Result<U256, Box<dyn Error>> {
+    Ok(wallet_balance(conn, wallet_address)?
+        .get(&currency_id)
+        .copied()
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    fn transaction(
+        hash: u8,
+        sender: Option<&str>,
+        sender_output_index: Option<u32>,
+        recipient: &str,
+        amount: u64,
+    ) -> TransactionDb {
+        TransactionDb {
+            transaction_hash: format!("0x{}", hex::encode([hash; 32])),
+            transaction_type: if sender.is_some() { 2 } else { 1 },
+            currency_id: 16842752,
+            amount: format!("0x{:064x}", amount),
+            timestamp: 1700000000,
+            sender_address: sender.map(str::to_owned),
+            sender_output_index,
+            recipient_address: recipient.to_owned(),
+            sender_signature: None,
+            source_transaction_hash: None,
+            signature_version: None,
+            network_id: None,
+            multisig: None,
+        }
+    }
+
+    #[test]
+    fn test_concurrent_sends_cannot_overspend() {
+        // Файл, а не спільна база в пам'яті: перевіряємо справжнє блокування між з'єднаннями
+        let path = std::env::temp_dir().join(format!("osanwe-{}.db", uuid::Uuid::new_v4()));
+        let wallet = "0x00000000000000000000000000000000000000aa";
+        {
+            let mut store = Store::open_at(&path).unwrap();
+            super::super::create_database(&mut store.conn).unwrap();
+            assert!(store
+                .save_transaction(&transaction(1, None, None, wallet, 10))
+                .unwrap());
+            // Повторне збереження тієї самої транзакції нічого не змінює
+            assert!(!store
+                .save_transaction(&transaction(1, None, None, wallet, 10))
+                .unwrap());
+        }
+
+        // Кожна з двох відправок сама по собі проходить перевірку балансу, але не обидві разом
+        let barrier = Arc::new(Barrier::new(2));
+        let handles: Vec<_> = (0..2u8)
+            .map(|i| {
+                let (path, barrier) = (path.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    let mut store = Store::open_at(&path).map_err(|e| e.to_string())?;
+                    barrier.wait();
+                    store
+                        .insert_outgoing(wallet, 16842752, |balance, index| {
+                            // Вікно між перевіркою і записом, в яке раніше встигав інший процес
+                            std::thread::sleep(Duration::from_millis(50));
+                            if balance < U256::from(7) {
+                                return Err("Insufficient funds".into());
+                            }
+                            Ok(transaction(2 + i, Some(wallet), Some(index), "0xbb", 7))
+                        })
+                        .map(|tx_db| tx_db.sender_output_index)
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(
+            results.iter().filter(|r| r.is_ok()).count(),
+            1,
+            "{:?}",
+            results
+        );
+        let store = Store::open_at(&path).unwrap();
+        assert_eq!(
+            store.wallet_currency_balance(wallet, 16842752).unwrap(),
+            U256::from(3)
+        );
+        assert_eq!(store.next_sender_output_index(wallet).unwrap(), 2);
+
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_outgoing_is_signed_outside_the_lock_and_retried() {
+        let path = std::env::temp_dir().join(format!("osanwe-{}.db", uuid::Uuid::new_v4()));
+        let wallet = "0x00000000000000000000000000000000000000aa";
+        let mut store = Store::open_at(&path).unwrap();
+        super::super::create_database(&mut store.conn).unwrap();
+        store
+            .save_transaction(&transaction(1, None, None, wallet, 10))
+            .unwrap();
+
+        let mut attempts = Vec::new();
+        let tx_db = store
+            .insert_outgoing(wallet, 16842752, |balance, index| {
+                attempts.push((balance, index));
+                if attempts.len() == 1 {
+                    // Поки «підписуємо», інший процес записує переказ з тим самим індексом.
+                    // Якби блокування трималось під час підпису, тут був би тайм-аут
+                    let mut other = Store::open_at(&path)?;
+                    other.conn.busy_timeout(Duration::ZERO)?;
+                    other.save_transaction(&transaction(
+                        2,
+                        Some(wallet),
+                        Some(index),
+                        "0xbb",
+                        4,
+                    ))?;
+                }
+                Ok(transaction(3, Some(wallet), Some(index), "0xcc", 5))
+            })
+            .unwrap();
+
+        assert_eq!(attempts, vec![(U256::from(10), 1), (U256::from(6), 2)]);
+        assert_eq!(tx_db.sender_output_index, Some(2));
+        assert_eq!(
+            store.wallet_currency_balance(wallet, 16842752).unwrap(),
+            U256::from(1)
+        );
+
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
@@ -4,7 +4,7 @@ pub struct TransactionPb {
     /// 32 байти: Хеш транзакції
     #[prost(bytes = "vec", tag = "1")]
     pub transaction_hash: ::prost::alloc::vec::Vec<u8>,
-    /// 4 байти: Тип транзакції (1 або 2)
+    /// 4 байти: Тип транзакції (1 - поповнення, 2 - переказ, 3 - виведення)
     #[prost(uint32, tag = "2")]
     pub transaction_type: u32,
     /// 4 байти: Криптовалюта (номер зі довідника)
@@ -22,7 +22,7 @@ pub struct TransactionPb {
     /// 4 байти: Порядковий номер вихідної транзакції відправника
     #[prost(uint32, tag = "8")]
     pub sender_output_index: u32,
-    /// 20 байтів: Адреса отримувача
+    /// 20 байтів: Адреса отримувача (для виведення - адреса в зовнішньому блокчейні)
     #[prost(bytes = "vec", tag = "9")]
     pub recipient_address: ::prost::alloc::vec::Vec<u8>,
     /// 65 байтів: Підпис відправника
@@ -31,6 +31,29 @@ pub struct TransactionPb {
     /// 32 байти: Хеш транзакції поповнення в блокчейні
     #[prost(bytes = "vec", tag = "11")]
     pub source_transaction_hash: ::prost::alloc::vec::Vec<u8>,
+    /// Версія конверта підпису (для переказу і виведення)
+    #[prost(uint32, tag = "12")]
+    pub signature_version: u32,
+    /// Мережа (розгортання) Osanwe, для якої підписано транзакцію
+    #[prost(string, tag = "13")]
+    pub network_id: ::prost::alloc::string::String,
+    /// Підписи мультипідписного відправника (тоді sender_signature порожній)
+    #[prost(message, optional, tag = "14")]
+    pub multisig: ::core::option::Option<MultisigAuthorization>,
+}
+/// Відправник m-of-n: sender_address виводиться з порогу і набору адрес підписантів,
+/// а транзакцію підписують щонайменше threshold з них (той самий дайджест EIP-712)
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultisigAuthorization {
+    /// m: скільки підписів потрібно
+    #[prost(uint32, tag = "1")]
+    pub threshold: u32,
+    /// n адрес по 20 байтів, у порядку зростання
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub signers: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    /// 65 байтів кожен, по одному від різних підписантів
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub signatures: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct TransactionResponse {
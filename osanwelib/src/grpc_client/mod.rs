@@ -1,14 +1,16 @@
-use crate::generated::{
-    transaction_service_client::TransactionServiceClient, TransactionPb,
-};
+use crate::generated::{transaction_service_client::TransactionServiceClient, TransactionPb};
+use crate::profile;
+
+pub async fn send_transaction_to_server(
+    tx: TransactionPb,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server_url = profile::server_url();
+    log::debug!("Підключення до gRPC сервера за адресою {}", server_url);
+    let mut client = TransactionServiceClient::connect(server_url).await?;
 
-pub async fn send_transaction_to_server(tx: TransactionPb) -> Result<(), Box<dyn std::error::Error>> {
-    log::debug!("Підключення до gRPC сервера за адресою http://[::1]:50051");
-    let mut client = TransactionServiceClient::connect("http://[::1]:50051").await?;
-    
     log::info!("Відправка транзакції на сервер");
     let response = client.submit_transaction(tx).await?;
-    
+
     log::info!("Відповідь сервера: {:?}", response.into_inner());
     Ok(())
 }
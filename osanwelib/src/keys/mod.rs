@@ -1,13 +1,34 @@
-use crate::db;
+use crate::db::{self, Account, PropertyKey};
+use coins_bip32::path::DerivationPath;
+use coins_bip32::xkeys::XPriv;
 use ethers::core::k256::ecdsa::SigningKey;
 use ethers::prelude::*;
+use ethers::signers::coins_bip39::{English, Mnemonic};
 use ethers::utils::keccak256;
 use hex::{decode, encode};
 use rand::thread_rng;
 use std::error::Error;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+mod shamir;
+
+pub use shamir::{BackupShare, SecretKind};
 
 pub const PRIV_KEY: &str = "priv_key";
 pub const WALLET: &str = "wallet";
+/// BIP-39 seed (фраза + passphrase), з якого виведено ключ гаманця
+pub const HD_SEED: &str = "hd_seed";
+
+/// Мітка рахунку з індексом 0 — ключа `PRIV_KEY`, який є в кожному гаманці
+pub const MAIN_ACCOUNT: &str = "main";
+/// Кількість слів у новій BIP-39 фразі
+const MNEMONIC_WORDS: usize = 24;
+/// n / 2 для кривої secp256k1 — межа low-s підписів
+const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
 
 /// Генерує нову пару ключів Ethereum (приватний і публічний).
 pub fn generate_ethereum_keypair() -> (SigningKey, Address) {
@@ -15,49 +36,367 @@ pub fn generate_ethereum_keypair() -> (SigningKey, Address) {
     let wallet = LocalWallet::from(signing_key.clone()); // Створюємо гаманець з цього ключа
     let address = wallet.address(); // Отримуємо Ethereum-адресу
 
-    println!("Private Key: {}", hex::encode(signing_key.to_bytes()));
-    println!("Ethereum Address: {:?}", address);
-
     (signing_key, address)
 }
 
-pub fn generate_save_keypair(external_key: &[u8]) -> Result<Address, Box<dyn std::error::Error>> {
-    // Generate the Ethereum keypair
-    let (signing_key, address) = generate_ethereum_keypair();
+/// Створює гаманець з нової BIP-39 фрази і зберігає ключ у БД.
+/// Повертає адресу і фразу — її треба показати користувачу для резервної копії.
+pub fn generate_save_keypair(
+    property_key: &PropertyKey,
+    passphrase: Option<&str>,
+) -> Result<(Address, Zeroizing<String>), Box<dyn std::error::Error>> {
+    let mnemonic = Mnemonic::<English>::new_with_count(&mut thread_rng(), MNEMONIC_WORDS)?;
+    let phrase = Zeroizing::new(mnemonic.to_phrase());
+
+    let address = restore_keypair(&phrase, passphrase, property_key)?;
+    Ok((address, phrase))
+}
+
+/// Відновлює гаманець з BIP-39 фрази і зберігає ключ у БД.
+pub fn restore_keypair(
+    phrase: &str,
+    passphrase: Option<&str>,
+    property_key: &PropertyKey,
+) -> Result<Address, Box<dyn std::error::Error>> {
+    let seed = seed_from_mnemonic(phrase, passphrase)?;
+    save_seed(seed.as_ref(), property_key)
+}
+
+/// Зберігає в БД seed, ключ рахунку main, виведений з нього, і сам рахунок main.
+fn save_seed(seed: &[u8], property_key: &PropertyKey) -> Result<Address, Box<dyn Error>> {
+    let (signing_key, address) = derive_keypair(seed, 0)?;
 
     // Convert SigningKey to a hex string
-    let signing_key_hex = encode(signing_key.to_bytes());
+    let signing_key_hex = Zeroizing::new(encode(signing_key.to_bytes()));
 
     // Convert Address to a string
     let address_str = format!("{:?}", address); // Alternatively, use address.to_string() if available
 
     // Save the keypair to the database
-    save_keypair(&signing_key_hex, &address_str, external_key)?;
+    save_keypair(&signing_key_hex, &address_str, property_key)?;
+
+    // Seed зберігаємо, щоб виводити з нього наступні рахунки без повторного введення фрази
+    db::insert_property(HD_SEED, &Zeroizing::new(encode(seed)), property_key)?;
+    db::insert_account(&Account {
+        account_index: 0,
+        label: MAIN_ACCOUNT.to_string(),
+        address: address_str,
+    })?;
 
     Ok(address)
 }
 
+/// Виводить ключ першого рахунку (`m/44'/60'/0'/0/0`) з BIP-39 фрази.
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: Option<&str>,
+) -> Result<(SigningKey, Address), Box<dyn Error>> {
+    derive_keypair(seed_from_mnemonic(phrase, passphrase)?.as_ref(), 0)
+}
+
+fn seed_from_mnemonic(
+    phrase: &str,
+    passphrase: Option<&str>,
+) -> Result<Zeroizing<[u8; 64]>, Box<dyn Error>> {
+    let mnemonic = Mnemonic::<English>::new_from_phrase(phrase.trim())?;
+    Ok(Zeroizing::new(mnemonic.to_seed(passphrase)?))
+}
+
+/// Стандартний шлях BIP-44 Ethereum-рахунку з даним індексом.
+pub fn account_derivation_path(account_index: u32) -> String {
+    format!("m/44'/60'/0'/0/{}", account_index)
+}
+
+/// Виводить ключ рахунку `account_index` з BIP-39 seed (BIP-32).
+pub fn derive_keypair(
+    seed: &[u8],
+    account_index: u32,
+) -> Result<(SigningKey, Address), Box<dyn Error>> {
+    let path: DerivationPath = account_derivation_path(account_index).parse()?;
+    let xpriv = XPriv::root_from_seed(seed, None)?.derive_path(path)?;
+    let key: &coins_bip32::prelude::SigningKey = xpriv.as_ref();
+
+    let signing_key = SigningKey::from_bytes(&key.to_bytes())?;
+    let address = ethers::utils::secret_key_to_address(&signing_key);
+    Ok((signing_key, address))
+}
+
+/// Усі рахунки гаманця. Гаманцю, створеному до появи рахунків, додає рахунок `main`.
+pub fn list_accounts(property_key: &PropertyKey) -> Result<Vec<Account>, Box<dyn Error>> {
+    let accounts = db::get_accounts()?;
+    if !accounts.is_empty() {
+        return Ok(accounts);
+    }
+
+    let main = Account {
+        account_index: 0,
+        label: MAIN_ACCOUNT.to_string(),
+        address: get_wallet_address(property_key)?,
+    };
+    db::insert_account(&main)?;
+    Ok(vec![main])
+}
+
+/// Рахунок за міткою або індексом; `None` — рахунок `main`.
+pub fn resolve_account(
+    name: Option<&str>,
+    property_key: &PropertyKey,
+) -> Result<Account, Box<dyn Error>> {
+    let name = name.unwrap_or(MAIN_ACCOUNT);
+    list_accounts(property_key)?
+        .into_iter()
+        .find(|account| account.label == name || account.account_index.to_string() == name)
+        .ok_or_else(|| format!("Unknown account: {}", name).into())
+}
+
+/// Виводить наступний рахунок з seed гаманця і зберігає його під міткою `label`.
+pub fn create_account(label: &str, property_key: &PropertyKey) -> Result<Account, Box<dyn Error>> {
+    // Числові мітки сплутались би з індексами в `resolve_account`
+    if label.is_empty() || label.parse::<u32>().is_ok() {
+        return Err(format!("Invalid account label: '{}'", label).into());
+    }
+
+    let accounts = list_accounts(property_key)?;
+    if accounts.iter().any(|account| account.label == label) {
+        return Err(format!("Account '{}' already exists", label).into());
+    }
+
+    let seed = load_hd_seed(property_key)?;
+    let account_index = accounts.iter().map(|a| a.account_index).max().unwrap_or(0) + 1;
+    let (_, address) = derive_keypair(&seed, account_index)?;
+
+    let account = Account {
+        account_index,
+        label: label.to_string(),
+        address: format!("{:?}", address),
+    };
+    db::insert_account(&account)?;
+    Ok(account)
+}
+
+fn load_hd_seed(property_key: &PropertyKey) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let seed_hex = db::get_secret_property(HD_SEED, property_key).map_err(|_| {
+        "This wallet was created without a recovery phrase, so it has no additional accounts"
+    })?;
+    Ok(Zeroizing::new(decode(seed_hex.as_str())?))
+}
+
 pub fn save_keypair(
     signing_key: &str,
     address: &str,
-    external_key: &[u8],
+    property_key: &PropertyKey,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    db::insert_property(PRIV_KEY, signing_key, external_key)?;
-    db::insert_property(WALLET, address, external_key)?;
+    db::insert_property(PRIV_KEY, signing_key, property_key)?;
+    db::insert_property(WALLET, address, property_key)?;
     Ok(())
 }
 
-pub fn get_wallet_address(external_key: &[u8]) -> Result<String, Box<dyn Error>> {
-    db::get_property_by_key(WALLET, external_key)
+pub fn get_wallet_address(property_key: &PropertyKey) -> Result<String, Box<dyn Error>> {
+    db::get_property_by_key(WALLET, property_key)
+}
+
+/// Приватний ключ рахунку `account_index`.
+pub(crate) fn load_private_key(
+    account_index: u32,
+    property_key: &PropertyKey,
+) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    // Рахунок 0 — збережений `PRIV_KEY` (є і в гаманцях без seed), інші виводяться з seed
+    if account_index == 0 {
+        let priv_key_hex = db::get_secret_property(PRIV_KEY, property_key)?;
+        Ok(Zeroizing::new(decode(priv_key_hex.as_str())?))
+    } else {
+        let seed = load_hd_seed(property_key)?;
+        let (signing_key, _) = derive_keypair(&seed, account_index)?;
+        Ok(Zeroizing::new(signing_key.to_bytes().to_vec()))
+    }
+}
+
+/// Приватний ключ рахунку `account_index` у hex з префіксом 0x — для явного експорту
+/// користувачем. Більше ніде ключ з бібліотеки не виходить.
+pub fn export_private_key(
+    account_index: u32,
+    property_key: &PropertyKey,
+) -> Result<Zeroizing<String>, Box<dyn Error>> {
+    let private_key = load_private_key(account_index, property_key)?;
+    Ok(Zeroizing::new(format!("0x{}", encode(&*private_key))))
+}
+
+/// Ділить секрет гаманця на `count` текстових частин, з яких будь-які `threshold`
+/// відновлюють гаманець. Ділиться seed (тоді відновлюються всі рахунки), а в гаманці
+/// без seed — ключ рахунку main.
+pub fn split_backup(
+    threshold: u8,
+    count: u8,
+    property_key: &PropertyKey,
+) -> Result<Vec<BackupShare>, Box<dyn Error>> {
+    let address: Address = get_wallet_address(property_key)?.parse()?;
+    let wallet_id = backup_wallet_id(&address);
+
+    match db::get_secret_property(HD_SEED, property_key) {
+        Ok(seed_hex) => {
+            let seed = Zeroizing::new(decode(seed_hex.as_str())?);
+            shamir::split(&seed, SecretKind::Seed, wallet_id, threshold, count)
+        }
+        Err(_) => {
+            let private_key = load_private_key(0, property_key)?;
+            shamir::split(
+                &private_key,
+                SecretKind::PrivateKey,
+                wallet_id,
+                threshold,
+                count,
+            )
+        }
+    }
+}
+
+/// Адреса рахунку main гаманця, відновленого з частин, — без запису в БД.
+/// Дає змогу перевірити частини до того, як питати новий пароль.
+pub fn check_backup(shares: &[BackupShare]) -> Result<Address, Box<dyn Error>> {
+    combine_backup(shares).map(|recovered| recovered.address)
+}
+
+/// Відновлює гаманець з частин резервної копії у свіжу БД.
+pub fn restore_backup(
+    shares: &[BackupShare],
+    property_key: &PropertyKey,
+) -> Result<Address, Box<dyn Error>> {
+    let RecoveredBackup {
+        kind,
+        secret,
+        address,
+    } = combine_backup(shares)?;
+    match kind {
+        SecretKind::Seed => save_seed(&secret, property_key),
+        SecretKind::PrivateKey => {
+            let address_str = format!("{:?}", address);
+            save_keypair(
+                &Zeroizing::new(encode(&*secret)),
+                &address_str,
+                property_key,
+            )?;
+            db::insert_account(&Account {
+                account_index: 0,
+                label: MAIN_ACCOUNT.to_string(),
+                address: address_str,
+            })?;
+            Ok(address)
+        }
+    }
+}
+
+/// Секрет, зібраний з частин, і адреса рахунку main, яку він дає.
+struct RecoveredBackup {
+    kind: SecretKind,
+    secret: Zeroizing<Vec<u8>>,
+    address: Address,
+}
+
+/// Збирає секрет з частин і перевіряє, що він дає гаманець з ідентифікатором частин.
+fn combine_backup(shares: &[BackupShare]) -> Result<RecoveredBackup, Box<dyn Error>> {
+    let first = shares.first().ok_or("No backup shares given")?;
+    let secret = shamir::combine(shares)?;
+
+    let address = match first.kind {
+        SecretKind::Seed => derive_keypair(&secret, 0)?.1,
+        SecretKind::PrivateKey => {
+            ethers::utils::secret_key_to_address(&SigningKey::from_slice(&secret)?)
+        }
+    };
+    // Контрольні суми ловлять помилки в окремих частинах, а це — частини різних поділів
+    // того самого гаманця, змішані між собою
+    if backup_wallet_id(&address) != first.wallet_id {
+        return Err("Backup shares do not combine into the original wallet".into());
+    }
+    Ok(RecoveredBackup {
+        kind: first.kind,
+        secret,
+        address,
+    })
+}
+
+fn backup_wallet_id(address: &Address) -> [u8; shamir::WALLET_ID_LEN] {
+    let mut wallet_id = [0u8; shamir::WALLET_ID_LEN];
+    wallet_id.copy_from_slice(&address.as_bytes()[..shamir::WALLET_ID_LEN]);
+    wallet_id
+}
+
+/// Імпортує ключ з V3 keystore (Web3 Secret Storage) як ключ рахунку main у свіжу БД.
+/// Такий гаманець не має seed, тож додаткових рахунків у нього немає.
+pub fn import_keystore(
+    path: &Path,
+    keystore_password: &str,
+    property_key: &PropertyKey,
+) -> Result<Address, Box<dyn Error>> {
+    let (signing_key, address) = decrypt_keystore(path, keystore_password)?;
+    let address_str = format!("{:?}", address);
+
+    let signing_key_hex = Zeroizing::new(encode(signing_key.to_bytes()));
+    save_keypair(&signing_key_hex, &address_str, property_key)?;
+    db::insert_account(&Account {
+        account_index: 0,
+        label: MAIN_ACCOUNT.to_string(),
+        address: address_str,
+    })?;
+
+    Ok(address)
 }
 
+/// Експортує ключ рахунку `account_index` у новий V3 keystore-файл `path`.
+pub fn export_keystore(
+    path: &Path,
+    account_index: u32,
+    keystore_password: &str,
+    property_key: &PropertyKey,
+) -> Result<Address, Box<dyn Error>> {
+    let private_key = load_private_key(account_index, property_key)?;
+    encrypt_keystore(path, &private_key, keystore_password)
+}
+
+/// Розшифровує V3 keystore (scrypt або pbkdf2), як його зберігають geth і MetaMask.
+pub fn decrypt_keystore(
+    path: &Path,
+    keystore_password: &str,
+) -> Result<(SigningKey, Address), Box<dyn Error>> {
+    let wallet = LocalWallet::decrypt_keystore(path, keystore_password)?;
+    Ok((wallet.signer().clone(), wallet.address()))
+}
+
+/// Записує приватний ключ у V3 keystore (scrypt). Наявний файл не перезаписується.
+pub fn encrypt_keystore(
+    path: &Path,
+    private_key: &[u8],
+    keystore_password: &str,
+) -> Result<Address, Box<dyn Error>> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid keystore file name")?;
+
+    let (wallet, _) = LocalWallet::encrypt_keystore(
+        dir,
+        &mut thread_rng(),
+        private_key,
+        keystore_password,
+        Some(name),
+    )?;
+    Ok(wallet.address())
+}
+
+/// Підписує дані ключем рахунку `account_index`.
 pub fn sign_byte_array_sync(
     data: Vec<u8>,
-    external_key: &[u8],
+    account_index: u32,
+    property_key: &PropertyKey,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    // Отримуємо зашифрований приватний ключ з БД
-    let priv_key_hex = db::get_property_by_key(PRIV_KEY, external_key)?;
-    let priv_key_bytes = decode(priv_key_hex)?;
+    let priv_key_bytes = load_private_key(account_index, property_key)?;
 
     // Викликаємо чисту функцію підпису, яка не залежить від БД
     sign_message_with_private_key(&priv_key_bytes, &data)
@@ -65,18 +404,29 @@ pub fn sign_byte_array_sync(
 
 /// Відновлює (recover) адресу підписанта з байтів повідомлення (`data`) і байтів підпису (`signature`).
 pub fn recover_signer_sync(data: &[u8], signature: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    // 1. Хешуємо вхідні дані (EVM-стиль, Keccak-256)
+    // Хешуємо вхідні дані (EVM-стиль, Keccak-256)
     let digest = keccak256(data);
-    let hash = H256::from_slice(&digest);
+    recover_digest_signer(H256::from_slice(&digest), signature)
+}
 
-    // 2. Конвертуємо 65-байтовий підпис (r, s, v) у тип `ethers::types::Signature`
+/// Відновлює адресу (20 байтів), яка підписала готовий дайджест (наприклад, EIP-712).
+/// Приймає лише канонічні підписи з low-s (EIP-2).
+pub fn recover_digest_signer(digest: H256, signature: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    // 1. Конвертуємо 65-байтовий підпис (r, s, v) у тип `ethers::types::Signature`
     let signature = Signature::try_from(signature)
         .map_err(|_| "Invalid signature length or format. Expected 65 bytes (r,s,v)".to_string())?;
 
-    // 3. Відновлюємо адресу, яка підписала хеш
-    let recovered_address = signature.recover(hash)?;
+    // (r, n - s) — другий дійсний підпис того самого дайджесту; приймаємо лише один з двох
+    if signature.s > U256::from_big_endian(&SECP256K1_HALF_ORDER) {
+        return Err(
+            "Non-canonical signature: s must be in the lower half of the curve order".into(),
+        );
+    }
+
+    // 2. Відновлюємо адресу, яка підписала хеш
+    let recovered_address = signature.recover(digest)?;
 
-    // 4. Повертаємо 20 байтів адреси у `Vec<u8>` (для порівняння з sender_address)
+    // 3. Повертаємо 20 байтів адреси у `Vec<u8>` (для порівняння з sender_address)
     Ok(recovered_address.as_bytes().to_vec())
 }
 
@@ -84,20 +434,37 @@ pub fn sign_message_with_private_key(
     private_key_bytes: &[u8],
     data: &[u8],
 ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    // Перетворюємо байти у масив [u8; 32]
-    let priv_key_array: [u8; 32] = private_key_bytes
-        .try_into()
-        .map_err(|_| "Invalid private key length")?;
-
-    // Створюємо гаманець із приватного ключа
-    let wallet = LocalWallet::from(SigningKey::from_bytes((&priv_key_array).into())?);
-
     // Хешуємо дані за допомогою Keccak-256 (Ethereum-стандарт)
     let digest = keccak256(data);
-    let hash = H256::from_slice(&digest);
+    sign_digest_with_private_key(private_key_bytes, H256::from_slice(&digest))
+}
+
+/// Підписує готовий 32-байтовий дайджест ключем рахунку `account_index`.
+pub fn sign_digest_sync(
+    digest: H256,
+    account_index: u32,
+    property_key: &PropertyKey,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let priv_key_bytes = load_private_key(account_index, property_key)?;
+    sign_digest_with_private_key(&priv_key_bytes, digest)
+}
 
-    // Підписуємо хешовані дані
-    let signature = wallet.sign_hash(hash)?;
+pub fn sign_digest_with_private_key(
+    private_key_bytes: &[u8],
+    digest: H256,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Перетворюємо байти у масив [u8; 32], який затреться після підпису
+    if private_key_bytes.len() != 32 {
+        return Err("Invalid private key length".into());
+    }
+    let mut priv_key_array = Zeroizing::new([0u8; 32]);
+    priv_key_array.copy_from_slice(private_key_bytes);
+
+    // Створюємо гаманець із приватного ключа
+    let wallet = LocalWallet::from(SigningKey::from_bytes((&*priv_key_array).into())?);
+
+    // Підписуємо дайджест
+    let signature = wallet.sign_hash(digest)?;
 
     Ok(signature.to_vec())
 }
@@ -135,6 +502,111 @@ mod tests {
         assert_eq!(address, derived_address, "Згенерована адреса некоректна");
     }
 
+    #[test]
+    fn test_keypair_from_mnemonic() {
+        // Загальновідома тестова фраза (Hardhat/anvil): перший рахунок m/44'/60'/0'/0/0
+        let phrase = "test test test test test test test test test test test junk";
+        let (_, address) = keypair_from_mnemonic(phrase, None).unwrap();
+        assert_eq!(
+            address,
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                .parse::<Address>()
+                .unwrap()
+        );
+
+        // Passphrase дає зовсім інший гаманець
+        let (_, other) = keypair_from_mnemonic(phrase, Some("osanwe")).unwrap();
+        assert_ne!(address, other);
+
+        // Фраза з помилкою не приймається
+        assert!(keypair_from_mnemonic("test test test", None).is_err());
+    }
+
+    #[test]
+    fn test_accounts_are_derived_from_one_seed() {
+        let phrase = "test test test test test test test test test test test junk";
+        let seed = seed_from_mnemonic(phrase, None).unwrap();
+
+        let (_, main) = derive_keypair(seed.as_ref(), 0).unwrap();
+        assert_eq!(main, keypair_from_mnemonic(phrase, None).unwrap().1);
+
+        // Другий рахунок anvil: m/44'/60'/0'/0/1
+        let (_, second) = derive_keypair(seed.as_ref(), 1).unwrap();
+        assert_eq!(
+            second,
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+                .parse::<Address>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_backup_shares_restore_the_seed_wallet() {
+        let phrase = "test test test test test test test test test test test junk";
+        let seed = seed_from_mnemonic(phrase, None).unwrap();
+        let (_, main) = derive_keypair(seed.as_ref(), 0).unwrap();
+
+        let shares = shamir::split(
+            seed.as_ref(),
+            SecretKind::Seed,
+            backup_wallet_id(&main),
+            2,
+            3,
+        )
+        .unwrap();
+        let texts: Vec<_> = shares.iter().map(|share| share.to_text()).collect();
+        let parsed: Vec<BackupShare> = texts[1..]
+            .iter()
+            .map(|text| BackupShare::parse(text).unwrap())
+            .collect();
+        assert_eq!(check_backup(&parsed).unwrap(), main);
+
+        // Частини з чужим ідентифікатором гаманця не приймаються навіть з правильним секретом
+        let foreign = shamir::split(
+            seed.as_ref(),
+            SecretKind::Seed,
+            [0; shamir::WALLET_ID_LEN],
+            2,
+            3,
+        )
+        .unwrap();
+        assert!(check_backup(&foreign[..2]).is_err());
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let (signing_key, address) = generate_ethereum_keypair();
+        let path = std::env::temp_dir().join(format!("osanwe-{}.json", uuid::Uuid::new_v4()));
+
+        let exported = encrypt_keystore(&path, &signing_key.to_bytes(), "keystore-pw").unwrap();
+        assert_eq!(exported, address);
+        // Наявний файл не перезаписується
+        assert!(encrypt_keystore(&path, &signing_key.to_bytes(), "other").is_err());
+
+        let (restored, restored_address) = decrypt_keystore(&path, "keystore-pw").unwrap();
+        assert_eq!(restored.to_bytes(), signing_key.to_bytes());
+        assert_eq!(restored_address, address);
+        assert!(decrypt_keystore(&path, "wrong").is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decrypt_pbkdf2_keystore() {
+        // Тестовий вектор зі специфікації Web3 Secret Storage
+        let json = r#"{"crypto":{"cipher":"aes-128-ctr","cipherparams":{"iv":"6087dab2f9fdbbfaddc31a909735c1e6"},"ciphertext":"5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46","kdf":"pbkdf2","kdfparams":{"c":262144,"dklen":32,"prf":"hmac-sha256","salt":"ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"},"mac":"517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"},"id":"3198bc9c-6672-5ab3-d995-4942343ae5b6","version":3}"#;
+        let path = std::env::temp_dir().join(format!("osanwe-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json).unwrap();
+
+        let (signing_key, _) = decrypt_keystore(&path, "testpassword").unwrap();
+        assert_eq!(
+            encode(signing_key.to_bytes()),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_sign_message_with_private_key() {
         // Генеруємо пару ключів
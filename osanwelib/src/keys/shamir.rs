@@ -0,0 +1,272 @@
+use ethers::utils::keccak256;
+use hex::{decode, encode};
+use rand::RngCore;
+use std::error::Error;
+use zeroize::Zeroizing;
+
+/// Префікс і версія текстового формату частини
+const SHARE_PREFIX: &str = "osanwe-share";
+const SHARE_VERSION: u32 = 1;
+/// Довжина контрольної суми в байтах (перші байти keccak256 тексту частини)
+const CHECKSUM_LEN: usize = 4;
+/// Довжина ідентифікатора гаманця в байтах (початок адреси рахунку main)
+pub const WALLET_ID_LEN: usize = 4;
+
+/// Що саме розділено на частини.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    /// BIP-39 seed — з нього відновлюються всі рахунки
+    Seed,
+    /// Приватний ключ гаманця без seed (наприклад, імпортованого з keystore)
+    PrivateKey,
+}
+
+impl SecretKind {
+    fn code(self) -> &'static str {
+        match self {
+            SecretKind::Seed => "seed",
+            SecretKind::PrivateKey => "key",
+        }
+    }
+
+    fn from_code(code: &str) -> Result<Self, Box<dyn Error>> {
+        match code {
+            "seed" => Ok(SecretKind::Seed),
+            "key" => Ok(SecretKind::PrivateKey),
+            other => Err(format!("Unknown backup share kind '{}'", other).into()),
+        }
+    }
+}
+
+/// Одна частина резервної копії за схемою Шаміра над GF(256).
+///
+/// Текстовий вигляд — один рядок
+/// `osanwe-share:1:<поріг>:<номер>:<ідентифікатор гаманця>:<seed|key>:<дані hex>:<контрольна сума>`,
+/// де контрольна сума — перші 4 байти keccak256 усього, що перед нею.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupShare {
+    /// Скільки частин потрібно для відновлення
+    pub threshold: u8,
+    /// Номер частини (x-координата), від 1
+    pub index: u8,
+    /// Початок адреси рахунку main — щоб не змішати частини різних гаманців
+    pub wallet_id: [u8; WALLET_ID_LEN],
+    pub kind: SecretKind,
+    data: Zeroizing<Vec<u8>>,
+}
+
+impl BackupShare {
+    /// Текстовий вигляд частини для друку чи збереження.
+    pub fn to_text(&self) -> Zeroizing<String> {
+        let body = Zeroizing::new(format!(
+            "{}:{}:{}:{}:{}:{}:{}",
+            SHARE_PREFIX,
+            SHARE_VERSION,
+            self.threshold,
+            self.index,
+            encode(self.wallet_id),
+            self.kind.code(),
+            encode(&*self.data)
+        ));
+        Zeroizing::new(format!("{}:{}", &*body, checksum(&body)))
+    }
+
+    /// Розбирає текстову частину; помилка в будь-якому символі виявляється контрольною сумою.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let text = text.trim();
+        let (body, sum) = text.rsplit_once(':').ok_or("Invalid backup share format")?;
+        if !sum.eq_ignore_ascii_case(&checksum(body)) {
+            return Err("Backup share checksum mismatch: the share is mistyped or damaged".into());
+        }
+
+        let fields: Vec<&str> = body.split(':').collect();
+        if fields.len() != 7 || fields[0] != SHARE_PREFIX {
+            return Err("Invalid backup share format".into());
+        }
+        if fields[1].parse::<u32>()? != SHARE_VERSION {
+            return Err(format!("Unsupported backup share version {}", fields[1]).into());
+        }
+
+        let threshold: u8 = fields[2].parse()?;
+        let index: u8 = fields[3].parse()?;
+        if threshold == 0 || index == 0 {
+            return Err("Invalid backup share threshold or index".into());
+        }
+        let wallet_id: [u8; WALLET_ID_LEN] = decode(fields[4])?
+            .try_into()
+            .map_err(|_| "Invalid backup share wallet id")?;
+
+        Ok(BackupShare {
+            threshold,
+            index,
+            wallet_id,
+            kind: SecretKind::from_code(fields[5])?,
+            data: Zeroizing::new(decode(fields[6])?),
+        })
+    }
+}
+
+fn checksum(body: &str) -> String {
+    encode(&keccak256(body.as_bytes())[..CHECKSUM_LEN])
+}
+
+/// Ділить `secret` на `count` частин, з яких будь-які `threshold` відновлюють секрет,
+/// а менша кількість не дає про нього жодної інформації.
+pub fn split(
+    secret: &[u8],
+    kind: SecretKind,
+    wallet_id: [u8; WALLET_ID_LEN],
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<BackupShare>, Box<dyn Error>> {
+    if threshold == 0 || threshold > count {
+        return Err(format!(
+            "Invalid threshold {}: must be between 1 and the number of shares {}",
+            threshold, count
+        )
+        .into());
+    }
+
+    // Для кожного байта секрету — свій випадковий многочлен степеня threshold - 1
+    // з вільним членом, рівним цьому байту
+    let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize - 1]);
+    let mut shares: Vec<BackupShare> = (1..=count)
+        .map(|index| BackupShare {
+            threshold,
+            index,
+            wallet_id,
+            kind,
+            data: Zeroizing::new(Vec::with_capacity(secret.len())),
+        })
+        .collect();
+
+    for &byte in secret {
+        rand::thread_rng().fill_bytes(&mut coefficients);
+        for share in &mut shares {
+            // Схема Горнера: ((a_{k-1}·x + a_{k-2})·x + …)·x + секрет
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(gf_mul(y, share.index) ^ byte);
+        }
+    }
+    Ok(shares)
+}
+
+/// Відновлює секрет з `threshold` частин одного поділу (інтерполяція Лагранжа в нулі).
+pub fn combine(shares: &[BackupShare]) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let first = shares.first().ok_or("No backup shares given")?;
+    if shares.len() < first.threshold as usize {
+        return Err(format!(
+            "Not enough backup shares: {} of {} required",
+            shares.len(),
+            first.threshold
+        )
+        .into());
+    }
+    let shares = &shares[..first.threshold as usize];
+
+    for (i, share) in shares.iter().enumerate() {
+        if share.threshold != first.threshold
+            || share.wallet_id != first.wallet_id
+            || share.kind != first.kind
+            || share.data.len() != first.data.len()
+        {
+            return Err("Backup shares belong to different backups".into());
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(format!("Backup share {} is given twice", share.index).into());
+        }
+    }
+
+    let mut secret = Zeroizing::new(vec![0u8; first.data.len()]);
+    for share in shares {
+        // Базисний многочлен Лагранжа в нулі: Π x_j / (x_j - x_i), віднімання в GF(256) — XOR
+        let mut basis = 1u8;
+        for other in shares.iter().filter(|other| other.index != share.index) {
+            basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+        }
+        for (byte, y) in secret.iter_mut().zip(share.data.iter()) {
+            *byte ^= gf_mul(*y, basis);
+        }
+    }
+    Ok(secret)
+}
+
+/// Множення в GF(2^8) з многочленом x^8 + x^4 + x^3 + x + 1 (як в AES).
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Ділення в GF(2^8): a · b^254 (b^255 = 1 для b ≠ 0).
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_threshold_shares_recover_the_secret() {
+        let secret: Vec<u8> = (0..64).collect();
+        let shares = split(&secret, SecretKind::Seed, [0xAB; 4], 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<BackupShare> = picked.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(*combine(&subset).unwrap(), secret);
+        }
+
+        // Двох частин замало, а повтор частини не рахується
+        assert!(combine(&shares[..2]).is_err());
+        let repeated = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(combine(&repeated).is_err());
+    }
+
+    #[test]
+    fn test_share_text_detects_typos() {
+        let shares = split(&[0x42; 32], SecretKind::PrivateKey, [0x01; 4], 2, 3).unwrap();
+        let text = shares[1].to_text();
+        assert!(text.starts_with("osanwe-share:1:2:2:01010101:key:"));
+        assert_eq!(BackupShare::parse(&text).unwrap(), shares[1]);
+
+        // Заміна одного символу в даних ламає контрольну суму
+        let mut typo = text.to_string();
+        let position = typo.len() - 12;
+        let replacement = if &typo[position..position + 1] == "0" {
+            "1"
+        } else {
+            "0"
+        };
+        typo.replace_range(position..position + 1, replacement);
+        let error = BackupShare::parse(&typo).unwrap_err().to_string();
+        assert!(error.contains("checksum"), "{}", error);
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        // 0x53 · 0xCA = 1 у полі AES
+        assert_eq!(gf_mul(0x53, 0xCA), 0x01);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(gf_div(1, a), a), 1);
+        }
+    }
+}
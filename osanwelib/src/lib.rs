@@ -1,7 +1,16 @@
+#[cfg(unix)]
+pub mod agent;
 pub mod db;
+pub mod grpc_client;
 pub mod keys;
+pub mod multisig;
+pub mod profile;
+pub mod signer;
 pub mod tx;
-pub mod grpc_client;
 pub mod generated {
     include!("generated/transactions.rs");
+
+    pub mod signer {
+        include!("generated/signer.rs");
+    }
 }
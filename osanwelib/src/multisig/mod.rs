@@ -0,0 +1,224 @@
+use crate::db::{self, MultisigAccount};
+use crate::generated::MultisigAuthorization;
+use crate::keys;
+use ethers::types::{Address, H256};
+use ethers::utils::keccak256;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Найбільша кількість підписантів одного рахунку
+pub const MAX_SIGNERS: usize = 16;
+
+/// Префікс хешу адреси: адреса мультипідпису не може збігтися з адресою, виведеною з ключа
+const ADDRESS_DOMAIN: &[u8] = b"osanwe-multisig-v1";
+
+/// Довжини в байтах (див. transaction_pb.proto)
+const ADDRESS_LEN: usize = 20;
+const SIGNATURE_LEN: usize = 65;
+
+/// Адреса рахунку m-of-n: останні 20 байтів
+/// `keccak256("osanwe-multisig-v1" ‖ m ‖ n ‖ адреси підписантів за зростанням)`,
+/// де m і n — u32 big-endian. Підписанти мають бути впорядковані й без повторів.
+pub fn derive_address(threshold: u32, signers: &[Address]) -> Result<Address, Box<dyn Error>> {
+    check_policy(threshold, signers)?;
+
+    let mut data = ADDRESS_DOMAIN.to_vec();
+    data.extend_from_slice(&threshold.to_be_bytes());
+    data.extend_from_slice(&(signers.len() as u32).to_be_bytes());
+    for signer in signers {
+        data.extend_from_slice(signer.as_bytes());
+    }
+    Ok(Address::from_slice(&keccak256(&data)[12..]))
+}
+
+/// 1 ≤ m ≤ n ≤ [`MAX_SIGNERS`], адреси строго за зростанням — тоді в кожного набору
+/// підписантів рівно одне подання і одна адреса.
+fn check_policy(threshold: u32, signers: &[Address]) -> Result<(), Box<dyn Error>> {
+    if signers.is_empty() || signers.len() > MAX_SIGNERS {
+        return Err(format!("A multisig account needs 1 to {} signers", MAX_SIGNERS).into());
+    }
+    if threshold == 0 || threshold as usize > signers.len() {
+        return Err(format!(
+            "Invalid threshold {}: must be between 1 and {}",
+            threshold,
+            signers.len()
+        )
+        .into());
+    }
+    if signers.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("Multisig signers must be unique and sorted in ascending order".into());
+    }
+    Ok(())
+}
+
+/// Створює рахунок m-of-n з міткою `label` і зберігає його в БД.
+/// Порядок і повтори в `signers` не мають значення.
+pub fn create_account(
+    label: &str,
+    threshold: u32,
+    signers: &[Address],
+) -> Result<MultisigAccount, Box<dyn Error>> {
+    if label.is_empty() || label.parse::<u32>().is_ok() {
+        return Err(format!("Invalid account label: '{}'", label).into());
+    }
+    if list_accounts()?
+        .iter()
+        .any(|account| account.label == label)
+    {
+        return Err(format!("Multisig account '{}' already exists", label).into());
+    }
+
+    let mut signers = signers.to_vec();
+    signers.sort();
+    signers.dedup();
+    let address = derive_address(threshold, &signers)?;
+
+    let account = MultisigAccount {
+        label: label.to_owned(),
+        address: format!("{:?}", address),
+        threshold,
+        signers: signers
+            .iter()
+            .map(|signer| format!("{:?}", signer))
+            .collect(),
+    };
+    db::insert_multisig_account(&account)?;
+    Ok(account)
+}
+
+pub fn list_accounts() -> Result<Vec<MultisigAccount>, Box<dyn Error>> {
+    db::get_multisig_accounts()
+}
+
+/// Мультипідписний рахунок за міткою або адресою.
+pub fn resolve_account(name: &str) -> Result<MultisigAccount, Box<dyn Error>> {
+    list_accounts()?
+        .into_iter()
+        .find(|account| account.label == name || account.address.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown multisig account: {}", name).into())
+}
+
+/// Порожня авторизація рахунку: поріг і підписанти без жодного підпису.
+pub fn authorization(account: &MultisigAccount) -> Result<MultisigAuthorization, Box<dyn Error>> {
+    let signers = account
+        .signers
+        .iter()
+        .map(|signer| Ok(signer.parse::<Address>()?.as_bytes().to_vec()))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    Ok(MultisigAuthorization {
+        threshold: account.threshold,
+        signers,
+        signatures: Vec::new(),
+    })
+}
+
+/// Підписанти, чиї підписи `digest` вже є в `authorization`, у порядку підписів.
+/// Підпис стороннього ключа або повторний підпис того самого підписанта — помилка.
+pub fn signed_by(
+    authorization: &MultisigAuthorization,
+    digest: H256,
+) -> Result<Vec<Address>, Box<dyn Error>> {
+    let signers = signer_addresses(authorization)?;
+
+    let mut signed = Vec::new();
+    let mut seen = HashSet::new();
+    for signature in &authorization.signatures {
+        if signature.len() != SIGNATURE_LEN {
+            return Err(format!(
+                "Invalid multisig signature length: expected {} bytes, got {}",
+                SIGNATURE_LEN,
+                signature.len()
+            )
+            .into());
+        }
+        let signer = Address::from_slice(&keys::recover_digest_signer(digest, signature)?);
+        if !signers.contains(&signer) {
+            return Err(format!("{:?} is not a signer of this multisig account", signer).into());
+        }
+        if !seen.insert(signer) {
+            return Err(format!("Duplicate multisig signature from {:?}", signer).into());
+        }
+        signed.push(signer);
+    }
+    Ok(signed)
+}
+
+/// Перевіряє, що `sender_address` — адреса рахунку з порогом і підписантами `authorization`.
+pub fn check_sender(
+    authorization: &MultisigAuthorization,
+    sender_address: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let signers = signer_addresses(authorization)?;
+    let address = derive_address(authorization.threshold, &signers)?;
+    if address.as_bytes() != sender_address {
+        return Err("Multisig signers and threshold do not match sender_address".into());
+    }
+    Ok(())
+}
+
+/// Перевіряє, що `sender_address` — адреса рахунку з цими порогом і підписантами,
+/// а `digest` підписали щонайменше `threshold` різних підписантів.
+pub fn verify_authorization(
+    authorization: &MultisigAuthorization,
+    sender_address: &[u8],
+    digest: H256,
+) -> Result<(), Box<dyn Error>> {
+    check_sender(authorization, sender_address)?;
+
+    let signed = signed_by(authorization, digest)?;
+    if signed.len() < authorization.threshold as usize {
+        return Err(format!(
+            "Not enough multisig signatures: {} of {} required",
+            signed.len(),
+            authorization.threshold
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn signer_addresses(authorization: &MultisigAuthorization) -> Result<Vec<Address>, Box<dyn Error>> {
+    authorization
+        .signers
+        .iter()
+        .map(|signer| {
+            if signer.len() != ADDRESS_LEN {
+                return Err(format!(
+                    "Invalid multisig signer length: expected {} bytes, got {}",
+                    ADDRESS_LEN,
+                    signer.len()
+                )
+                .into());
+            }
+            Ok(Address::from_slice(signer))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(bytes: &[u8]) -> Vec<Address> {
+        bytes.iter().map(|b| Address::repeat_byte(*b)).collect()
+    }
+
+    #[test]
+    fn test_address_depends_on_signers_and_threshold() {
+        let signers = addresses(&[0x11, 0x22, 0x33]);
+        let two_of_three = derive_address(2, &signers).unwrap();
+
+        assert_eq!(derive_address(2, &signers).unwrap(), two_of_three);
+        assert_ne!(derive_address(3, &signers).unwrap(), two_of_three);
+        assert_ne!(
+            derive_address(2, &addresses(&[0x11, 0x22, 0x44])).unwrap(),
+            two_of_three
+        );
+
+        // Неканонічний порядок, повтори і неможливий поріг не приймаються
+        assert!(derive_address(2, &addresses(&[0x22, 0x11, 0x33])).is_err());
+        assert!(derive_address(2, &addresses(&[0x11, 0x11, 0x33])).is_err());
+        assert!(derive_address(0, &signers).is_err());
+        assert!(derive_address(4, &signers).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Профіль, якщо не вказано інший
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Змінна оточення з каталогом даних (поступається лише `--data-dir`)
+pub const DATA_DIR_ENV: &str = "OSANWE_DATA_DIR";
+
+/// Змінна оточення з назвою профілю (поступається лише `--profile`)
+pub const PROFILE_ENV: &str = "OSANWE_PROFILE";
+
+/// Сервер, з яким працює новий профіль
+pub const DEFAULT_SERVER_URL: &str = "http://[::1]:50051";
+
+/// Підкаталог каталогу даних, де лежать профілі
+const PROFILES_DIR: &str = "profiles";
+
+/// Файл налаштувань у каталозі профілю
+const SETTINGS_FILE: &str = "profile.json";
+
+/// Профіль, з яким працює процес; `None` — база `db::DB_PATH` у поточному каталозі
+static ACTIVE: RwLock<Option<Profile>> = RwLock::new(None);
+
+/// Налаштування профілю, що зберігаються в `profile.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSettings {
+    /// Адреса gRPC-сервера, куди надсилаються транзакції
+    pub server_url: String,
+    /// Мережа, для якої підписуються транзакції (див. `tx::network_id`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<String>,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        ProfileSettings {
+            server_url: DEFAULT_SERVER_URL.to_owned(),
+            network_id: None,
+        }
+    }
+}
+
+/// Іменований профіль: окрема база гаманця і окремий сервер.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    /// Каталог профілю `<каталог даних>/profiles/<name>`
+    pub dir: PathBuf,
+    pub settings: ProfileSettings,
+}
+
+impl Profile {
+    /// Шлях до бази гаманця профілю.
+    pub fn db_path(&self) -> PathBuf {
+        self.dir.join(crate::db::DB_PATH)
+    }
+
+    /// Записує налаштування профілю в `profile.json`.
+    pub fn save_settings(&self) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(&self.settings)?;
+        fs::write(self.dir.join(SETTINGS_FILE), json + "\n")?;
+        Ok(())
+    }
+}
+
+/// Каталог даних: `explicit` (`--data-dir`), інакше `OSANWE_DATA_DIR`, інакше
+/// `$XDG_DATA_HOME/osanwe`, інакше `~/.local/share/osanwe`.
+pub fn data_dir(explicit: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(dir) = explicit {
+        return Ok(dir.to_path_buf());
+    }
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    // Відносний XDG_DATA_HOME специфікація велить ігнорувати
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("osanwe"));
+    }
+    let home = std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .ok_or("Cannot find the data directory: set HOME, XDG_DATA_HOME or OSANWE_DATA_DIR")?;
+    Ok(PathBuf::from(home).join(".local/share/osanwe"))
+}
+
+/// Назва профілю: `explicit` (`--profile`), інакше `OSANWE_PROFILE`, інакше [`DEFAULT_PROFILE`].
+pub fn profile_name(explicit: Option<&str>) -> String {
+    explicit
+        .map(str::to_owned)
+        .or_else(|| {
+            std::env::var(PROFILE_ENV)
+                .ok()
+                .filter(|name| !name.is_empty())
+        })
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_owned())
+}
+
+/// Відкриває профіль `name` у каталозі даних `data_dir`; новий профіль створюється
+/// з налаштуваннями за замовчуванням. Каталог доступний лише власнику.
+pub fn open(data_dir: &Path, name: &str) -> Result<Profile, Box<dyn Error>> {
+    check_name(name)?;
+    let dir = data_dir.join(PROFILES_DIR).join(name);
+    create_private_dir(&dir)?;
+
+    let profile = Profile {
+        name: name.to_owned(),
+        settings: load_settings(&dir)?.unwrap_or_default(),
+        dir,
+    };
+    if !profile.dir.join(SETTINGS_FILE).exists() {
+        profile.save_settings()?;
+    }
+    Ok(profile)
+}
+
+/// Усі профілі каталогу даних за назвою.
+pub fn list(data_dir: &Path) -> Result<Vec<Profile>, Box<dyn Error>> {
+    let entries = match fs::read_dir(data_dir.join(PROFILES_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        if !entry.file_type()?.is_dir() || check_name(&name).is_err() {
+            continue;
+        }
+        let dir = entry.path();
+        profiles.push(Profile {
+            name,
+            settings: load_settings(&dir)?.unwrap_or_default(),
+            dir,
+        });
+    }
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// Робить `profile` активним для всього процесу: з ним працюють `db`, `grpc_client`,
+/// `tx::network_id` і агент розблокування.
+pub fn activate(profile: Profile) {
+    *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = Some(profile);
+}
+
+/// Активний профіль, якщо його задано.
+pub fn active() -> Option<Profile> {
+    ACTIVE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Адреса сервера активного профілю або [`DEFAULT_SERVER_URL`].
+pub fn server_url() -> String {
+    active()
+        .map(|profile| profile.settings.server_url)
+        .unwrap_or_else(|| DEFAULT_SERVER_URL.to_owned())
+}
+
+/// Назва стає назвою каталогу і частиною шляху до сокета агента, тож лише `[A-Za-z0-9_-]`.
+fn check_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_'",
+            name
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn load_settings(dir: &Path) -> Result<Option<ProfileSettings>, Box<dyn Error>> {
+    let path = dir.join(SETTINGS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(json) => Ok(Some(serde_json::from_str(&json).map_err(|e| {
+            format!("Invalid profile settings {}: {}", path.display(), e)
+        })?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn create_private_dir(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_are_separate_directories() {
+        let data_dir = std::env::temp_dir().join(format!("osanwe-{}", uuid::Uuid::new_v4()));
+        assert!(list(&data_dir).unwrap().is_empty());
+
+        let default = open(&data_dir, DEFAULT_PROFILE).unwrap();
+        assert_eq!(default.settings, ProfileSettings::default());
+
+        let mut shop = open(&data_dir, "shop-testnet").unwrap();
+        assert_ne!(shop.db_path(), default.db_path());
+        shop.settings.server_url = "http://osanwe.test:50051".to_owned();
+        shop.settings.network_id = Some("osanwe-testnet".to_owned());
+        shop.save_settings().unwrap();
+
+        // Налаштування читаються з profile.json при наступному відкритті
+        assert_eq!(open(&data_dir, "shop-testnet").unwrap(), shop);
+        let names: Vec<String> = list(&data_dir)
+            .unwrap()
+            .into_iter()
+            .map(|profile| profile.name)
+            .collect();
+        assert_eq!(names, vec![DEFAULT_PROFILE, "shop-testnet"]);
+
+        // Назва не може вийти за межі каталогу профілів
+        assert!(open(&data_dir, "../elsewhere").is_err());
+        assert!(open(&data_dir, "").is_err());
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
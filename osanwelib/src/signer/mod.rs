@@ -0,0 +1,456 @@
+use crate::db::{Account, PropertyKey};
+use crate::generated::signer::signer_service_client::SignerServiceClient;
+use crate::generated::signer::{AddressRequest, SignDigestRequest};
+use crate::keys;
+use ethers::types::transaction::eip712::TypedData;
+use ethers::types::{Address, H256};
+use hex::{decode, encode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tonic::transport::Channel;
+
+/// Довжина підпису (r, s, v)
+const SIGNATURE_LEN: usize = 65;
+
+/// Джерело підписів для транзакцій: знає адресу ключа і підписує дайджести EIP-712.
+/// Приватний ключ може взагалі не потрапляти в процес гаманця.
+pub trait Signer {
+    /// Адреса, від імені якої підписуються транзакції
+    fn address(&self) -> Result<Address, Box<dyn Error>>;
+
+    /// Підписує Keccak-256 дайджест; результат — 65 байтів (r, s, v).
+    /// `typed_data` — дані EIP-712, з яких обчислено `digest`: підписувач поза гаманцем
+    /// перераховує з них дайджест і відмовляє, якщо той не збігається
+    fn sign_digest(&self, digest: H256, typed_data: &TypedData) -> Result<Vec<u8>, Box<dyn Error>>;
+}
+
+/// Локальний ключ із зашифрованої БД
+pub const LOCAL: &str = "local";
+
+/// Агент розблокування, див. [`crate::agent`]
+pub const AGENT: &str = "agent";
+
+/// Специфікація підписувача, якщо її не вказано явно: агент розблокування, коли він
+/// запущений, інакше локальний ключ.
+pub fn resolve_spec(spec: Option<&str>) -> &str {
+    match spec {
+        Some(spec) => spec,
+        #[cfg(unix)]
+        None if crate::agent::is_running() => AGENT,
+        None => LOCAL,
+    }
+}
+
+/// Створює підписувача за специфікацією з CLI (`None` — див. [`resolve_spec`]):
+/// - `local` — ключ рахунку `account` із зашифрованої БД;
+/// - `agent` — ключ, розблокований агентом, див. [`crate::agent::AgentSigner`];
+/// - `exec:<команда>` — зовнішній процес, див. [`ExternalSigner`];
+/// - `grpc:<URL>` — віддалений підписувач, див. [`RemoteSigner`].
+///
+/// Для агента і зовнішніх підписувачів `account` (за замовчуванням `main`) — ідентифікатор
+/// ключа на їхньому боці, а `property_key` не потрібен.
+pub fn from_spec(
+    spec: Option<&str>,
+    account: Option<&str>,
+    property_key: Option<PropertyKey>,
+) -> Result<Box<dyn Signer>, Box<dyn Error>> {
+    let key_id = account.unwrap_or(keys::MAIN_ACCOUNT);
+    match resolve_spec(spec) {
+        LOCAL => {
+            let property_key = property_key.ok_or("The local signer needs the wallet password")?;
+            Ok(Box::new(LocalSigner::new(account, property_key)?))
+        }
+        #[cfg(unix)]
+        AGENT => Ok(Box::new(crate::agent::AgentSigner::new(key_id)?)),
+        spec => {
+            if let Some(command) = spec.strip_prefix("exec:") {
+                Ok(Box::new(ExternalSigner::new(command, key_id)?))
+            } else if let Some(url) = spec.strip_prefix("grpc:") {
+                Ok(Box::new(RemoteSigner::connect(url, key_id)?))
+            } else {
+                Err(format!(
+                    "Unknown signer '{}': expected local, agent, exec:<COMMAND> or grpc:<URL>",
+                    spec
+                )
+                .into())
+            }
+        }
+    }
+}
+
+/// Ключ рахунку гаманця, зашифрований у локальній БД.
+pub struct LocalSigner {
+    account: Account,
+    property_key: PropertyKey,
+}
+
+impl LocalSigner {
+    /// `account` — мітка або індекс рахунку, `None` — рахунок main.
+    pub fn new(account: Option<&str>, property_key: PropertyKey) -> Result<Self, Box<dyn Error>> {
+        Ok(LocalSigner {
+            account: keys::resolve_account(account, &property_key)?,
+            property_key,
+        })
+    }
+}
+
+impl Signer for LocalSigner {
+    fn address(&self) -> Result<Address, Box<dyn Error>> {
+        Ok(self.account.address.parse()?)
+    }
+
+    fn sign_digest(&self, digest: H256, _: &TypedData) -> Result<Vec<u8>, Box<dyn Error>> {
+        keys::sign_digest_sync(digest, self.account.account_index, &self.property_key)
+    }
+}
+
+/// Запит до зовнішнього підписувача — один рядок JSON у stdin.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum ExternalRequest {
+    Address {
+        key_id: String,
+    },
+    /// `typed_data` — JSON EIP-712 (як для `eth_signTypedData_v4`), з якого обчислено `digest`
+    SignDigest {
+        key_id: String,
+        digest: String,
+        typed_data: serde_json::Value,
+    },
+}
+
+/// Відповідь зовнішнього підписувача — один рядок JSON у stdout.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExternalResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Зовнішній процес, який тримає ключі. На кожен запит процес запускається заново,
+/// отримує рядок JSON у stdin і відповідає рядком JSON у stdout:
+///
+/// ```text
+/// {"method":"address","key_id":"main"}                                      -> {"address":"0x…"}
+/// {"method":"sign_digest","key_id":"main","digest":"0x…","typed_data":{…}} -> {"signature":"0x…"}
+/// ```
+///
+/// Відмову процес повідомляє як `{"error":"…"}` — зокрема, коли дайджест, перерахований
+/// з `typed_data`, не збігається з `digest`.
+pub struct ExternalSigner {
+    program: String,
+    args: Vec<String>,
+    key_id: String,
+}
+
+impl ExternalSigner {
+    /// `command` — програма з аргументами через пробіл (без лапок і екранування).
+    pub fn new(command: &str, key_id: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parts = command.split_whitespace().map(str::to_owned);
+        let program = parts.next().ok_or("External signer command is empty")?;
+        Ok(ExternalSigner {
+            program,
+            args: parts.collect(),
+            key_id: key_id.to_owned(),
+        })
+    }
+
+    fn call(&self, request: &ExternalRequest) -> Result<ExternalResponse, Box<dyn Error>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Cannot start external signer '{}': {}", self.program, e))?;
+
+        {
+            let mut stdin = child.stdin.take().ok_or("External signer has no stdin")?;
+            writeln!(stdin, "{}", serde_json::to_string(request)?)?;
+        } // Закриваємо stdin, щоб процес побачив кінець запиту
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(format!("External signer exited with {}", output.status).into());
+        }
+
+        let stdout = String::from_utf8(output.stdout)?;
+        let line = stdout
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .ok_or("External signer returned no response")?;
+        let response: ExternalResponse = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid external signer response: {}", e))?;
+
+        match response.error {
+            Some(error) => Err(format!("External signer refused: {}", error).into()),
+            None => Ok(response),
+        }
+    }
+}
+
+impl Signer for ExternalSigner {
+    fn address(&self) -> Result<Address, Box<dyn Error>> {
+        let response = self.call(&ExternalRequest::Address {
+            key_id: self.key_id.clone(),
+        })?;
+        let address = response
+            .address
+            .ok_or("External signer response has no address")?;
+        Ok(address.parse()?)
+    }
+
+    fn sign_digest(&self, digest: H256, typed_data: &TypedData) -> Result<Vec<u8>, Box<dyn Error>> {
+        let response = self.call(&ExternalRequest::SignDigest {
+            key_id: self.key_id.clone(),
+            digest: format!("0x{}", encode(digest)),
+            typed_data: serde_json::to_value(typed_data)?,
+        })?;
+        let signature = response
+            .signature
+            .ok_or("External signer response has no signature")?;
+        check_signature(decode(signature.trim_start_matches("0x"))?)
+    }
+}
+
+/// Віддалений підписувач за gRPC (`proto/signer.proto`).
+///
+/// Клієнт синхронний, як і решта підпису: має власний однопотоковий runtime,
+/// тож його не можна викликати зсередини іншого tokio runtime.
+pub struct RemoteSigner {
+    runtime: tokio::runtime::Runtime,
+    client: SignerServiceClient<Channel>,
+    key_id: String,
+}
+
+impl RemoteSigner {
+    pub fn connect(url: &str, key_id: &str) -> Result<Self, Box<dyn Error>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = runtime
+            .block_on(SignerServiceClient::connect(url.to_owned()))
+            .map_err(|e| format!("Cannot connect to remote signer {}: {}", url, e))?;
+        Ok(RemoteSigner {
+            runtime,
+            client,
+            key_id: key_id.to_owned(),
+        })
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn address(&self) -> Result<Address, Box<dyn Error>> {
+        let mut client = self.client.clone();
+        let response = self.runtime.block_on(client.get_address(AddressRequest {
+            key_id: self.key_id.clone(),
+        }))?;
+
+        let address = response.into_inner().address;
+        if address.len() != 20 {
+            return Err(format!("Remote signer returned {} address bytes", address.len()).into());
+        }
+        Ok(Address::from_slice(&address))
+    }
+
+    fn sign_digest(&self, digest: H256, typed_data: &TypedData) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut client = self.client.clone();
+        let response = self
+            .runtime
+            .block_on(client.sign_digest(SignDigestRequest {
+                key_id: self.key_id.clone(),
+                digest: digest.as_bytes().to_vec(),
+                typed_data: serde_json::to_string(typed_data)?,
+            }))?;
+        check_signature(response.into_inner().signature)
+    }
+}
+
+pub(crate) fn check_signature(signature: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if signature.len() != SIGNATURE_LEN {
+        return Err(format!(
+            "Signer returned {} signature bytes, expected {}",
+            signature.len(),
+            SIGNATURE_LEN
+        )
+        .into());
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::signer::signer_service_server::{SignerService, SignerServiceServer};
+    use crate::generated::signer::{AddressResponse, SignDigestResponse};
+    use crate::generated::TransactionPb;
+    use crate::tx;
+    use ethers::core::k256::ecdsa::SigningKey;
+    use ethers::types::transaction::eip712::Eip712;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::{Request, Response, Status};
+
+    fn test_key() -> (SigningKey, Address) {
+        let private_key = [0x11u8; 32];
+        let signing_key = SigningKey::from_bytes((&private_key).into()).unwrap();
+        let address = ethers::utils::secret_key_to_address(&signing_key);
+        (signing_key, address)
+    }
+
+    /// Дані EIP-712 переказу і їхній дайджест
+    fn test_typed_data() -> (TypedData, H256) {
+        let transaction = TransactionPb {
+            transaction_type: tx::TX_TYPE_TRANSFER,
+            currency_id: 16842752,
+            amount: vec![0x01, 0x00],
+            timestamp: 1700000000,
+            sender_address: vec![0xCC; 20],
+            sender_output_index: 1,
+            recipient_address: vec![0xDD; 20],
+            signature_version: tx::SIGNATURE_VERSION,
+            network_id: "osanwe-test".to_owned(),
+            ..Default::default()
+        };
+        let typed_data = tx::typed_data(&transaction).unwrap();
+        let digest = H256::from(typed_data.encode_eip712().unwrap());
+        (typed_data, digest)
+    }
+
+    fn assert_signed_by(signature: &[u8], digest: H256, address: Address) {
+        let signature = ethers::types::Signature::try_from(signature).unwrap();
+        assert_eq!(signature.recover(digest).unwrap(), address);
+    }
+
+    #[test]
+    fn test_external_signer_protocol() {
+        let (signing_key, address) = test_key();
+        let (typed_data, digest) = test_typed_data();
+        let signature =
+            keys::sign_digest_with_private_key(&signing_key.to_bytes(), digest).unwrap();
+
+        // Підписувач-заглушка: відповідає заздалегідь підготовленим підписом
+        let script = std::env::temp_dir().join(format!("signer-{}.sh", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &script,
+            format!(
+                "read request\ncase \"$request\" in\n\
+                 *'\"key_id\":\"denied\"'*) echo '{{\"error\":\"key is locked\"}}' ;;\n\
+                 *'\"method\":\"address\"'*) echo '{{\"address\":\"{:?}\"}}' ;;\n\
+                 *'\"digest\":\"0x{}\",\"typed_data\":{{'*) echo '{{\"signature\":\"0x{}\"}}' ;;\n\
+                 *) exit 1 ;;\nesac\n",
+                address,
+                encode(digest),
+                encode(&signature)
+            ),
+        )
+        .unwrap();
+        let command = format!("sh {}", script.display());
+
+        let signer = ExternalSigner::new(&command, "main").unwrap();
+        assert_eq!(signer.address().unwrap(), address);
+        assert_signed_by(
+            &signer.sign_digest(digest, &typed_data).unwrap(),
+            digest,
+            address,
+        );
+
+        // Невідомий дайджест — процес завершується з помилкою
+        assert!(signer.sign_digest(H256::zero(), &typed_data).is_err());
+
+        let denied = ExternalSigner::new(&command, "denied").unwrap();
+        let error = denied.address().unwrap_err().to_string();
+        assert!(error.contains("key is locked"), "{}", error);
+
+        std::fs::remove_file(script).unwrap();
+    }
+
+    struct TestSignerService {
+        signing_key: SigningKey,
+        address: Address,
+    }
+
+    #[tonic::async_trait]
+    impl SignerService for TestSignerService {
+        async fn get_address(
+            &self,
+            request: Request<AddressRequest>,
+        ) -> Result<Response<AddressResponse>, Status> {
+            if request.into_inner().key_id != "treasury" {
+                return Err(Status::not_found("unknown key"));
+            }
+            Ok(Response::new(AddressResponse {
+                address: self.address.as_bytes().to_vec(),
+            }))
+        }
+
+        async fn sign_digest(
+            &self,
+            request: Request<SignDigestRequest>,
+        ) -> Result<Response<SignDigestResponse>, Status> {
+            let request = request.into_inner();
+            let digest = H256::from_slice(&request.digest);
+            // Підписуємо лише те, що можемо перевірити: дайджест має відповідати даним
+            let typed_data: TypedData = serde_json::from_str(&request.typed_data)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            let expected = typed_data
+                .encode_eip712()
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            if digest != H256::from(expected) {
+                return Err(Status::failed_precondition(
+                    "digest does not match the typed data",
+                ));
+            }
+            let signature =
+                keys::sign_digest_with_private_key(&self.signing_key.to_bytes(), digest)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            Ok(Response::new(SignDigestResponse { signature }))
+        }
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let (signing_key, address) = test_key();
+
+        // Сервер працює у власному runtime, клієнт — синхронний
+        let server_runtime = tokio::runtime::Runtime::new().unwrap();
+        let url = server_runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+            let service = TestSignerService {
+                signing_key,
+                address,
+            };
+            tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_service(SignerServiceServer::new(service))
+                    .serve_with_incoming(incoming),
+            );
+            url
+        });
+
+        let signer = RemoteSigner::connect(&url, "treasury").unwrap();
+        assert_eq!(signer.address().unwrap(), address);
+
+        let (typed_data, digest) = test_typed_data();
+        assert_signed_by(
+            &signer.sign_digest(digest, &typed_data).unwrap(),
+            digest,
+            address,
+        );
+
+        // Дайджест, що не відповідає даним, підписувач відхиляє
+        let error = signer
+            .sign_digest(H256::zero(), &typed_data)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("does not match"), "{}", error);
+
+        let unknown = RemoteSigner::connect(&url, "hot").unwrap();
+        assert!(unknown.address().is_err());
+    }
+}
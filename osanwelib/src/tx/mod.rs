@@ -1,7 +1,13 @@
-use crate::generated::TransactionPb;
-use crate::{db, grpc_client, keys};
+use crate::db::{MultisigAccount, Store};
+use crate::generated::{MultisigAuthorization, TransactionPb};
+use crate::signer::Signer;
+use crate::{db, grpc_client, keys, multisig};
+use ethers::types::Address;
 use ethers::{
-    types::U256,
+    types::{
+        transaction::eip712::{Eip712, TypedData},
+        H256, U256,
+    },
     utils::{format_units, hex as ethers_hex, keccak256, parse_units},
 };
 use hex::decode;
@@ -29,6 +35,22 @@ pub struct TransactionDb {
     pub recipient_address: String,
     pub sender_signature: Option<String>,
     pub source_transaction_hash: Option<String>,
+    /// Конверт підпису; у старих файлах .osnjs його немає
+    #[serde(default)]
+    pub signature_version: Option<u32>,
+    #[serde(default)]
+    pub network_id: Option<String>,
+    /// Підписи мультипідписного відправника, див. [`MultisigAuthorization`]
+    #[serde(default)]
+    pub multisig: Option<MultisigDb>,
+}
+
+/// [`MultisigAuthorization`] у форматі `TransactionDb`: адреси і підписи в hex з префіксом 0x.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigDb {
+    pub threshold: u32,
+    pub signers: Vec<String>,
+    pub signatures: Vec<String>,
 }
 
 /// Функція, яка конвертує TransactionDb у JSON-рядок.
@@ -79,6 +101,25 @@ pub fn to_transaction_db(tx: &TransactionPb) -> TransactionDb {
         } else {
             Some(to_hex_string(&tx.source_transaction_hash))
         },
+        signature_version: if tx.signature_version == 0 {
+            None
+        } else {
+            Some(tx.signature_version)
+        },
+        network_id: if tx.network_id.is_empty() {
+            None
+        } else {
+            Some(tx.network_id.clone())
+        },
+        multisig: tx.multisig.as_ref().map(|multisig| MultisigDb {
+            threshold: multisig.threshold,
+            signers: multisig.signers.iter().map(|s| to_hex_string(s)).collect(),
+            signatures: multisig
+                .signatures
+                .iter()
+                .map(|s| to_hex_string(s))
+                .collect(),
+        }),
     }
 }
 
@@ -111,6 +152,26 @@ pub fn from_transaction_db(tx_db: &TransactionDb) -> Result<TransactionPb, Box<d
         None => Vec::new(),
     };
 
+    let multisig = match &tx_db.multisig {
+        Some(multisig) => {
+            let decode_all = |values: &[String], len: usize| {
+                values
+                    .iter()
+                    .map(|value| {
+                        validate_hex_length_with_prefix(value, len)?;
+                        Ok(decode(&value[2..])?)
+                    })
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()
+            };
+            Some(MultisigAuthorization {
+                threshold: multisig.threshold,
+                signers: decode_all(&multisig.signers, 20)?,
+                signatures: decode_all(&multisig.signatures, 65)?,
+            })
+        }
+        None => None,
+    };
+
     Ok(TransactionPb {
         transaction_hash: decode(&tx_db.transaction_hash[2..])?,
         transaction_type: tx_db.transaction_type,
@@ -122,6 +183,9 @@ pub fn from_transaction_db(tx_db: &TransactionDb) -> Result<TransactionPb, Box<d
         recipient_address: decode(&tx_db.recipient_address[2..])?,
         sender_signature,
         source_transaction_hash,
+        signature_version: tx_db.signature_version.unwrap_or(0),
+        network_id: tx_db.network_id.clone().unwrap_or_default(),
+        multisig,
     })
 }
 
@@ -144,6 +208,7 @@ fn validate_hex_length_with_prefix(hex: &str, expected_bytes: usize) -> Result<(
 }
 
 /// Конвертація TransactionDb у TransactionPb, очікуючи наявність префікса 0x
+#[allow(clippy::too_many_arguments)]
 pub fn parse_transaction_pb(
     transaction_hash: &str,
     transaction_type: &str,
@@ -178,13 +243,17 @@ pub fn parse_transaction_pb(
         recipient_address: decode(&recipient_address[2..])?,
         sender_signature: decode(&sender_signature[2..])?,
         source_transaction_hash: decode(&source_transaction_hash[2..])?,
+        signature_version: 0,
+        network_id: String::new(),
+        multisig: None,
     })
 }
 
-/// Зберігає транзакцію у базі даних.
+/// Зберігає транзакцію у базі даних і надсилає її на сервер.
 ///
 /// # Аргументи
 ///
+/// * `store` - Сховище гаманця.
 /// * `tx` - Транзакція у форматі `TransactionPb`.
 ///
 /// # Повертає
@@ -192,28 +261,33 @@ pub fn parse_transaction_pb(
 /// * `Ok(())` - Якщо збереження успішне.
 /// * `Err(Box<dyn Error>)` - Якщо виникла помилка.
 ///
-
-pub fn store_transaction(tx: &TransactionPb) -> Result<(), Box<dyn Error>> {
+pub fn store_transaction(store: &mut Store, tx: &TransactionPb) -> Result<(), Box<dyn Error>> {
     verify_transaction(tx)?;
 
     let tx_db = to_transaction_db(tx);
 
-    store_transaction_db(&tx_db)?;
+    store_transaction_db(store, &tx_db)?;
     Ok(())
 }
 
-pub fn store_transaction_db(tx_db: &TransactionDb) -> Result<(), Box<dyn Error>> {
-    // Перевіряємо, чи транзакція вже існує
-    if db::get_transaction_by_hash(&tx_db.transaction_hash).is_ok() {
+pub fn store_transaction_db(
+    store: &mut Store,
+    tx_db: &TransactionDb,
+) -> Result<(), Box<dyn Error>> {
+    // Перевірка наявності і запис — в одній транзакції SQLite
+    if !store.save_transaction(tx_db)? {
         println!("Transaction already exists. Skipping insertion.");
         return Ok(()); // Якщо запис вже є, ігноруємо подальші дії
     }
 
-    // Зберігаємо транзакцію в базі даних
-    db::save_transaction(&tx_db)?;
-
     // Конвертуємо TransactionDb у TransactionPb (для відправки)
-    let transaction_pb = from_transaction_db(&tx_db)?;
+    submit_transaction(&from_transaction_db(tx_db)?)
+}
+
+/// Надсилає вже збережену транзакцію на сервер активного профілю.
+/// Помилка зв'язку лише виводиться: транзакція лишається в локальній базі.
+pub fn submit_transaction(transaction_pb: &TransactionPb) -> Result<(), Box<dyn Error>> {
+    let transaction_pb = transaction_pb.clone();
 
     // Виводимо повідомлення перед відправкою
     println!("Sending transaction to server, please wait...");
@@ -247,7 +321,7 @@ pub fn fetch_transaction(transaction_hash: &str) -> Result<TransactionPb, Box<dy
         Err(err) if err.to_string().contains("QueryReturnedNoRows") => {
             Err(format!("Transaction with hash {} not found", transaction_hash).into())
         }
-        Err(err) => Err(err.into()),
+        Err(err) => Err(err),
     }
 }
 
@@ -256,6 +330,7 @@ pub fn fetch_transaction(transaction_hash: &str) -> Result<TransactionPb, Box<dy
 /// # Аргументи
 ///
 /// * `amount_str` - Сума у вигляді рядка, наприклад, "345.5".
+/// * `decimals` - Кількість десяткових знаків валюти (див. `CryptoAsset::decimals`).
 ///
 /// # Повертає
 ///
@@ -267,12 +342,16 @@ pub fn fetch_transaction(transaction_hash: &str) -> Result<TransactionPb, Box<dy
 /// ```
 /// use osanwelib::tx::convert_amount_to_hex;
 ///
-/// let hex = convert_amount_to_hex("0.000000000000000023")?;
+/// let hex = convert_amount_to_hex("0.000000000000000023", 18)?;
 /// assert_eq!(hex, "0x0000000000000000000000000000000000000000000000000000000000000017");
+///
+/// // 10 USDT (6 знаків після коми)
+/// let hex = convert_amount_to_hex("10", 6)?;
+/// assert_eq!(hex, "0x0000000000000000000000000000000000000000000000000000000000989680");
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn convert_amount_to_hex(amount_str: &str) -> Result<String, Box<dyn Error>> {
-    let bytes = convert_amount_to_bytes(amount_str)?; // Використовуємо `?` замість `unwrap()`
+pub fn convert_amount_to_hex(amount_str: &str, decimals: u32) -> Result<String, Box<dyn Error>> {
+    let bytes = convert_amount_to_bytes(amount_str, decimals)?; // Використовуємо `?` замість `unwrap()`
 
     // Перетворення байтів у шістнадцятковий рядок з префіксом "0x"
     let hex_str = format!("0x{}", ethers_hex::encode(bytes));
@@ -280,12 +359,24 @@ pub fn convert_amount_to_hex(amount_str: &str) -> Result<String, Box<dyn Error>>
     Ok(hex_str)
 }
 
-pub fn convert_amount_to_bytes(amount_str: &str) -> Result<[u8; 32], Box<dyn Error>> {
-    let decimals = 18; // Кількість десяткових знаків для ETH. Змінюйте за потреби.
+pub fn convert_amount_to_bytes(
+    amount_str: &str,
+    decimals: u32,
+) -> Result<[u8; 32], Box<dyn Error>> {
+    // parse_units мовчки відкидає зайві знаки після коми, а нам потрібна точна сума
+    if let Some((_, fraction)) = amount_str.split_once('.') {
+        if fraction.trim_end_matches('0').len() > decimals as usize {
+            return Err(format!(
+                "Amount '{}' has more than {} decimal places",
+                amount_str, decimals
+            )
+            .into());
+        }
+    }
 
-    // Перетворення рядка у U256 (wei) використовуючи ethers::utils::parse_units
+    // Перетворення рядка у U256 (мінімальні одиниці валюти) використовуючи ethers::utils::parse_units
     let amount_wei: U256 = parse_units(amount_str, decimals)
-        .map_err(|e| Box::<dyn Error>::from(e))?
+        .map_err(Box::<dyn Error>::from)?
         .into(); // Explicitly convert the error
 
     // Перетворення U256 у 32-байтовий масив (big-endian)
@@ -301,13 +392,14 @@ pub fn replenishing(
     source_transaction: &str,
 ) -> Result<TransactionPb, Box<dyn Error>> {
     let recipient_bytes = decode(&recipient_address[2..])?;
-    let amount_bytes = convert_amount_to_bytes(amount_str)?;
+    let decimals = db::get_cryptoasset_decimals(currency_id)?;
+    let amount_bytes = convert_amount_to_bytes(amount_str, decimals)?;
     let source_transaction_hash = decode(&source_transaction[2..])?;
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
 
     let mut transaction = TransactionPb {
         transaction_hash: Vec::new(), // Порожнє
-        transaction_type: 1,          // За замовчуванням
+        transaction_type: TX_TYPE_REPLENISH,
         currency_id,
         amount: amount_bytes.to_vec(),
         timestamp,
@@ -316,6 +408,10 @@ pub fn replenishing(
         recipient_address: recipient_bytes,
         sender_signature: Vec::new(), // Порожнє
         source_transaction_hash,
+        // Поповнення не підписується: його перевіряє сервер у вихідному блокчейні
+        signature_version: 0,
+        network_id: String::new(),
+        multisig: None,
     };
 
     let data = tx_to_bytes(&transaction);
@@ -328,48 +424,241 @@ pub fn replenishing(
     Ok(transaction)
 }
 
+/// Тип транзакції: поповнення з зовнішнього блокчейну
+pub const TX_TYPE_REPLENISH: u32 = 1;
+/// Тип транзакції: переказ між гаманцями Osanwe
+pub const TX_TYPE_TRANSFER: u32 = 2;
+/// Тип транзакції: виведення (спалення) коштів на адресу в зовнішньому блокчейні
+pub const TX_TYPE_WITHDRAW: u32 = 3;
+
+/// Поточна версія конверта підпису: EIP-712 з ідентифікатором мережі в повідомленні
+pub const SIGNATURE_VERSION: u32 = 1;
+/// Мережа за замовчуванням — локальна розробка
+pub const DEFAULT_NETWORK_ID: &str = "osanwe-dev";
+
+/// Мережа, для якої клієнт підписує транзакції: `OSANWE_NETWORK_ID`, інакше мережа
+/// активного профілю, інакше [`DEFAULT_NETWORK_ID`]. Сервер приймає лише транзакції
+/// своєї мережі, тож підпис для тестового сервера не можна відтворити на робочому.
+pub fn network_id() -> String {
+    std::env::var("OSANWE_NETWORK_ID")
+        .ok()
+        .or_else(|| crate::profile::active().and_then(|profile| profile.settings.network_id))
+        .unwrap_or_else(|| DEFAULT_NETWORK_ID.to_owned())
+}
+
+/// Формує переказ (тип 2), підписаний `signer` (див. [`crate::signer::from_spec`])
+/// для мережі `network_id`, і зберігає його в `store`. На сервер його надсилає
+/// [`submit_transaction`].
 pub fn send_money(
-    external_key: &str,
+    store: &mut Store,
+    signer: &dyn Signer,
+    network_id: &str,
     amount_str: &str,
     currency_id: u32,
     recipient: &str,
 ) -> Result<TransactionPb, Box<dyn Error>> {
-    // 1. Отримуємо адресу відправника зі сховища ключів
-    let sender_address_str = keys::get_wallet_address(external_key.as_bytes())?;
-    let sender_address = decode(&sender_address_str[2..])?;
-
-    // 2. Зчитуємо поточний баланс гаманця (32 байти в Big-Endian)
-    let balance_bytes = db::get_wallet_balance(&sender_address_str)?;
-    let big_balance = U256::from_big_endian(&balance_bytes);
+    build_outgoing_transaction(
+        store,
+        signer,
+        network_id,
+        TX_TYPE_TRANSFER,
+        amount_str,
+        currency_id,
+        recipient,
+    )
+}
 
-    // 3. Парсимо кількість, яку збираємось відправити, у форматі U256 (wei, якщо 18 знаків після коми)
-    let amount_wei: U256 = parse_units(amount_str, 18)?.into();
+/// Формує підписану транзакцію виведення (тип 3): спалює `amount_str` валюти `currency_id`
+/// з адреси `signer` і вказує адресу `destination` у вихідній EVM-мережі, куди оператор має виплатити кошти.
+/// Транзакція зберігається в `store`, як і в [`send_money`].
+pub fn withdraw(
+    store: &mut Store,
+    signer: &dyn Signer,
+    network_id: &str,
+    amount_str: &str,
+    currency_id: u32,
+    destination: &str,
+) -> Result<TransactionPb, Box<dyn Error>> {
+    check_withdrawal(currency_id, destination)?;
+
+    build_outgoing_transaction(
+        store,
+        signer,
+        network_id,
+        TX_TYPE_WITHDRAW,
+        amount_str,
+        currency_id,
+        destination,
+    )
+}
 
-    // 4. Перевіряємо, чи вистачає балансу
-    if big_balance < amount_wei {
-        // Можна додатково відформатувати баланс у звичних одиницях (наприклад, ETH).
-        let balance_formatted = format_units(big_balance, 18)?;
+/// Виплата можлива лише в EVM-мережу, де адреса отримувача — 20 байтів.
+fn check_withdrawal(currency_id: u32, destination: &str) -> Result<(), Box<dyn Error>> {
+    let asset = db::get_cryptoasset_by_id(currency_id)?
+        .ok_or_else(|| format!("Unknown currency_id: {}", currency_id))?;
+    if asset.evm_chain_id.is_none() {
         return Err(format!(
-            "Insufficient funds. Your wallet has {} (wei), which is less than the requested amount {}",
-            balance_formatted,
-            amount_str
+            "Withdrawal of {} is not supported: only EVM networks can be paid out",
+            asset.symbol
         )
         .into());
     }
+    validate_hex_length_with_prefix(destination, 20)?;
+    Ok(())
+}
 
-    // 5. Якщо коштів достатньо, конвертуємо amount_str у 32-байтове представлення:
-    let amount_bytes = convert_amount_to_bytes(amount_str)?;
+/// Спільна логіка для переказу (тип 2) і виведення (тип 3): обидва списують кошти
+/// з гаманця відправника і мають бути ним підписані.
+fn build_outgoing_transaction(
+    store: &mut Store,
+    signer: &dyn Signer,
+    network_id: &str,
+    transaction_type: u32,
+    amount_str: &str,
+    currency_id: u32,
+    recipient: &str,
+) -> Result<TransactionPb, Box<dyn Error>> {
+    // 1. Адреса відправника — адреса ключа підписувача
+    let sender = signer.address()?;
+
+    // Підпис — без блокування бази; баланс та індекс перевіряються знову перед записом
+    let tx_db = store.insert_outgoing(
+        &format!("{:?}", sender),
+        currency_id,
+        |balance, sender_output_index| {
+            let mut transaction = prepare_outgoing_transaction(
+                sender,
+                sender_output_index,
+                network_id,
+                transaction_type,
+                amount_str,
+                currency_id,
+                recipient,
+            )?;
+            check_funds(balance, &transaction, amount_str)?;
+
+            // 8. Підписуємо дайджест EIP-712, щоб гаманець міг показати, що саме підписується
+            let typed_data = typed_data(&transaction)?;
+            let digest = H256::from(typed_data.encode_eip712()?);
+            let sender_signature = signer.sign_digest(digest, &typed_data)?;
+            // Зовнішній підписувач міг підписати іншим ключем — такий підпис сервер однаково відхилить
+            if keys::recover_digest_signer(digest, &sender_signature)? != transaction.sender_address
+            {
+                return Err("Signature does not match the signer address".into());
+            }
+            transaction.sender_signature = sender_signature;
+            Ok(to_transaction_db(&transaction))
+        },
+    )?;
+
+    // Повертаємо готову транзакцію
+    from_transaction_db(&tx_db)
+}
+
+/// Пропозиція переказу (тип 2) або виведення (тип 3) від мультипідписного рахунку `account`,
+/// одразу підписана `signer` — одним із підписантів рахунку. Решту підписів додає
+/// [`cosign_multisig`]; зберегти й надіслати транзакцію можна, щойно їх буде `threshold`.
+#[allow(clippy::too_many_arguments)]
+pub fn propose_multisig(
+    store: &Store,
+    account: &MultisigAccount,
+    signer: &dyn Signer,
+    network_id: &str,
+    transaction_type: u32,
+    amount_str: &str,
+    currency_id: u32,
+    recipient: &str,
+) -> Result<TransactionPb, Box<dyn Error>> {
+    match transaction_type {
+        TX_TYPE_TRANSFER => {}
+        TX_TYPE_WITHDRAW => check_withdrawal(currency_id, recipient)?,
+        other => return Err(format!("Transaction type {} cannot be proposed", other).into()),
+    }
+
+    let sender: Address = account.address.parse()?;
+    let authorization = multisig::authorization(account)?;
+    multisig::check_sender(&authorization, sender.as_bytes())?;
+
+    // Пропозиція зберігається лише після збору підписів, тож індекс беремо без запису
+    let sender_address = format!("{:?}", sender);
+    let mut transaction = prepare_outgoing_transaction(
+        sender,
+        store.next_sender_output_index(&sender_address)?,
+        network_id,
+        transaction_type,
+        amount_str,
+        currency_id,
+        recipient,
+    )?;
+    check_funds(
+        store.wallet_currency_balance(&sender_address, currency_id)?,
+        &transaction,
+        amount_str,
+    )?;
+    transaction.multisig = Some(authorization);
+    cosign_multisig(&mut transaction, signer)?;
+    Ok(transaction)
+}
+
+/// Додає до мультипідписної транзакції підпис `signer` і повертає, скільки підписів зібрано.
+pub fn cosign_multisig(
+    tx: &mut TransactionPb,
+    signer: &dyn Signer,
+) -> Result<usize, Box<dyn Error>> {
+    let typed_data = typed_data(tx)?;
+    let digest = H256::from(typed_data.encode_eip712()?);
+    let authorization = tx
+        .multisig
+        .as_mut()
+        .ok_or("Transaction does not have a multisig sender")?;
+    multisig::check_sender(authorization, &tx.sender_address)?;
+
+    let address = signer.address()?;
+    if !authorization
+        .signers
+        .iter()
+        .any(|member| member[..] == address.as_bytes()[..])
+    {
+        return Err(format!("{:?} is not a signer of this multisig account", address).into());
+    }
+    let signed = multisig::signed_by(authorization, digest)?;
+    if signed.contains(&address) {
+        return Err(format!("{:?} has already signed this transaction", address).into());
+    }
+
+    let signature = signer.sign_digest(digest, &typed_data)?;
+    if keys::recover_digest_signer(digest, &signature)? != address.as_bytes() {
+        return Err("Signature does not match the signer address".into());
+    }
+    authorization.signatures.push(signature);
+    Ok(signed.len() + 1)
+}
+
+/// Непідписаний переказ або виведення від `sender` з індексом `sender_output_index`
+/// і хешем транзакції.
+fn prepare_outgoing_transaction(
+    sender: Address,
+    sender_output_index: u32,
+    network_id: &str,
+    transaction_type: u32,
+    amount_str: &str,
+    currency_id: u32,
+    recipient: &str,
+) -> Result<TransactionPb, Box<dyn Error>> {
+    let sender_address = sender.as_bytes().to_vec();
+
+    // 3. Парсимо кількість, яку збираємось відправити, у мінімальних одиницях валюти
+    //    (wei для ETH, 6 знаків після коми для USDT тощо)
+    let decimals = db::get_cryptoasset_decimals(currency_id)?;
+    let amount_bytes = convert_amount_to_bytes(amount_str, decimals)?;
 
     let recipient_bytes = decode(&recipient[2..])?;
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
 
-    // Приклад: отримати наступний відправний індекс (UTXO-подібний лічильник) з БД
-    let sender_output_index = db::get_next_sender_output_index(&sender_address_str)?;
-
     // 6. Формуємо транзакцію
     let mut transaction = TransactionPb {
         transaction_hash: Vec::new(),
-        transaction_type: 2,
+        transaction_type,
         currency_id,
         amount: amount_bytes.to_vec(),
         timestamp,
@@ -378,6 +667,9 @@ pub fn send_money(
         recipient_address: recipient_bytes,
         sender_signature: Vec::new(),
         source_transaction_hash: Vec::new(),
+        signature_version: SIGNATURE_VERSION,
+        network_id: network_id.to_owned(),
+        multisig: None,
     };
 
     // 7. Рахуємо хеш транзакції (без підпису, тому що підпис йде поверх)
@@ -385,14 +677,32 @@ pub fn send_money(
     let transaction_hash = keccak256(&data);
     transaction.transaction_hash = transaction_hash.to_vec();
 
-    // 8. Підписуємо байти транзакції
-    let sender_signature = keys::sign_byte_array_sync(data, external_key.as_bytes())?;
-    transaction.sender_signature = sender_signature;
-
-    // Повертаємо готову транзакцію
     Ok(transaction)
 }
 
+/// Перевіряє, чи вистачає `balance` (у мінімальних одиницях) на суму транзакції.
+fn check_funds(
+    balance: U256,
+    transaction: &TransactionPb,
+    amount_str: &str,
+) -> Result<(), Box<dyn Error>> {
+    if balance >= U256::from_big_endian(&transaction.amount) {
+        return Ok(());
+    }
+    // Відформатовуємо баланс у звичних одиницях валюти (наприклад, ETH).
+    let balance_formatted = format_units(
+        balance,
+        db::get_cryptoasset_decimals(transaction.currency_id)?,
+    )?;
+    Err(format!(
+        "Insufficient funds. Your wallet has {} of currency {}, which is less than the requested amount {}",
+        balance_formatted,
+        transaction.currency_id,
+        amount_str
+    )
+    .into())
+}
+
 /// Перетворює TransactionPb у вектор байтів, який містить наступні поля:
 /// - transaction_type (u32, 4 байти, big-endian)
 /// - currency_id (u32, 4 байти, big-endian)
@@ -401,10 +711,13 @@ pub fn send_money(
 /// - sender_address (масив байтів, наприклад, 20 байтів)
 /// - sender_output_index (u32, 4 байти, big-endian)
 /// - recipient_address (масив байтів, наприклад, 20 байтів)
+///
+/// Для виведення (тип 3) набір полів такий самий, як для переказу, а `recipient_address` —
+/// це адреса отримувача в зовнішньому блокчейні.
 pub fn tx_to_bytes(tx: &TransactionPb) -> Vec<u8> {
     let mut buffer = Vec::new();
 
-    if tx.transaction_type == 1 {
+    if tx.transaction_type == TX_TYPE_REPLENISH {
         // Серіалізація для транзакції типу 1
         buffer.extend_from_slice(&tx.transaction_type.to_be_bytes());
         buffer.extend_from_slice(&tx.currency_id.to_be_bytes());
@@ -412,8 +725,9 @@ pub fn tx_to_bytes(tx: &TransactionPb) -> Vec<u8> {
         buffer.extend_from_slice(&tx.timestamp.to_be_bytes());
         buffer.extend_from_slice(&tx.recipient_address);
         buffer.extend_from_slice(&tx.source_transaction_hash);
-    } else if tx.transaction_type == 2 {
-        // Серіалізація для транзакції типу 2 (на даному етапі така ж, як для типу 1)
+    } else if tx.transaction_type == TX_TYPE_TRANSFER || tx.transaction_type == TX_TYPE_WITHDRAW {
+        // Серіалізація для транзакцій типу 2 і 3 (тип входить у підписані байти, тож підпис
+        // переказу не можна видати за підпис виведення і навпаки)
         buffer.extend_from_slice(&tx.transaction_type.to_be_bytes());
         buffer.extend_from_slice(&tx.currency_id.to_be_bytes());
         buffer.extend_from_slice(&tx.amount);
@@ -430,13 +744,91 @@ pub fn tx_to_bytes(tx: &TransactionPb) -> Vec<u8> {
     buffer
 }
 
+/// Домен EIP-712 для підписів транзакцій Osanwe; версія домену — [`SIGNATURE_VERSION`]
+pub const EIP712_DOMAIN_NAME: &str = "Osanwe";
+/// Основний тип EIP-712 для переказу (тип 2) і виведення (тип 3)
+pub const EIP712_PRIMARY_TYPE: &str = "OsanweTransaction";
+
+/// Типізовані дані EIP-712 підписаної транзакції у форматі `eth_signTypedData_v4`:
+/// саме їх стандартний Ethereum-гаманець показує користувачу перед підписом.
+pub fn typed_data(tx: &TransactionPb) -> Result<TypedData, Box<dyn Error>> {
+    if tx.transaction_type != TX_TYPE_TRANSFER && tx.transaction_type != TX_TYPE_WITHDRAW {
+        return Err(format!(
+            "Transaction type {} is not signed by the sender",
+            tx.transaction_type
+        )
+        .into());
+    }
+    if tx.signature_version != SIGNATURE_VERSION {
+        return Err(format!(
+            "Unsupported signature version {} (expected {})",
+            tx.signature_version, SIGNATURE_VERSION
+        )
+        .into());
+    }
+    if tx.network_id.is_empty() {
+        return Err("Transaction has no network_id".into());
+    }
+    if tx.amount.len() > 32 {
+        return Err("Amount does not fit into uint256".into());
+    }
+
+    let typed_data = serde_json::json!({
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" }
+            ],
+            EIP712_PRIMARY_TYPE: [
+                { "name": "network", "type": "string" },
+                { "name": "transactionType", "type": "uint32" },
+                { "name": "currencyId", "type": "uint32" },
+                { "name": "amount", "type": "uint256" },
+                { "name": "timestamp", "type": "uint64" },
+                { "name": "sender", "type": "address" },
+                { "name": "senderOutputIndex", "type": "uint32" },
+                { "name": "recipient", "type": "address" }
+            ]
+        },
+        "primaryType": EIP712_PRIMARY_TYPE,
+        "domain": {
+            "name": EIP712_DOMAIN_NAME,
+            "version": SIGNATURE_VERSION.to_string()
+        },
+        "message": {
+            "network": tx.network_id,
+            "transactionType": tx.transaction_type,
+            "currencyId": tx.currency_id,
+            "amount": U256::from_big_endian(&tx.amount).to_string(),
+            "timestamp": tx.timestamp,
+            "sender": to_hex_string(&tx.sender_address),
+            "senderOutputIndex": tx.sender_output_index,
+            "recipient": to_hex_string(&tx.recipient_address)
+        }
+    });
+
+    Ok(serde_json::from_value(typed_data)?)
+}
+
+/// Дайджест EIP-712, який підписує відправник.
+pub fn eip712_digest(tx: &TransactionPb) -> Result<H256, Box<dyn Error>> {
+    Ok(H256::from(typed_data(tx)?.encode_eip712()?))
+}
+
 /// Перевіряє цілісність транзакції:
 /// 1. Хеш `transaction_hash` має збігатись із `keccak256(tx_to_bytes(tx))`.
-/// 2. Якщо тип транзакції = 2 (наприклад, надсилання коштів),
+/// 2. Якщо відправник мультипідписний (`multisig`), підписи щонайменше `threshold` його
+///    підписантів, а адреса відправника виведена з порогу і набору підписантів.
+/// 3. Інакше, якщо тип транзакції = 2 або 3 (надсилання чи виведення коштів),
 ///    підпис (`sender_signature`) має бути валідною і належати `sender_address`.
+///    Підпис береться над дайджестом EIP-712 (див. [`typed_data`]) поточної версії конверта,
+///    тож він дійсний лише в мережі `network_id`. Чи це мережа сервера, перевіряє сервер.
 pub fn verify_transaction(tx: &TransactionPb) -> Result<(), Box<dyn Error>> {
     // 1. Формуємо байтове подання транзакції (без підпису).
     let data = tx_to_bytes(tx);
+    if data.is_empty() {
+        return Err(format!("Unsupported transaction type {}", tx.transaction_type).into());
+    }
 
     // 2. Перевіряємо, що хеш збігається з `transaction_hash`.
     let computed_hash = keccak256(&data);
@@ -444,17 +836,40 @@ pub fn verify_transaction(tx: &TransactionPb) -> Result<(), Box<dyn Error>> {
         return Err("Invalid transaction hash: does not match keccak256(tx_to_bytes)".into());
     }
 
-    // 3. Для транзакцій, які вимагають підпису (наприклад, type=2), перевіряємо підпис:
-    if tx.transaction_type == 2 {
+    // 3. Мультипідписний відправник: замість одного підпису — щонайменше `threshold` підписів
+    //    його підписантів над тим самим дайджестом EIP-712
+    if let Some(authorization) = &tx.multisig {
+        if tx.transaction_type != TX_TYPE_TRANSFER && tx.transaction_type != TX_TYPE_WITHDRAW {
+            return Err(format!(
+                "Transaction type {} cannot have a multisig sender",
+                tx.transaction_type
+            )
+            .into());
+        }
+        if !tx.sender_signature.is_empty() {
+            return Err("Multisig transaction must not have a sender_signature".into());
+        }
+        return multisig::verify_authorization(
+            authorization,
+            &tx.sender_address,
+            eip712_digest(tx)?,
+        );
+    }
+
+    // 4. Для транзакцій, які вимагають підпису (type=2 і type=3), перевіряємо підпис:
+    if tx.transaction_type == TX_TYPE_TRANSFER || tx.transaction_type == TX_TYPE_WITHDRAW {
         // a) Переконуємось, що поле підпису не порожнє
         if tx.sender_signature.is_empty() {
-            return Err("Missing sender signature for transaction type 2".into());
+            return Err(format!(
+                "Missing sender signature for transaction type {}",
+                tx.transaction_type
+            )
+            .into());
         }
 
-        // b) Відновлюємо адресу підписанта (recover) за допомогою наявного коду в keys (псевдо-приклад)
-        // Припустімо, що у вас є `keys::recover_signer_sync(data, signature) -> Result<Vec<u8>, Box<dyn Error>>`
-        // яка повертає адресу (20 байтів).
-        let recovered_address = keys::recover_signer_sync(&data, &tx.sender_signature)?;
+        // b) Відновлюємо адресу підписанта з дайджесту EIP-712
+        let recovered_address =
+            keys::recover_digest_signer(eip712_digest(tx)?, &tx.sender_signature)?;
 
         // c) Звіряємо з адресою відправника.
         if recovered_address != tx.sender_address {
@@ -474,12 +889,19 @@ mod tests {
 
     #[test]
     pub fn test_send_money() {
-        let result = send_money(
-            "password",
-            "100.5",
-            1,
-            "0xabcdefabcdefabcdefabcdefabcdefabcdefabcdef",
-        );
+        let result = crate::db::unlock(b"password")
+            .and_then(|property_key| Ok(property_key.ok_or("Incorrect password")?))
+            .and_then(|property_key| crate::signer::LocalSigner::new(None, property_key))
+            .and_then(|signer| {
+                send_money(
+                    &mut Store::open()?,
+                    &signer,
+                    DEFAULT_NETWORK_ID,
+                    "100.5",
+                    1,
+                    "0xabcdefabcdefabcdefabcdefabcdefabcdefabcdef",
+                )
+            });
         println!("{:?}", result);
     }
 
@@ -495,6 +917,9 @@ mod tests {
             recipient_address: vec![0xDD; 20],
             sender_signature: vec![0xEE; 65],
             source_transaction_hash: vec![0xFF; 32],
+            signature_version: SIGNATURE_VERSION,
+            network_id: "osanwe-test".to_owned(),
+            multisig: None,
         }
     }
 
@@ -510,6 +935,9 @@ mod tests {
             recipient_address: "0x".to_owned() + &"DD".repeat(20),
             sender_signature: Some("0x".to_owned() + &"EE".repeat(65)),
             source_transaction_hash: Some("0x".to_owned() + &"FF".repeat(32)),
+            signature_version: Some(SIGNATURE_VERSION),
+            network_id: Some("osanwe-test".to_owned()),
+            multisig: None,
         }
     }
 
@@ -525,6 +953,9 @@ mod tests {
             recipient_address: vec![0xDD; 20],
             sender_signature: Vec::new(),
             source_transaction_hash: Vec::new(),
+            signature_version: 0,
+            network_id: String::new(),
+            multisig: None,
         }
     }
 
@@ -540,6 +971,9 @@ mod tests {
             recipient_address: "0x".to_owned() + &"DD".repeat(20),
             sender_signature: None,
             source_transaction_hash: None,
+            signature_version: None,
+            network_id: None,
+            multisig: None,
         }
     }
 
@@ -550,7 +984,7 @@ mod tests {
         // Convert to hex: 345500000000000000000 = 0x12bac6937669760000
         // Pad with leading zeros to make it 32 bytes (64 hex chars)
         let expected_hex = "0x000000000000000000000000000000000000000000000012bac6937669760000";
-        let result = convert_amount_to_hex(amount_str).unwrap();
+        let result = convert_amount_to_hex(amount_str, 18).unwrap();
 
         // Assert lengths first
         assert_eq!(
@@ -572,7 +1006,7 @@ mod tests {
         let amount_str = "345.5";
         let expected_decimal = U256::from_dec_str("345500000000000000000").unwrap();
 
-        let result_hex = convert_amount_to_hex(amount_str).unwrap();
+        let result_hex = convert_amount_to_hex(amount_str, 18).unwrap();
         let result_bytes = hex::decode(&result_hex[2..]).unwrap();
         let result_decimal = U256::from_big_endian(&result_bytes);
 
@@ -582,6 +1016,268 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_convert_amount_uses_asset_decimals() {
+        // 10 USDT (6 знаків) і 10 WBTC (8 знаків) — це різні суми в мінімальних одиницях
+        let usdt = convert_amount_to_bytes("10", 6).unwrap();
+        let wbtc = convert_amount_to_bytes("10", 8).unwrap();
+        assert_eq!(U256::from_big_endian(&usdt), U256::from(10_000_000u64));
+        assert_eq!(U256::from_big_endian(&wbtc), U256::from(1_000_000_000u64));
+
+        // Більше знаків після коми, ніж підтримує валюта, — помилка
+        assert!(convert_amount_to_bytes("0.0000001", 6).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_requires_sender_signature() {
+        let (signing_key, address) = keys::generate_ethereum_keypair();
+        let mut tx = TransactionPb {
+            transaction_hash: Vec::new(),
+            transaction_type: TX_TYPE_WITHDRAW,
+            currency_id: 16842753,
+            amount: vec![0x01; 32],
+            timestamp: 1700000000,
+            sender_address: address.as_bytes().to_vec(),
+            sender_output_index: 1,
+            recipient_address: vec![0xDD; 20],
+            sender_signature: Vec::new(),
+            source_transaction_hash: Vec::new(),
+            signature_version: SIGNATURE_VERSION,
+            network_id: "osanwe-test".to_owned(),
+            multisig: None,
+        };
+        let data = tx_to_bytes(&tx);
+        assert_eq!(&data[..4], &TX_TYPE_WITHDRAW.to_be_bytes());
+        tx.transaction_hash = keccak256(&data).to_vec();
+
+        // Без підпису виведення не приймається
+        assert!(verify_transaction(&tx).is_err());
+
+        // Підпис сирих байтів без конверта більше не приймається
+        tx.sender_signature =
+            keys::sign_message_with_private_key(&signing_key.to_bytes(), &data).unwrap();
+        assert!(verify_transaction(&tx).is_err());
+
+        tx.sender_signature = keys::sign_digest_with_private_key(
+            &signing_key.to_bytes(),
+            eip712_digest(&tx).unwrap(),
+        )
+        .unwrap();
+        verify_transaction(&tx).unwrap();
+    }
+
+    /// Переказ із фіксованими полями для тестових векторів EIP-712
+    fn eip712_sample_transaction() -> TransactionPb {
+        let sender: ethers::types::Address = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+            .parse()
+            .unwrap();
+        let mut amount = [0u8; 32];
+        (U256::exp10(18) * U256::from(5)).to_big_endian(&mut amount); // 5 ETH
+        let mut tx = TransactionPb {
+            transaction_hash: Vec::new(),
+            transaction_type: TX_TYPE_TRANSFER,
+            currency_id: 16842752,
+            amount: amount.to_vec(),
+            timestamp: 1700000000000,
+            sender_address: sender.as_bytes().to_vec(),
+            sender_output_index: 7,
+            recipient_address: decode("70997970c51812dc3a010c7d01b50e0d17dc79c8").unwrap(),
+            sender_signature: Vec::new(),
+            source_transaction_hash: Vec::new(),
+            signature_version: SIGNATURE_VERSION,
+            network_id: "osanwe-mainnet".to_owned(),
+            multisig: None,
+        };
+        tx.transaction_hash = keccak256(tx_to_bytes(&tx)).to_vec();
+        tx
+    }
+
+    #[test]
+    fn test_eip712_digest_matches_specification() {
+        use ethers::abi::{encode, Token};
+
+        let tx = eip712_sample_transaction();
+
+        // Кодування за EIP-712 вручну: domainSeparator і hashStruct
+        let domain_separator = keccak256(encode(&[
+            Token::FixedBytes(keccak256("EIP712Domain(string name,string version)").to_vec()),
+            Token::FixedBytes(keccak256(EIP712_DOMAIN_NAME).to_vec()),
+            Token::FixedBytes(keccak256("1").to_vec()),
+        ]));
+        let type_hash = keccak256(
+            "OsanweTransaction(string network,uint32 transactionType,uint32 currencyId,uint256 amount,\
+             uint64 timestamp,address sender,uint32 senderOutputIndex,address recipient)",
+        );
+        let struct_hash = keccak256(encode(&[
+            Token::FixedBytes(type_hash.to_vec()),
+            Token::FixedBytes(keccak256("osanwe-mainnet").to_vec()),
+            Token::Uint(tx.transaction_type.into()),
+            Token::Uint(tx.currency_id.into()),
+            Token::Uint(U256::from_big_endian(&tx.amount)),
+            Token::Uint(tx.timestamp.into()),
+            Token::Address(ethers::types::Address::from_slice(&tx.sender_address)),
+            Token::Uint(tx.sender_output_index.into()),
+            Token::Address(ethers::types::Address::from_slice(&tx.recipient_address)),
+        ]));
+        let expected = keccak256([&[0x19, 0x01], &domain_separator[..], &struct_hash[..]].concat());
+
+        let digest = eip712_digest(&tx).unwrap();
+        assert_eq!(digest, H256::from(expected));
+        assert_eq!(
+            format!("{:?}", digest),
+            "0x4d06faf6663e0986154d9e9f289aca9cccfb82191aa7b0857c0348da422ee497"
+        );
+
+        // Гаманець отримує JSON і має порахувати той самий дайджест
+        let json = serde_json::to_string(&typed_data(&tx).unwrap()).unwrap();
+        let parsed: TypedData = serde_json::from_str(&json).unwrap();
+        assert_eq!(H256::from(parsed.encode_eip712().unwrap()), digest);
+
+        // Поповнення відправник не підписує
+        let mut replenish = tx.clone();
+        replenish.transaction_type = TX_TYPE_REPLENISH;
+        assert!(eip712_digest(&replenish).is_err());
+    }
+
+    #[test]
+    fn test_eip712_signature_interoperates_with_ethers() {
+        use ethers::signers::{LocalWallet, Signer as _};
+
+        // Перший ключ anvil — адреса 0xf39F…2266
+        let private_key =
+            decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80").unwrap();
+        let wallet = LocalWallet::from_bytes(&private_key).unwrap();
+
+        let mut tx = eip712_sample_transaction();
+        let ethers_signature =
+            futures::executor::block_on(wallet.sign_typed_data(&typed_data(&tx).unwrap()))
+                .unwrap()
+                .to_vec();
+        let our_signature =
+            keys::sign_digest_with_private_key(&private_key, eip712_digest(&tx).unwrap()).unwrap();
+        assert_eq!(ethers_signature, our_signature);
+
+        tx.sender_signature = ethers_signature;
+        verify_transaction(&tx).unwrap();
+
+        // Підпис не переноситься на іншого отримувача
+        let mut moved = tx.clone();
+        moved.recipient_address = vec![0xDD; 20];
+        moved.transaction_hash = keccak256(tx_to_bytes(&moved)).to_vec();
+        assert!(verify_transaction(&moved).is_err());
+
+        // ...і в іншу мережу: хеш той самий, а підпис уже не сходиться
+        let mut replayed = tx.clone();
+        replayed.network_id = "osanwe-testnet".to_owned();
+        assert_eq!(replayed.transaction_hash, tx.transaction_hash);
+        assert!(verify_transaction(&replayed).is_err());
+
+        // Транзакція без версії конверта не приймається
+        let mut unversioned = tx.clone();
+        unversioned.signature_version = 0;
+        assert!(verify_transaction(&unversioned).is_err());
+    }
+
+    #[test]
+    fn test_high_s_signature_is_rejected() {
+        let private_key =
+            decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80").unwrap();
+        let mut tx = eip712_sample_transaction();
+        let digest = eip712_digest(&tx).unwrap();
+        tx.sender_signature = keys::sign_digest_with_private_key(&private_key, digest).unwrap();
+        verify_transaction(&tx).unwrap();
+
+        // (r, n - s) з протилежним v — друга форма того самого підпису
+        let order = U256::from_str_radix(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        )
+        .unwrap();
+        let s = U256::from_big_endian(&tx.sender_signature[32..64]);
+        let mut high_s = tx.clone();
+        (order - s).to_big_endian(&mut high_s.sender_signature[32..64]);
+        high_s.sender_signature[64] ^= 1; // 27 <-> 28
+
+        let error = keys::recover_digest_signer(digest, &high_s.sender_signature)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Non-canonical"), "{}", error);
+        assert!(verify_transaction(&high_s).is_err());
+    }
+
+    /// Підписувач із ключем у пам'яті — для тестів без БД
+    struct KeySigner(ethers::core::k256::ecdsa::SigningKey);
+
+    impl Signer for KeySigner {
+        fn address(&self) -> Result<Address, Box<dyn Error>> {
+            Ok(ethers::utils::secret_key_to_address(&self.0))
+        }
+
+        fn sign_digest(&self, digest: H256, _: &TypedData) -> Result<Vec<u8>, Box<dyn Error>> {
+            keys::sign_digest_with_private_key(&self.0.to_bytes(), digest)
+        }
+    }
+
+    #[test]
+    fn test_multisig_transaction_needs_threshold_signatures() {
+        let signers: Vec<KeySigner> = (0..3)
+            .map(|_| KeySigner(keys::generate_ethereum_keypair().0))
+            .collect();
+        let mut addresses: Vec<Address> = signers.iter().map(|s| s.address().unwrap()).collect();
+        addresses.sort();
+        let account = MultisigAccount {
+            label: "treasury".to_owned(),
+            address: format!("{:?}", multisig::derive_address(2, &addresses).unwrap()),
+            threshold: 2,
+            signers: addresses.iter().map(|a| format!("{:?}", a)).collect(),
+        };
+
+        let mut tx = eip712_sample_transaction();
+        tx.sender_address = account
+            .address
+            .parse::<Address>()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        tx.transaction_hash = keccak256(tx_to_bytes(&tx)).to_vec();
+        tx.multisig = Some(multisig::authorization(&account).unwrap());
+
+        // Один підпис з двох потрібних — транзакція ще не дійсна
+        assert_eq!(cosign_multisig(&mut tx, &signers[0]).unwrap(), 1);
+        let error = verify_transaction(&tx).unwrap_err().to_string();
+        assert!(error.contains("1 of 2"), "{}", error);
+        assert!(cosign_multisig(&mut tx, &signers[0]).is_err());
+
+        // Сторонній ключ не може підписати
+        let outsider = KeySigner(keys::generate_ethereum_keypair().0);
+        assert!(cosign_multisig(&mut tx, &outsider).is_err());
+
+        assert_eq!(cosign_multisig(&mut tx, &signers[2]).unwrap(), 2);
+        verify_transaction(&tx).unwrap();
+
+        // Частковий підпис переживає експорт у файл і назад
+        let json = tx_to_json(&to_transaction_db(&tx)).unwrap();
+        assert_eq!(json_to_txpb(&json).unwrap(), tx);
+
+        // Інший поріг дає іншу адресу, тож підписантів не можна підмінити
+        let mut lowered = tx.clone();
+        lowered.multisig.as_mut().unwrap().threshold = 1;
+        assert!(verify_transaction(&lowered).is_err());
+
+        // Звичайний підпис поруч з мультипідписом не допускається
+        let mut mixed = tx.clone();
+        mixed.sender_signature = vec![0x01; 65];
+        assert!(verify_transaction(&mixed).is_err());
+    }
+
+    #[test]
+    fn test_unknown_transaction_type_is_rejected() {
+        let mut tx = sample_transaction_pb_with_missing_fields();
+        tx.transaction_type = 42;
+        tx.transaction_hash = keccak256(tx_to_bytes(&tx)).to_vec();
+        assert!(verify_transaction(&tx).is_err());
+    }
+
     #[test]
     fn test_conversion_to_transaction_db() {
         let pb = sample_transaction_pb();
@@ -109,4 +109,5 @@ fn test_transaction_flow() {
 
     // Завершити сервер
     server.kill().expect("Failed to stop server");
+    let _ = server.wait();
 }
@@ -9,10 +9,71 @@
             sender_output_index INTEGER,                    -- порядковий номер вихідної транзакції відправника
             recipient_address bytea NOT NULL,               -- адреса отримувача (20 байт)
             sender_signature bytea,                         -- підпис відправника (65 байт)
-            source_transaction_hash bytea                   -- хеш поповнення, якщо є (32 байти)
+            source_transaction_hash bytea,                  -- хеш поповнення, якщо є (32 байти)
+            signature_version INTEGER,                      -- версія конверта підпису (тип 2 і 3)
+            network_id TEXT,                                -- мережа, для якої підписано транзакцію
+            multisig_threshold INTEGER,                     -- m мультипідписного відправника
+            multisig_signers bytea[],                       -- адреси підписантів (по 20 байт, за зростанням)
+            multisig_signatures bytea[]                     -- підписи підписантів (по 65 байт)
         );
 
+        -- Таблиці, створені до появи конверта підпису
+        ALTER TABLE transactions ADD COLUMN IF NOT EXISTS signature_version INTEGER;
+        ALTER TABLE transactions ADD COLUMN IF NOT EXISTS network_id TEXT;
+        -- ... і до появи мультипідписних відправників
+        ALTER TABLE transactions ADD COLUMN IF NOT EXISTS multisig_threshold INTEGER;
+        ALTER TABLE transactions ADD COLUMN IF NOT EXISTS multisig_signers bytea[];
+        ALTER TABLE transactions ADD COLUMN IF NOT EXISTS multisig_signatures bytea[];
+
 -- Приклади індексів для поліпшення продуктивності пошуку
         CREATE INDEX IF NOT EXISTS idx_sender_address ON transactions(sender_address);
         CREATE INDEX IF NOT EXISTS idx_recipient_address ON transactions(recipient_address);
-        
\ No newline at end of file
+        -- Одна транзакція блокчейну може перенести кілька валют або кошти кількох відправників,
+        -- тож кожна пара валюта-отримувач з неї зараховується лише один раз
+        DROP INDEX IF EXISTS idx_replenishment_source_hash;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_replenishment_source
+            ON transactions(source_transaction_hash, currency_id, recipient_address)
+            WHERE transaction_type = 1;
+
+        -- Виплати за прийнятими виведеннями (тип 3) у зовнішній блокчейн
+        CREATE TABLE IF NOT EXISTS withdrawal_payouts (
+            withdrawal_hash bytea PRIMARY KEY REFERENCES transactions(transaction_hash),
+            status TEXT NOT NULL,                           -- pending, sent, confirmed, failed, refund_due
+            payout_tx_hash bytea,                           -- хеш транзакції виплати в EVM-мережі (32 байти)
+            nonce BIGINT,                                   -- nonce транзакції виплати
+            raw_transaction bytea,                          -- підписана транзакція для повторної розсилки
+            error TEXT                                      -- причина, якщо виплата не вдалася
+        );
+        CREATE INDEX IF NOT EXISTS idx_withdrawal_payouts_status ON withdrawal_payouts(status);
+        -- Виведення, прийняті до появи виплат, теж стають у чергу
+        INSERT INTO withdrawal_payouts (withdrawal_hash, status)
+            SELECT transaction_hash, 'pending' FROM transactions WHERE transaction_type = 3
+            ON CONFLICT DO NOTHING;
+
+        -- Останні переглянуті блоки пошуку депозитів у кожній EVM-мережі; найновіший —
+        -- курсор пошуку, решта — для пошуку спільного предка після реорганізації
+        CREATE TABLE IF NOT EXISTS deposit_scanned_blocks (
+            evm_chain_id BIGINT NOT NULL,
+            block_number BIGINT NOT NULL,
+            block_hash bytea NOT NULL,                      -- 32 байти
+            PRIMARY KEY (evm_chain_id, block_number)
+        );
+        -- Курсор зі старої таблиці, де зберігався лише останній блок
+        DO $$
+        BEGIN
+            IF to_regclass('deposit_scan_state') IS NOT NULL THEN
+                INSERT INTO deposit_scanned_blocks (evm_chain_id, block_number, block_hash)
+                    SELECT evm_chain_id, last_block, last_block_hash FROM deposit_scan_state
+                    ON CONFLICT DO NOTHING;
+                DROP TABLE deposit_scan_state;
+            END IF;
+        END $$;
+
+        -- Блок, з якого зараховано кожен знайдений депозит, для перевірки після реорганізацій
+        CREATE TABLE IF NOT EXISTS deposit_credits (
+            replenishment_hash bytea PRIMARY KEY REFERENCES transactions(transaction_hash),
+            evm_chain_id BIGINT NOT NULL,
+            block_number BIGINT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_deposit_credits_block
+            ON deposit_credits(evm_chain_id, block_number);
@@ -0,0 +1,35 @@
+//! Довідник `CryptoAssets` сервера. Він береться з тієї ж вбудованої міграції, що й у
+//! гаманця, але тримається в пам'яті лише для читання: сервер не створює файл гаманця.
+
+use osanwelib::db::{self, CryptoAsset};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::OnceLock;
+
+static REGISTRY: OnceLock<HashMap<u32, CryptoAsset>> = OnceLock::new();
+
+fn registry() -> Result<&'static HashMap<u32, CryptoAsset>, Box<dyn Error>> {
+    if let Some(registry) = REGISTRY.get() {
+        return Ok(registry);
+    }
+    let loaded = db::builtin_cryptoassets()?
+        .into_iter()
+        .map(|asset| (asset.id as u32, asset))
+        .collect();
+    Ok(REGISTRY.get_or_init(|| loaded))
+}
+
+/// Завантажує довідник при старті, щоб помилка в ньому зупинила сервер одразу.
+pub fn load() -> Result<(), Box<dyn Error>> {
+    registry().map(|_| ())
+}
+
+/// Актив за `currency_id`.
+pub fn get(currency_id: u32) -> Result<Option<&'static CryptoAsset>, Box<dyn Error>> {
+    Ok(registry()?.get(&currency_id))
+}
+
+/// Усі активи довідника.
+pub fn all() -> Result<impl Iterator<Item = &'static CryptoAsset>, Box<dyn Error>> {
+    Ok(registry()?.values())
+}
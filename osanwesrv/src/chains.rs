@@ -0,0 +1,59 @@
+use config::Config;
+use ethers::providers::{Http, Provider};
+use ethers::types::H256;
+use ethers::utils::keccak256;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Налаштування однієї EVM-мережі, з якою працює оператор (поповнення і виплати).
+#[derive(Debug, serde::Deserialize)]
+pub struct ChainSettings {
+    /// Справжній EVM chain id (див. `CryptoAssets.evm_chain_id`)
+    pub evm_chain_id: u64,
+    /// JSON-RPC вузол мережі (для тестів — локальний anvil)
+    pub rpc_url: String,
+    /// Скільки блоків має бути над транзакцією, щоб вважати її підтвердженою
+    pub confirmations: u64,
+    /// З якого блоку шукати депозити при першому запуску (за замовчуванням — з поточного)
+    #[serde(default)]
+    pub start_block: Option<u64>,
+}
+
+/// Підключення до однієї мережі.
+pub struct ChainClient {
+    pub provider: Provider<Http>,
+    pub confirmations: u64,
+    pub start_block: Option<u64>,
+}
+
+/// Завантажує список `[[chains]]` з config.toml та змінних оточення.
+pub fn load_settings() -> Result<Vec<ChainSettings>, config::ConfigError> {
+    let settings = Config::builder()
+        .add_source(config::File::with_name("config"))
+        .add_source(config::Environment::with_prefix("APP"))
+        .build()?;
+
+    settings.get::<Vec<ChainSettings>>("chains")
+}
+
+/// Створює JSON-RPC клієнти для всіх мереж, ключ — `evm_chain_id`.
+pub fn connect(settings: &[ChainSettings]) -> Result<HashMap<u64, ChainClient>, Box<dyn Error>> {
+    let mut chains = HashMap::new();
+    for chain in settings {
+        let provider = Provider::<Http>::try_from(chain.rpc_url.as_str())?;
+        chains.insert(
+            chain.evm_chain_id,
+            ChainClient {
+                provider,
+                confirmations: chain.confirmations,
+                start_block: chain.start_block,
+            },
+        );
+    }
+    Ok(chains)
+}
+
+/// topic0 події ERC-20 `Transfer(address indexed from, address indexed to, uint256 value)`.
+pub fn erc20_transfer_topic() -> H256 {
+    H256::from(keccak256("Transfer(address,address,uint256)"))
+}
@@ -0,0 +1,595 @@
+use crate::assets;
+use crate::chains::{self, ChainClient};
+use crate::{pgdb, validation};
+use config::Config;
+use ethers::providers::{JsonRpcClient, Middleware, Provider, ProviderError};
+use ethers::types::{Address, Filter, H256, U256};
+use ethers::utils::keccak256;
+use osanwelib::generated::TransactionPb;
+use osanwelib::tx;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::Code;
+
+/// Скільки останніх переглянутих блоків зберігається з хешами для пошуку спільного
+/// предка після реорганізації
+const SCANNED_BLOCKS_KEPT: i64 = 64;
+
+/// Секція `[deposits]` у config.toml.
+#[derive(Debug, serde::Deserialize)]
+pub struct DepositSettings {
+    /// Пауза між проходами, секунд
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+    /// Скільки блоків однієї мережі переглядати за один прохід
+    #[serde(default = "default_max_blocks")]
+    pub max_blocks_per_round: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    15
+}
+
+fn default_max_blocks() -> u64 {
+    100
+}
+
+/// Переказ на адресу оператора, знайдений у блокчейні.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Deposit {
+    pub source_transaction_hash: H256,
+    pub currency_id: u32,
+    /// Відправник у блокчейні; йому ж належить Osanwe-гаманець, який поповнюється
+    pub depositor: Address,
+    pub amount: U256,
+    /// Блок, у який потрапила транзакція
+    pub block_number: u64,
+}
+
+/// Поповнення, вже видане за депозитом, і блок, з якого його зараховано.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DepositCredit {
+    pub replenishment_hash: H256,
+    pub source_transaction_hash: H256,
+    pub block_number: u64,
+}
+
+/// Валюти однієї мережі з довідника `CryptoAssets`.
+#[derive(Debug, Default)]
+pub struct ChainAssets {
+    /// `currency_id` нативної монети мережі
+    pub native: Option<u32>,
+    /// `currency_id` ERC-20 токенів за адресою контракту
+    pub tokens: HashMap<Address, u32>,
+}
+
+impl ChainAssets {
+    pub fn load(evm_chain_id: u64) -> Result<Self, Box<dyn Error>> {
+        let mut assets = ChainAssets::default();
+        for asset in assets::all()? {
+            if asset.evm_chain_id != Some(evm_chain_id) {
+                continue;
+            }
+            match &asset.contract_address {
+                Some(contract) => {
+                    assets.tokens.insert(contract.parse()?, asset.id as u32);
+                }
+                None => assets.native = Some(asset.id as u32),
+            }
+        }
+        Ok(assets)
+    }
+}
+
+/// Завантажує секцію `[deposits]` з config.toml та змінних оточення.
+pub fn load_settings() -> Result<DepositSettings, config::ConfigError> {
+    let settings = Config::builder()
+        .add_source(config::File::with_name("config"))
+        .add_source(config::Environment::with_prefix("APP"))
+        .build()?;
+
+    settings.get::<DepositSettings>("deposits")
+}
+
+/// Фоновий пошук депозитів на адресу оператора і автоматичне зарахування поповнень.
+pub struct DepositWatcher {
+    custody_address: Address,
+    chains: HashMap<u64, ChainClient>,
+    poll_interval: Duration,
+    max_blocks: u64,
+}
+
+impl DepositWatcher {
+    pub fn new(
+        settings: &DepositSettings,
+        custody_address: Address,
+        chains: HashMap<u64, ChainClient>,
+    ) -> Self {
+        Self {
+            custody_address,
+            chains,
+            poll_interval: Duration::from_secs(settings.poll_interval_secs),
+            max_blocks: settings.max_blocks_per_round.max(1),
+        }
+    }
+
+    /// Нескінченний цикл; помилки однієї мережі лише логуються.
+    pub async fn run(self) {
+        loop {
+            for (chain_id, chain) in &self.chains {
+                if let Err(e) = self.scan_chain(*chain_id, chain).await {
+                    eprintln!("Deposit scan on chain {} failed: {}", chain_id, e);
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Переглядає наступну порцію підтверджених блоків мережі і зараховує депозити.
+    ///
+    /// Після реорганізації пошук повертається до спільного предка — найновішого збереженого
+    /// блоку, хеш якого досі в ланцюгу, — і переглядає все вище за нього. Зарахувань це не
+    /// скасовує: поповнення з витісненого блоку лишається в реєстрі, а депозит, що потрапив
+    /// у новий ланцюг під іншим хешем, зараховується вдруге. Такі зарахування лише
+    /// виводяться в лог для ручної перевірки.
+    async fn scan_chain(&self, chain_id: u64, chain: &ChainClient) -> Result<(), Box<dyn Error>> {
+        let mut client = pgdb::get_db_client().await?;
+        let provider = &chain.provider;
+        let confirmations = chain.confirmations.max(1);
+
+        // Блок остаточний, коли разом із ним є `confirmations` блоків
+        let head = provider.get_block_number().await?.as_u64();
+        let Some(safe) = (head + 1).checked_sub(confirmations) else {
+            return Ok(());
+        };
+
+        let scanned = pgdb::get_scanned_blocks(&client, chain_id).await?;
+        let from = match (scanned.first(), scanned.last()) {
+            (Some(&(last_block, _)), Some(&(oldest_block, _))) => {
+                match common_ancestor(provider, &scanned).await? {
+                    Some(ancestor) if ancestor == last_block => last_block + 1,
+                    // Унікальний ключ поповнення захищає лише від повторного зарахування тієї ж
+                    // транзакції блокчейну, тож зарахування з цих блоків перевіряє оператор
+                    Some(ancestor) => {
+                        eprintln!(
+                            "Chain {} reorganized above block {}, rescanning from block {}",
+                            chain_id,
+                            ancestor,
+                            ancestor + 1
+                        );
+                        report_reorged_credits(&client, chain_id, provider, ancestor + 1).await?;
+                        ancestor + 1
+                    }
+                    None => {
+                        eprintln!(
+                            "Chain {} reorganized below all {} stored blocks, rescanning from block {}",
+                            chain_id,
+                            scanned.len(),
+                            oldest_block
+                        );
+                        report_reorged_credits(&client, chain_id, provider, oldest_block).await?;
+                        oldest_block
+                    }
+                }
+            }
+            _ => chain.start_block.unwrap_or(safe),
+        };
+        if from > safe {
+            return Ok(());
+        }
+        let to = safe.min(from + self.max_blocks - 1);
+
+        // Хеш беремо до пошуку: якщо блоки зміняться під час пошуку, наступний прохід
+        // побачить розбіжність і перегляне їх знову
+        let to_hash = provider
+            .get_block(to)
+            .await?
+            .and_then(|b| b.hash)
+            .ok_or_else(|| format!("Block {} not found", to))?;
+
+        let assets = ChainAssets::load(chain_id)?;
+        for deposit in find_deposits(provider, self.custody_address, &assets, from, to).await? {
+            credit_deposit(chain_id, &deposit).await?;
+        }
+
+        pgdb::add_scanned_block(&mut client, chain_id, to, to_hash, SCANNED_BLOCKS_KEPT).await?;
+        Ok(())
+    }
+}
+
+/// Найновіший зі збережених блоків `scanned` (від найновішого), хеш якого досі той самий
+/// у ланцюгу, тобто спільний предок збереженого і поточного ланцюгів. `None`, якщо
+/// реорганізація глибша за всі збережені блоки.
+pub async fn common_ancestor<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    scanned: &[(u64, H256)],
+) -> Result<Option<u64>, ProviderError> {
+    for &(block_number, block_hash) in scanned {
+        let current = provider.get_block(block_number).await?.and_then(|b| b.hash);
+        if current == Some(block_hash) {
+            return Ok(Some(block_number));
+        }
+    }
+    Ok(None)
+}
+
+/// Видає поповнення (тип 1) за депозитом; вже зараховані депозити пропускаються.
+/// Депозит унікальний за хешем транзакції, валютою і вкладником, тож кілька депозитів
+/// з однієї транзакції блокчейну зараховуються кожен окремо.
+async fn credit_deposit(chain_id: u64, deposit: &Deposit) -> Result<(), Box<dyn Error>> {
+    let transaction = replenishment_for(deposit)?;
+    validation::validate_transaction(&transaction)?;
+
+    match pgdb::save_deposit(transaction, chain_id, deposit.block_number).await {
+        Ok(()) => {
+            println!(
+                "Credited deposit {:?}: {} of currency {} to {:?}",
+                deposit.source_transaction_hash,
+                deposit.amount,
+                deposit.currency_id,
+                deposit.depositor
+            );
+            Ok(())
+        }
+        Err(status) if status.code() == Code::AlreadyExists => {
+            eprintln!(
+                "Deposit {:?} of currency {} to {:?} skipped: {}",
+                deposit.source_transaction_hash,
+                deposit.currency_id,
+                deposit.depositor,
+                status.message()
+            );
+            Ok(())
+        }
+        Err(status) => Err(status.into()),
+    }
+}
+
+/// Виводить для ручної перевірки поповнення, зараховані з блоків від `from` після
+/// реорганізації, разом із тим, де тепер вихідна транзакція.
+async fn report_reorged_credits<P: JsonRpcClient>(
+    client: &tokio_postgres::Client,
+    chain_id: u64,
+    provider: &Provider<P>,
+    from: u64,
+) -> Result<(), Box<dyn Error>> {
+    for credit in pgdb::get_deposit_credits(client, chain_id, from).await? {
+        let mined_in = provider
+            .get_transaction_receipt(credit.source_transaction_hash)
+            .await?
+            .and_then(|receipt| receipt.block_number);
+        let location = match mined_in {
+            Some(block) if block.as_u64() == credit.block_number => {
+                "still in the same block".to_owned()
+            }
+            Some(block) => format!("re-mined in block {}", block),
+            None => "no longer in the chain".to_owned(),
+        };
+        eprintln!(
+            "Review replenishment {:?} on chain {}: deposit {:?} credited from block {} is {}",
+            credit.replenishment_hash,
+            chain_id,
+            credit.source_transaction_hash,
+            credit.block_number,
+            location
+        );
+    }
+    Ok(())
+}
+
+/// Транзакція поповнення з реальним `source_transaction_hash` депозиту.
+pub fn replenishment_for(deposit: &Deposit) -> Result<TransactionPb, Box<dyn Error>> {
+    let mut amount = [0u8; 32];
+    deposit.amount.to_big_endian(&mut amount);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+    let mut transaction = TransactionPb {
+        transaction_hash: Vec::new(),
+        transaction_type: tx::TX_TYPE_REPLENISH,
+        currency_id: deposit.currency_id,
+        amount: amount.to_vec(),
+        timestamp,
+        sender_address: Vec::new(),
+        sender_output_index: 0,
+        recipient_address: deposit.depositor.as_bytes().to_vec(),
+        sender_signature: Vec::new(),
+        source_transaction_hash: deposit.source_transaction_hash.as_bytes().to_vec(),
+        signature_version: 0,
+        network_id: String::new(),
+        multisig: None,
+    };
+    transaction.transaction_hash = keccak256(tx::tx_to_bytes(&transaction)).to_vec();
+    Ok(transaction)
+}
+
+/// Депозити на `custody_address` у блоках `from..=to`: прямі перекази нативної монети
+/// і ERC-20 `Transfer` відомих токенів. Внутрішні перекази з контрактів не відстежуються.
+pub async fn find_deposits<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    custody_address: Address,
+    assets: &ChainAssets,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Deposit>, ProviderError> {
+    let mut deposits = Vec::new();
+
+    if let Some(currency_id) = assets.native {
+        for number in from..=to {
+            let Some(block) = provider.get_block_with_txs(number).await? else {
+                continue;
+            };
+            for source_tx in block.transactions {
+                if source_tx.to != Some(custody_address)
+                    || source_tx.from == custody_address
+                    || source_tx.value.is_zero()
+                {
+                    continue;
+                }
+                // Невдала транзакція теж потрапляє в блок, але коштів не переносить
+                let receipt = provider.get_transaction_receipt(source_tx.hash).await?;
+                if receipt.and_then(|r| r.status) != Some(1u64.into()) {
+                    continue;
+                }
+                deposits.push(Deposit {
+                    source_transaction_hash: source_tx.hash,
+                    currency_id,
+                    depositor: source_tx.from,
+                    amount: source_tx.value,
+                    block_number: number,
+                });
+            }
+        }
+    }
+
+    if !assets.tokens.is_empty() {
+        let filter = Filter::new()
+            .address(assets.tokens.keys().copied().collect::<Vec<_>>())
+            .topic0(chains::erc20_transfer_topic())
+            .topic2(H256::from(custody_address))
+            .from_block(from)
+            .to_block(to);
+
+        // Кілька переказів одного токена від одного відправника в одній транзакції
+        // зараховуються однією сумою, як і в `replenish::verify_source_transfer`
+        let mut transfers: BTreeMap<(H256, Address, Address), (u64, U256)> = BTreeMap::new();
+        for log in provider.get_logs(&filter).await? {
+            if log.removed == Some(true) || log.topics.len() != 3 || log.data.len() != 32 {
+                continue;
+            }
+            let (Some(tx_hash), Some(block_number)) = (log.transaction_hash, log.block_number)
+            else {
+                continue;
+            };
+            let depositor = Address::from(log.topics[1]);
+            let (_, total) = transfers
+                .entry((tx_hash, log.address, depositor))
+                .or_insert((block_number.as_u64(), U256::zero()));
+            *total = total.saturating_add(U256::from_big_endian(&log.data));
+        }
+
+        for ((tx_hash, token, depositor), (block_number, amount)) in transfers {
+            if amount.is_zero() || depositor == custody_address {
+                continue;
+            }
+            deposits.push(Deposit {
+                source_transaction_hash: tx_hash,
+                currency_id: assets.tokens[&token],
+                depositor,
+                amount,
+                block_number,
+            });
+        }
+    }
+
+    Ok(deposits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Block, Log, Transaction, TransactionReceipt, U64};
+
+    fn custody() -> Address {
+        Address::repeat_byte(0xC0)
+    }
+
+    fn depositor() -> Address {
+        Address::repeat_byte(0xDE)
+    }
+
+    fn token() -> Address {
+        Address::repeat_byte(0x70)
+    }
+
+    fn assets() -> ChainAssets {
+        ChainAssets {
+            native: Some(16842752),
+            tokens: HashMap::from([(token(), 16842753)]),
+        }
+    }
+
+    fn transfer(hash: u8, to: Address, value: u64) -> Transaction {
+        Transaction {
+            hash: H256::repeat_byte(hash),
+            from: depositor(),
+            to: Some(to),
+            value: U256::from(value),
+            ..Default::default()
+        }
+    }
+
+    fn receipt(status: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            status: Some(U64::from(status)),
+            ..Default::default()
+        }
+    }
+
+    fn transfer_log(hash: u8, value: u64) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        Log {
+            address: token(),
+            topics: vec![
+                chains::erc20_transfer_topic(),
+                H256::from(depositor()),
+                H256::from(custody()),
+            ],
+            data: data.to_vec().into(),
+            transaction_hash: Some(H256::repeat_byte(hash)),
+            block_number: Some(U64::from(10)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_native_and_erc20_deposits_are_found() {
+        let block = Block {
+            transactions: vec![
+                transfer(0x01, custody(), 1000),
+                transfer(0x02, Address::repeat_byte(0x11), 5),
+                transfer(0x03, custody(), 7),
+            ],
+            ..Default::default()
+        };
+
+        // Відповіді MockProvider віддаються з кінця черги: блок, дві квитанції, логи
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(vec![transfer_log(0x04, 300), transfer_log(0x04, 200)])
+            .unwrap();
+        mock.push(receipt(0)).unwrap();
+        mock.push(receipt(1)).unwrap();
+        mock.push(block).unwrap();
+
+        let deposits = find_deposits(&provider, custody(), &assets(), 10, 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            deposits,
+            vec![
+                Deposit {
+                    source_transaction_hash: H256::repeat_byte(0x01),
+                    currency_id: 16842752,
+                    depositor: depositor(),
+                    amount: U256::from(1000),
+                    block_number: 10,
+                },
+                // Два перекази в одній транзакції — одне поповнення
+                Deposit {
+                    source_transaction_hash: H256::repeat_byte(0x04),
+                    currency_id: 16842753,
+                    depositor: depositor(),
+                    amount: U256::from(500),
+                    block_number: 10,
+                },
+            ]
+        );
+    }
+
+    fn block(hash: u8) -> Block<H256> {
+        Block {
+            hash: Some(H256::repeat_byte(hash)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reorg_deeper_than_confirmations_walks_back_to_common_ancestor() {
+        // Переглянуті блоки 10..=6; з двома підтвердженнями новий ланцюг замінив 10, 9 і 8
+        let scanned: Vec<(u64, H256)> = (6..=10u8)
+            .rev()
+            .map(|n| (n as u64, H256::repeat_byte(n)))
+            .collect();
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(block(7)).unwrap();
+        mock.push(block(0x88)).unwrap();
+        mock.push(block(0x99)).unwrap();
+        mock.push(block(0xAA)).unwrap();
+
+        assert_eq!(common_ancestor(&provider, &scanned).await.unwrap(), Some(7));
+
+        // Реорганізація глибша за всі збережені блоки
+        let (provider, mock) = Provider::mocked();
+        mock.push(block(0x77)).unwrap();
+        mock.push(block(0x88)).unwrap();
+        assert_eq!(
+            common_ancestor(&provider, &scanned[3..]).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_removed_logs_are_ignored() {
+        let mut removed = transfer_log(0x05, 100);
+        removed.removed = Some(true);
+
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(vec![removed]).unwrap();
+        let only_tokens = ChainAssets {
+            native: None,
+            tokens: assets().tokens,
+        };
+        let deposits = find_deposits(&provider, custody(), &only_tokens, 10, 20)
+            .await
+            .unwrap();
+        assert!(deposits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_two_tokens_in_one_transaction_are_two_deposits() {
+        let other_token = Address::repeat_byte(0x71);
+        let mut other_log = transfer_log(0x06, 40);
+        other_log.address = other_token;
+
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(vec![transfer_log(0x06, 300), other_log])
+            .unwrap();
+        let two_tokens = ChainAssets {
+            native: None,
+            tokens: HashMap::from([(token(), 16842753), (other_token, 16842754)]),
+        };
+        let deposits = find_deposits(&provider, custody(), &two_tokens, 10, 10)
+            .await
+            .unwrap();
+        assert_eq!(deposits.len(), 2);
+        assert!(deposits
+            .iter()
+            .all(|d| d.source_transaction_hash == H256::repeat_byte(0x06)));
+
+        // Поповнення різняться валютою, тож унікальний ключ не відкидає друге з них
+        let replenishments: Vec<_> = deposits
+            .iter()
+            .map(|d| replenishment_for(d).unwrap())
+            .collect();
+        assert_eq!(
+            replenishments
+                .iter()
+                .map(|tx| (tx.currency_id, U256::from_big_endian(&tx.amount)))
+                .collect::<Vec<_>>(),
+            vec![(16842753, U256::from(300)), (16842754, U256::from(40))]
+        );
+        assert_ne!(
+            replenishments[0].transaction_hash,
+            replenishments[1].transaction_hash
+        );
+    }
+
+    #[test]
+    fn test_replenishment_for_deposit_is_valid() {
+        let deposit = Deposit {
+            source_transaction_hash: H256::repeat_byte(0x01),
+            currency_id: 16842752,
+            depositor: depositor(),
+            amount: U256::from(1000),
+            block_number: 10,
+        };
+        let transaction = replenishment_for(&deposit).unwrap();
+        assert_eq!(transaction.source_transaction_hash, vec![0x01; 32]);
+        assert_eq!(transaction.recipient_address, depositor().as_bytes());
+
+        validation::check_format(&transaction).unwrap();
+        validation::check_integrity(&transaction).unwrap();
+    }
+}
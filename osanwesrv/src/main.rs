@@ -1,15 +1,27 @@
+mod assets;
+mod chains;
+mod deposits;
+mod payout;
 mod pgdb;
+mod replenish;
+mod validation;
 
 use async_trait::async_trait;
+use deposits::DepositWatcher;
 use osanwelib::generated::{
     transaction_service_server::{TransactionService, TransactionServiceServer},
     TransactionPb, TransactionResponse,
 };
+use payout::PayoutExecutor;
+use replenish::ReplenishmentVerifier;
 use tokio::{signal, sync::oneshot};
 use tonic::{transport::Server, Request, Response, Status};
 
-#[derive(Debug, Default)]
-pub struct MyTransactionService;
+pub struct MyTransactionService {
+    replenishment: ReplenishmentVerifier,
+    /// Мережа цього сервера: перекази, підписані для іншої, відхиляються
+    network_id: String,
+}
 
 #[async_trait]
 impl TransactionService for MyTransactionService {
@@ -21,7 +33,25 @@ impl TransactionService for MyTransactionService {
         let transaction = request.into_inner();
         println!("Received transaction: {:?}", transaction);
 
-        // Спроба збереження транзакції в базі даних
+        // Мережа, формат, валюта, хеш і підпис — до будь-яких звернень до PostgreSQL
+        let validated = match validation::check_network(&transaction, &self.network_id) {
+            Ok(()) => validation::validate_transaction(&transaction),
+            Err(status) => Err(status),
+        };
+        if let Err(status) = validated {
+            eprintln!("Rejected transaction: {}", status.message());
+            return Err(status);
+        }
+
+        // Поповнення має бути підтвердженим переказом у вихідному блокчейні
+        if transaction.transaction_type == 1 {
+            if let Err(status) = self.replenishment.verify(&transaction).await {
+                eprintln!("Rejected replenishment: {}", status.message());
+                return Err(status);
+            }
+        }
+
+        // Правила реєстру перевіряються і транзакція зберігається атомарно
         match pgdb::save_transaction(transaction).await {
             Ok(()) => {
                 let response = TransactionResponse {
@@ -29,9 +59,9 @@ impl TransactionService for MyTransactionService {
                 };
                 Ok(Response::new(response))
             }
-            Err(e) => {
-                eprintln!("Failed to save transaction: {:?}", e);
-                Err(Status::internal("Failed to save transaction"))
+            Err(status) => {
+                eprintln!("Failed to save transaction: {}", status.message());
+                Err(status)
             }
         }
     }
@@ -42,8 +72,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Ініціалізація бази даних (створення таблиць, індексів тощо)
     pgdb::init_db().await?;
 
+    // Довідник CryptoAssets (вбудований, у пам'яті) для перевірки currency_id
+    assets::load()?;
+
+    // Без налаштованих мереж сервер не приймає поповнень і не виплачує виведень
+    let chain_settings = chains::load_settings().unwrap_or_else(|e| {
+        eprintln!("No EVM chains configured: {}", e);
+        Vec::new()
+    });
+
+    let replenishment_settings = replenish::load_settings();
+    let replenishment = match &replenishment_settings {
+        Ok(settings) => ReplenishmentVerifier::new(settings, chains::connect(&chain_settings)?)?,
+        Err(e) => {
+            eprintln!("Replenishment is disabled: {}", e);
+            ReplenishmentVerifier::disabled()
+        }
+    };
+
+    // Депозити на адресу оператора зараховуються без участі користувача
+    match (&replenishment_settings, deposits::load_settings()) {
+        (Ok(replenishment_settings), Ok(settings)) => {
+            let watcher = DepositWatcher::new(
+                &settings,
+                replenishment_settings.custody_address.parse()?,
+                chains::connect(&chain_settings)?,
+            );
+            tokio::spawn(watcher.run());
+        }
+        (_, Err(e)) => eprintln!("Deposit detection is disabled: {}", e),
+        (Err(_), _) => eprintln!("Deposit detection is disabled: no custody address"),
+    }
+
+    // Виплати за виведеннями виконуються у фоні, незалежно від gRPC-запитів
+    match payout::load_settings() {
+        Ok(settings) => {
+            let executor = PayoutExecutor::new(&settings, chains::connect(&chain_settings)?)?;
+            println!("Payouts are sent from {:?}", executor.custody_address());
+            tokio::spawn(executor.run());
+        }
+        Err(e) => eprintln!("Payouts are disabled: {}", e),
+    }
+
     let addr = "[::1]:50051".parse()?;
-    let transaction_service = MyTransactionService::default();
+    let network_id = validation::load_network_id();
+    println!("Accepting transactions for network {}", network_id);
+    let transaction_service = MyTransactionService {
+        replenishment,
+        network_id,
+    };
 
     let (shutdown_tx, _shutdown_rx) = oneshot::channel::<()>();
     let server = Server::builder()
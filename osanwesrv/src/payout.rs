@@ -0,0 +1,657 @@
+use crate::assets;
+use crate::chains::ChainClient;
+use crate::pgdb;
+use config::Config;
+use ethers::abi::{self, Token};
+use ethers::providers::{JsonRpcClient, Middleware, Provider, ProviderError};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockNumber, Bytes, TransactionRequest, H256, U256};
+use ethers::utils::keccak256;
+use osanwelib::db;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::time::Duration;
+
+/// Стани виплати в таблиці `withdrawal_payouts`
+pub const PAYOUT_PENDING: &str = "pending";
+pub const PAYOUT_SENT: &str = "sent";
+pub const PAYOUT_CONFIRMED: &str = "confirmed";
+pub const PAYOUT_FAILED: &str = "failed";
+/// Транзакція виплати потрапила в блок і завершилась помилкою. Виведення вже списало кошти
+/// з гаманця, тож автоматично її не повторюємо (revert часто повториться, а кожна спроба
+/// коштує газу): оператор з'ясовує причину і повертає кошти поповненням або виплачує вручну.
+/// Такі виплати — `SELECT * FROM withdrawal_payouts WHERE status = 'refund_due'`.
+pub const PAYOUT_REFUND_DUE: &str = "refund_due";
+
+/// Секція `[payout]` у config.toml.
+#[derive(Debug, serde::Deserialize)]
+pub struct PayoutSettings {
+    /// Приватний ключ гаманця оператора (hex), з якого виплачуються виведення
+    pub custody_private_key: String,
+    /// Пауза між проходами виконавця, секунд
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    15
+}
+
+/// Прийняте виведення (тип 3) разом зі станом його виплати.
+#[derive(Debug)]
+pub struct Payout {
+    pub withdrawal_hash: Vec<u8>,
+    pub currency_id: u32,
+    pub amount: U256,
+    /// Адреса в зовнішньому блокчейні (`recipient_address` виведення)
+    pub destination: Address,
+    /// Для стану `sent`: підписана транзакція виплати
+    pub signed: Option<SignedPayout>,
+}
+
+/// Підписана, але, можливо, ще не розіслана транзакція виплати.
+#[derive(Debug, Clone)]
+pub struct SignedPayout {
+    pub tx_hash: H256,
+    pub nonce: u64,
+    pub raw: Bytes,
+}
+
+/// Результат перевірки вже розісланої виплати.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PayoutProgress {
+    /// Ще не в блоці або мало підтверджень
+    Waiting,
+    /// Достатньо підтверджень
+    Confirmed,
+    /// Транзакція потрапила в блок, але завершилась помилкою (див. [`PAYOUT_REFUND_DUE`])
+    Reverted,
+    /// Nonce використано іншою транзакцією, ця вже ніколи не потрапить у блок
+    Replaced,
+}
+
+/// Завантажує секцію `[payout]` з config.toml та змінних оточення.
+pub fn load_settings() -> Result<PayoutSettings, config::ConfigError> {
+    let settings = Config::builder()
+        .add_source(config::File::with_name("config"))
+        .add_source(config::Environment::with_prefix("APP"))
+        .build()?;
+
+    settings.get::<PayoutSettings>("payout")
+}
+
+/// Куди і чим виплачувати: мережа і, для ERC-20, контракт токена.
+struct Route<'a> {
+    chain_id: u64,
+    chain: &'a ChainClient,
+    token: Option<Address>,
+}
+
+/// Виплати одного проходу, розкладені за маршрутами.
+struct RoutedPayouts<'a> {
+    ready: Vec<(Payout, Route<'a>)>,
+    /// Виплати, які не виконати ніколи (невідома валюта, не EVM-актив), з причиною
+    unroutable: Vec<(Payout, String)>,
+}
+
+/// Фоновий виконавець: виплачує прийняті виведення з гаманця оператора.
+pub struct PayoutExecutor {
+    wallet: LocalWallet,
+    chains: HashMap<u64, ChainClient>,
+    poll_interval: Duration,
+}
+
+impl PayoutExecutor {
+    pub fn new(
+        settings: &PayoutSettings,
+        chains: HashMap<u64, ChainClient>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let wallet: LocalWallet = settings.custody_private_key.parse()?;
+
+        Ok(Self {
+            wallet,
+            chains,
+            poll_interval: Duration::from_secs(settings.poll_interval_secs),
+        })
+    }
+
+    /// Адреса, з якої виплачуються кошти
+    pub fn custody_address(&self) -> Address {
+        self.wallet.address()
+    }
+
+    /// Нескінченний цикл виплат; помилки одного проходу лише логуються.
+    pub async fn run(self) {
+        loop {
+            if let Err(e) = self.process_payouts().await {
+                eprintln!("Payout round failed: {}", e);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Один прохід: спершу відстежує розіслані виплати, потім підписує нові.
+    async fn process_payouts(&self) -> Result<(), Box<dyn Error>> {
+        let client = pgdb::get_db_client().await?;
+
+        // Мережі, де вузол не прийняв нашу транзакцію: nonce для нових виплат там
+        // ненадійний, тож нові виплати в них відкладаються до наступного проходу
+        let mut blocked_chains = HashSet::new();
+
+        let sent =
+            self.route_payouts(pgdb::get_payouts(&client, PAYOUT_SENT).await?, assets::get)?;
+        fail_unroutable(&client, &sent.unroutable).await?;
+        for (
+            payout,
+            Route {
+                chain_id, chain, ..
+            },
+        ) in sent.ready
+        {
+            let Some(signed) = &payout.signed else {
+                continue;
+            };
+
+            match track_payout(
+                &chain.provider,
+                chain.confirmations,
+                self.custody_address(),
+                signed,
+            )
+            .await
+            {
+                Ok(PayoutProgress::Waiting) => {}
+                Ok(PayoutProgress::Confirmed) => {
+                    pgdb::finish_payout(&client, &payout.withdrawal_hash, PAYOUT_CONFIRMED, None)
+                        .await?;
+                    println!("Payout {:?} confirmed", signed.tx_hash);
+                }
+                Ok(PayoutProgress::Reverted) => {
+                    let error = format!("Payout transaction {:?} reverted", signed.tx_hash);
+                    pgdb::finish_payout(
+                        &client,
+                        &payout.withdrawal_hash,
+                        PAYOUT_REFUND_DUE,
+                        Some(&error),
+                    )
+                    .await?;
+                    eprintln!(
+                        "{}: the withdrawal needs a refund or a manual payout",
+                        error
+                    );
+                }
+                Ok(PayoutProgress::Replaced) => {
+                    pgdb::reset_payout(&client, &payout.withdrawal_hash).await?;
+                    eprintln!(
+                        "Payout {:?} was replaced, will be signed again",
+                        signed.tx_hash
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Failed to track payout {:?}: {}", signed.tx_hash, e);
+                    blocked_chains.insert(chain_id);
+                }
+            }
+        }
+
+        let pending = self.route_payouts(
+            pgdb::get_payouts(&client, PAYOUT_PENDING).await?,
+            assets::get,
+        )?;
+        fail_unroutable(&client, &pending.unroutable).await?;
+        for (
+            payout,
+            Route {
+                chain_id,
+                chain,
+                token,
+            },
+        ) in pending.ready
+        {
+            if blocked_chains.contains(&chain_id) {
+                continue;
+            }
+
+            let signed = match sign_payout(
+                &chain.provider,
+                &self.wallet,
+                chain_id,
+                token,
+                payout.destination,
+                payout.amount,
+            )
+            .await
+            {
+                Ok(signed) => signed,
+                Err(e) => {
+                    eprintln!("Failed to sign payout on chain {}: {}", chain_id, e);
+                    blocked_chains.insert(chain_id);
+                    continue;
+                }
+            };
+
+            // Спершу записуємо підписану транзакцію, потім розсилаємо: якщо сервер впаде між
+            // цими кроками, наступний прохід розішле ту саму транзакцію, а не підпише нову
+            if !pgdb::mark_payout_sent(&client, &payout.withdrawal_hash, &signed).await? {
+                continue;
+            }
+            match chain
+                .provider
+                .send_raw_transaction(signed.raw.clone())
+                .await
+            {
+                Ok(_) => println!("Payout broadcast as {:?}", signed.tx_hash),
+                Err(e) => {
+                    eprintln!("Failed to broadcast payout {:?}: {}", signed.tx_hash, e);
+                    blocked_chains.insert(chain_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Розкладає виплати за маршрутами; `asset_of` шукає валюту в довіднику `CryptoAssets`.
+    /// Помилка довідника перериває прохід (вона тимчасова), а виплата, якій маршруту
+    /// немає і не буде, повертається в `unroutable` і не заважає решті.
+    fn route_payouts<'a, F>(
+        &self,
+        payouts: Vec<Payout>,
+        asset_of: F,
+    ) -> Result<RoutedPayouts<'_>, Box<dyn Error>>
+    where
+        F: Fn(u32) -> Result<Option<&'a db::CryptoAsset>, Box<dyn Error>>,
+    {
+        let mut routed = RoutedPayouts {
+            ready: Vec::new(),
+            unroutable: Vec::new(),
+        };
+        for payout in payouts {
+            let asset = asset_of(payout.currency_id)?;
+            match self.route(payout.currency_id, asset) {
+                Ok(Some(route)) => routed.ready.push((payout, route)),
+                Ok(None) => {}
+                Err(e) => routed.unroutable.push((payout, e.to_string())),
+            }
+        }
+        Ok(routed)
+    }
+
+    /// Мережа і токен для валюти виведення. `None`, якщо мережу не налаштовано —
+    /// тоді виплата лишається в черзі.
+    fn route(
+        &self,
+        currency_id: u32,
+        asset: Option<&db::CryptoAsset>,
+    ) -> Result<Option<Route<'_>>, Box<dyn Error>> {
+        let asset = asset.ok_or_else(|| format!("Unknown currency_id {}", currency_id))?;
+        let evm_chain_id = asset
+            .evm_chain_id
+            .ok_or_else(|| format!("{} is not an EVM asset", asset.symbol))?;
+
+        let Some(chain) = self.chains.get(&evm_chain_id) else {
+            eprintln!("Payouts on EVM chain {} are not configured", evm_chain_id);
+            return Ok(None);
+        };
+
+        let token = match &asset.contract_address {
+            Some(contract) => Some(contract.parse::<Address>()?),
+            None => None,
+        };
+
+        Ok(Some(Route {
+            chain_id: evm_chain_id,
+            chain,
+            token,
+        }))
+    }
+}
+
+/// Позначає виплати без маршруту як `failed` з причиною, щоб вони не блокували чергу.
+async fn fail_unroutable(
+    client: &tokio_postgres::Client,
+    unroutable: &[(Payout, String)],
+) -> Result<(), Box<dyn Error>> {
+    for (payout, error) in unroutable {
+        pgdb::finish_payout(client, &payout.withdrawal_hash, PAYOUT_FAILED, Some(error)).await?;
+        eprintln!(
+            "Payout for withdrawal 0x{} cannot be routed: {}",
+            ethers::utils::hex::encode(&payout.withdrawal_hash),
+            error
+        );
+    }
+    Ok(())
+}
+
+/// Будує і підписує транзакцію виплати (legacy, з явними nonce, gas і chain id).
+/// `token == None` означає нативну монету мережі, інакше — виклик `transfer` ERC-20 контракту.
+pub async fn sign_payout<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    wallet: &LocalWallet,
+    chain_id: u64,
+    token: Option<Address>,
+    destination: Address,
+    amount: U256,
+) -> Result<SignedPayout, Box<dyn Error>> {
+    let (to, value, data) = match token {
+        None => (destination, amount, Bytes::default()),
+        Some(token) => (
+            token,
+            U256::zero(),
+            erc20_transfer_call(destination, amount),
+        ),
+    };
+
+    let nonce = provider
+        .get_transaction_count(wallet.address(), Some(BlockNumber::Pending.into()))
+        .await?;
+    let gas_price = provider.get_gas_price().await?;
+
+    let mut tx: TypedTransaction = TransactionRequest::new()
+        .from(wallet.address())
+        .to(to)
+        .value(value)
+        .data(data)
+        .nonce(nonce)
+        .gas_price(gas_price)
+        .chain_id(chain_id)
+        .into();
+    let gas = provider.estimate_gas(&tx, None).await?;
+    tx.set_gas(gas);
+
+    let signature = wallet
+        .clone()
+        .with_chain_id(chain_id)
+        .sign_transaction_sync(&tx)?;
+    let raw = tx.rlp_signed(&signature);
+
+    Ok(SignedPayout {
+        tx_hash: H256::from(keccak256(&raw)),
+        nonce: nonce.as_u64(),
+        raw,
+    })
+}
+
+/// Перевіряє розіслану виплату і за потреби розсилає її повторно.
+pub async fn track_payout<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    confirmations: u64,
+    custody_address: Address,
+    signed: &SignedPayout,
+) -> Result<PayoutProgress, ProviderError> {
+    // Nonce читаємо до квитанції: якщо наша транзакція встигне потрапити в блок між
+    // двома запитами, квитанція це покаже, і виплату не буде помилково підписано вдруге
+    let mined_nonce = provider
+        .get_transaction_count(custody_address, Some(BlockNumber::Latest.into()))
+        .await?;
+
+    let Some(receipt) = provider.get_transaction_receipt(signed.tx_hash).await? else {
+        if mined_nonce > U256::from(signed.nonce) {
+            return Ok(PayoutProgress::Replaced);
+        }
+
+        // Вузол міг загубити транзакцію (перезапуск, витіснення з mempool)
+        if let Err(e) = provider.send_raw_transaction(signed.raw.clone()).await {
+            if !e.to_string().contains("already known") {
+                return Err(e);
+            }
+        }
+        return Ok(PayoutProgress::Waiting);
+    };
+
+    if receipt.status != Some(1u64.into()) {
+        return Ok(PayoutProgress::Reverted);
+    }
+
+    let Some(mined_in) = receipt.block_number else {
+        return Ok(PayoutProgress::Waiting);
+    };
+    let head = provider.get_block_number().await?;
+    let depth = head.saturating_sub(mined_in).as_u64() + 1;
+    if depth < confirmations {
+        return Ok(PayoutProgress::Waiting);
+    }
+
+    Ok(PayoutProgress::Confirmed)
+}
+
+/// Calldata виклику `transfer(address,uint256)` ERC-20 контракту.
+fn erc20_transfer_call(destination: Address, amount: U256) -> Bytes {
+    let mut data = keccak256("transfer(address,uint256)")[..4].to_vec();
+    data.extend(abi::encode(&[
+        Token::Address(destination),
+        Token::Uint(amount),
+    ]));
+    data.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Http;
+    use ethers::types::{TransactionReceipt, U64};
+    use ethers::utils::rlp::Rlp;
+
+    /// Перший ключ, яким anvil наповнює тестові рахунки
+    const ANVIL_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn wallet() -> LocalWallet {
+        ANVIL_KEY.parse().unwrap()
+    }
+
+    fn signed(nonce: u64) -> SignedPayout {
+        SignedPayout {
+            tx_hash: H256::repeat_byte(0x77),
+            nonce,
+            raw: Bytes::from(vec![0x01]),
+        }
+    }
+
+    fn executor() -> PayoutExecutor {
+        let chain = ChainClient {
+            provider: Provider::<Http>::try_from("http://127.0.0.1:8545").unwrap(),
+            confirmations: 1,
+            start_block: None,
+        };
+        PayoutExecutor {
+            wallet: wallet(),
+            chains: HashMap::from([(11155111, chain)]),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    fn asset(id: i32, symbol: &str, evm_chain_id: Option<u64>) -> db::CryptoAsset {
+        db::CryptoAsset {
+            id,
+            net_type: 1,
+            chain_code: 1,
+            token_id: 0,
+            symbol: symbol.to_owned(),
+            description: None,
+            decimals: 18,
+            contract_address: None,
+            evm_chain_id,
+        }
+    }
+
+    fn payout(hash: u8, currency_id: u32) -> Payout {
+        Payout {
+            withdrawal_hash: vec![hash; 32],
+            currency_id,
+            amount: U256::from(1000),
+            destination: Address::repeat_byte(0xDE),
+            signed: None,
+        }
+    }
+
+    fn receipt(block: u64, status: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(0x77),
+            block_number: Some(U64::from(block)),
+            status: Some(U64::from(status)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unroutable_payout_does_not_block_the_queue() {
+        let executor = executor();
+        let (eth, btc) = (asset(1, "ETH", Some(11155111)), asset(2, "BTC", None));
+        let assets = |currency_id: u32| -> Result<Option<&db::CryptoAsset>, Box<dyn Error>> {
+            Ok(match currency_id {
+                1 => Some(&eth),
+                2 => Some(&btc),
+                _ => None,
+            })
+        };
+
+        let routed = executor
+            .route_payouts(
+                vec![payout(0x01, 99), payout(0x02, 1), payout(0x03, 2)],
+                assets,
+            )
+            .unwrap();
+        assert_eq!(routed.ready.len(), 1);
+        assert_eq!(routed.ready[0].0.withdrawal_hash, vec![0x02; 32]);
+        assert_eq!(routed.ready[0].1.chain_id, 11155111);
+
+        let unroutable: Vec<(u8, &str)> = routed
+            .unroutable
+            .iter()
+            .map(|(payout, error)| (payout.withdrawal_hash[0], error.as_str()))
+            .collect();
+        assert_eq!(
+            unroutable,
+            vec![
+                (0x01, "Unknown currency_id 99"),
+                (0x03, "BTC is not an EVM asset")
+            ]
+        );
+
+        // Недоступний довідник — не вада виплати: прохід переривається, нічого не позначається
+        assert!(executor
+            .route_payouts(vec![payout(0x02, 1)], |_| Err("database is locked".into()))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_erc20_payout_is_signed_by_custody() {
+        let token = Address::repeat_byte(0x70);
+        let destination = Address::repeat_byte(0xDE);
+
+        // Відповіді MockProvider віддаються з кінця черги: nonce, gas price, estimate gas
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(60_000)).unwrap();
+        mock.push(U256::from(1_000_000_000u64)).unwrap();
+        mock.push(U256::from(7)).unwrap();
+
+        let signed = sign_payout(
+            &provider,
+            &wallet(),
+            11155111,
+            Some(token),
+            destination,
+            U256::from(10_000_000),
+        )
+        .await
+        .unwrap();
+        assert_eq!(signed.nonce, 7);
+
+        let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(&signed.raw)).unwrap();
+        assert_eq!(tx.to_addr(), Some(&token));
+        assert_eq!(tx.value().copied().unwrap_or_default(), U256::zero());
+        assert_eq!(
+            tx.data().unwrap(),
+            &erc20_transfer_call(destination, U256::from(10_000_000))
+        );
+        assert_eq!(tx.chain_id(), Some(U64::from(11155111)));
+        assert_eq!(signature.recover(tx.sighash()).unwrap(), wallet().address());
+        assert_eq!(signed.tx_hash, H256::from(keccak256(&signed.raw)));
+    }
+
+    #[tokio::test]
+    async fn test_payout_is_confirmed_after_enough_blocks() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(105)).unwrap();
+        mock.push(receipt(100, 1)).unwrap();
+        mock.push(U256::from(4)).unwrap();
+        let progress = track_payout(&provider, 12, wallet().address(), &signed(3))
+            .await
+            .unwrap();
+        assert_eq!(progress, PayoutProgress::Waiting);
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(111)).unwrap();
+        mock.push(receipt(100, 1)).unwrap();
+        mock.push(U256::from(4)).unwrap();
+        let progress = track_payout(&provider, 12, wallet().address(), &signed(3))
+            .await
+            .unwrap();
+        assert_eq!(progress, PayoutProgress::Confirmed);
+    }
+
+    #[tokio::test]
+    async fn test_reverted_and_replaced_payouts() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(receipt(100, 0)).unwrap();
+        mock.push(U256::from(4)).unwrap();
+        let progress = track_payout(&provider, 1, wallet().address(), &signed(3))
+            .await
+            .unwrap();
+        assert_eq!(progress, PayoutProgress::Reverted);
+
+        // Квитанції немає, а nonce 3 уже використано в мережі
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap();
+        mock.push(U256::from(4)).unwrap();
+        let progress = track_payout(&provider, 1, wallet().address(), &signed(3))
+            .await
+            .unwrap();
+        assert_eq!(progress, PayoutProgress::Replaced);
+
+        // Квитанції немає і nonce ще вільний: транзакція розсилається повторно
+        let (provider, mock) = Provider::mocked();
+        mock.push(H256::repeat_byte(0x77)).unwrap();
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap();
+        mock.push(U256::from(3)).unwrap();
+        let progress = track_payout(&provider, 1, wallet().address(), &signed(3))
+            .await
+            .unwrap();
+        assert_eq!(progress, PayoutProgress::Waiting);
+    }
+
+    /// Потребує запущеного `anvil` на 127.0.0.1:8545: `cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn test_native_payout_on_anvil() {
+        let provider = Provider::<Http>::try_from("http://127.0.0.1:8545").unwrap();
+        let chain_id = provider.get_chainid().await.unwrap().as_u64();
+        let destination = Address::random();
+        let amount = U256::exp10(15);
+
+        let signed = sign_payout(&provider, &wallet(), chain_id, None, destination, amount)
+            .await
+            .unwrap();
+        provider
+            .send_raw_transaction(signed.raw.clone())
+            .await
+            .unwrap();
+
+        let mut progress = PayoutProgress::Waiting;
+        for _ in 0..20 {
+            progress = track_payout(&provider, 1, wallet().address(), &signed)
+                .await
+                .unwrap();
+            if progress != PayoutProgress::Waiting {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        assert_eq!(progress, PayoutProgress::Confirmed);
+        assert_eq!(
+            provider.get_balance(destination, None).await.unwrap(),
+            amount
+        );
+    }
+}
@@ -1,7 +1,13 @@
+use crate::deposits::DepositCredit;
+use crate::payout::{Payout, SignedPayout, PAYOUT_PENDING, PAYOUT_SENT};
+use crate::validation::{self, LedgerState};
 use config::Config;
+use ethers::types::{Address, H256, U256};
+use osanwelib::generated::TransactionPb;
 use std::env;
-use tokio_postgres::{Client, Error, NoTls};
-use osanwelib::generated::TransactionPb; 
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, Error, NoTls, Transaction};
+use tonic::Status;
 
 #[derive(serde::Deserialize)]
 struct DatabaseSettings {
@@ -56,7 +62,6 @@ pub async fn init_db() -> Result<(), Error> {
     Ok(())
 }
 
-
 /// Функція для отримання клієнта БД (повторно використовувана логіка)
 pub async fn get_db_client() -> Result<Client, Error> {
     let settings = Config::builder()
@@ -71,7 +76,11 @@ pub async fn get_db_client() -> Result<Client, Error> {
 
     let connection_str = format!(
         "host={} port={} user={} password={} dbname={}",
-        db_settings.host, db_settings.port, db_settings.user, db_settings.password, db_settings.dbname
+        db_settings.host,
+        db_settings.port,
+        db_settings.user,
+        db_settings.password,
+        db_settings.dbname
     );
 
     let (client, connection) = tokio_postgres::connect(&connection_str, NoTls).await?;
@@ -85,13 +94,42 @@ pub async fn get_db_client() -> Result<Client, Error> {
     Ok(client)
 }
 
-/// Збереження транзакції в базі даних.
-/// Функція приймає об'єкт TransactionPb і вставляє його в таблицю transactions.
-/// Використовується ON CONFLICT для уникнення помилки при повторному збереженні транзакції з однаковим хешем.
-pub async fn save_transaction(tx: TransactionPb) -> Result<(), Box<dyn std::error::Error>> {
-    let client = get_db_client().await?;
+/// Збереження транзакції в базі даних після перевірки правил реєстру.
+/// Баланс і `sender_output_index` відправника читаються в тій самій транзакції PostgreSQL,
+/// що й вставка, під advisory-блокуванням на адресу відправника, тож дві паралельні
+/// транзакції одного відправника не можуть обидві пройти перевірку.
+pub async fn save_transaction(tx: TransactionPb) -> Result<(), Status> {
+    save(tx, None).await
+}
+
+/// Зберігає поповнення за знайденим депозитом разом із блоком, з якого його зараховано,
+/// щоб після реорганізації мережі знайти зарахування з витіснених блоків.
+pub async fn save_deposit(
+    tx: TransactionPb,
+    evm_chain_id: u64,
+    block_number: u64,
+) -> Result<(), Status> {
+    save(tx, Some((evm_chain_id, block_number))).await
+}
+
+async fn save(tx: TransactionPb, deposit_block: Option<(u64, u64)>) -> Result<(), Status> {
+    let mut client = get_db_client().await.map_err(internal)?;
+    let db_tx = client.transaction().await.map_err(internal)?;
+
+    if !tx.sender_address.is_empty() {
+        db_tx
+            .execute(
+                "SELECT pg_advisory_xact_lock($1)",
+                &[&sender_lock_key(&tx.sender_address)],
+            )
+            .await
+            .map_err(internal)?;
+    }
+
+    let state = load_ledger_state(&db_tx, &tx).await.map_err(internal)?;
+    validation::check_ledger(&tx, &state)?;
 
-    let stmt = client
+    let stmt = db_tx
         .prepare(
             "INSERT INTO transactions (
             transaction_hash,
@@ -103,13 +141,18 @@ pub async fn save_transaction(tx: TransactionPb) -> Result<(), Box<dyn std::erro
             sender_output_index,
             recipient_address,
             sender_signature,
-            source_transaction_hash
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-        ON CONFLICT (transaction_hash) DO NOTHING",
+            source_transaction_hash,
+            signature_version,
+            network_id,
+            multisig_threshold,
+            multisig_signers,
+            multisig_signatures
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
         )
-        .await?;
+        .await
+        .map_err(internal)?;
 
-    client
+    db_tx
         .execute(
             &stmt,
             &[
@@ -124,14 +167,343 @@ pub async fn save_transaction(tx: TransactionPb) -> Result<(), Box<dyn std::erro
                 &tx.recipient_address,
                 &tx.sender_signature,
                 &tx.source_transaction_hash,
+                // Поповнення не має конверта підпису — NULL
+                &(tx.signature_version != 0).then_some(tx.signature_version as i32),
+                &(!tx.network_id.is_empty()).then_some(tx.network_id.as_str()),
+                // Мультипідпис — NULL для звичайного відправника
+                &tx.multisig.as_ref().map(|m| m.threshold as i32),
+                &tx.multisig.as_ref().map(|m| &m.signers),
+                &tx.multisig.as_ref().map(|m| &m.signatures),
             ],
         )
-        .await?;
+        .await
+        .map_err(insert_error)?;
+
+    // Прийняте виведення стає в чергу виплат у тій самій транзакції PostgreSQL
+    if tx.transaction_type == 3 {
+        db_tx
+            .execute(
+                "INSERT INTO withdrawal_payouts (withdrawal_hash, status) VALUES ($1, $2)",
+                &[&tx.transaction_hash, &PAYOUT_PENDING],
+            )
+            .await
+            .map_err(insert_error)?;
+    }
+
+    if let Some((evm_chain_id, block_number)) = deposit_block {
+        db_tx
+            .execute(
+                "INSERT INTO deposit_credits (replenishment_hash, evm_chain_id, block_number)
+                 VALUES ($1, $2, $3)",
+                &[
+                    &tx.transaction_hash,
+                    &(evm_chain_id as i64),
+                    &(block_number as i64),
+                ],
+            )
+            .await
+            .map_err(insert_error)?;
+    }
+
+    db_tx.commit().await.map_err(internal)?;
 
     println!("Transaction saved successfully.");
     Ok(())
 }
 
+/// Зчитує з БД усе, що потрібно для `validation::check_ledger`.
+async fn load_ledger_state(
+    db_tx: &Transaction<'_>,
+    tx: &TransactionPb,
+) -> Result<LedgerState, Box<dyn std::error::Error>> {
+    let already_exists = db_tx
+        .query_opt(
+            "SELECT 1 FROM transactions WHERE transaction_hash = $1",
+            &[&tx.transaction_hash],
+        )
+        .await?
+        .is_some();
+
+    if tx.sender_address.is_empty() {
+        let source_hash_used = db_tx
+            .query_opt(
+                "SELECT 1 FROM transactions
+                 WHERE transaction_type = 1 AND source_transaction_hash = $1
+                   AND currency_id = $2 AND recipient_address = $3",
+                &[
+                    &tx.source_transaction_hash,
+                    &(tx.currency_id as i32),
+                    &tx.recipient_address,
+                ],
+            )
+            .await?
+            .is_some();
+
+        return Ok(LedgerState {
+            already_exists,
+            source_hash_used,
+            ..Default::default()
+        });
+    }
+
+    let currency_id = tx.currency_id as i32;
+    let incoming = db_tx
+        .query(
+            "SELECT amount FROM transactions
+             WHERE recipient_address = $1 AND currency_id = $2 AND transaction_type <> 3",
+            &[&tx.sender_address, &currency_id],
+        )
+        .await?;
+    let outgoing = db_tx
+        .query(
+            "SELECT amount FROM transactions WHERE sender_address = $1 AND currency_id = $2",
+            &[&tx.sender_address, &currency_id],
+        )
+        .await?;
+
+    let mut sender_balance = U256::zero();
+    for row in incoming {
+        let amount: Vec<u8> = row.get(0);
+        sender_balance = sender_balance
+            .checked_add(U256::from_big_endian(&amount))
+            .ok_or("Overflow in addition")?;
+    }
+    for row in outgoing {
+        let amount: Vec<u8> = row.get(0);
+        sender_balance = sender_balance
+            .checked_sub(U256::from_big_endian(&amount))
+            .ok_or("Underflow in subtraction")?;
+    }
+
+    let last_sender_output_index: Option<i32> = db_tx
+        .query_one(
+            "SELECT MAX(sender_output_index) FROM transactions WHERE sender_address = $1",
+            &[&tx.sender_address],
+        )
+        .await?
+        .get(0);
+
+    Ok(LedgerState {
+        already_exists,
+        sender_balance,
+        last_sender_output_index: last_sender_output_index.map(|v| v as u32),
+        source_hash_used: false,
+    })
+}
+
+/// Виведення з даним станом виплати, у порядку надходження.
+pub async fn get_payouts(client: &Client, status: &str) -> Result<Vec<Payout>, Error> {
+    let rows = client
+        .query(
+            "SELECT p.withdrawal_hash, t.currency_id, t.amount, t.recipient_address,
+                    p.payout_tx_hash, p.nonce, p.raw_transaction
+             FROM withdrawal_payouts p
+             JOIN transactions t ON t.transaction_hash = p.withdrawal_hash
+             WHERE p.status = $1
+             ORDER BY t.timestamp",
+            &[&status],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let amount: Vec<u8> = row.get(2);
+            let destination: Vec<u8> = row.get(3);
+            let tx_hash: Option<Vec<u8>> = row.get(4);
+            let nonce: Option<i64> = row.get(5);
+            let raw: Option<Vec<u8>> = row.get(6);
+
+            let signed = match (tx_hash, nonce, raw) {
+                (Some(tx_hash), Some(nonce), Some(raw)) => Some(SignedPayout {
+                    tx_hash: H256::from_slice(&tx_hash),
+                    nonce: nonce as u64,
+                    raw: raw.into(),
+                }),
+                _ => None,
+            };
+
+            Payout {
+                withdrawal_hash: row.get(0),
+                currency_id: row.get::<_, i32>(1) as u32,
+                amount: U256::from_big_endian(&amount),
+                destination: Address::from_slice(&destination),
+                signed,
+            }
+        })
+        .collect())
+}
+
+/// Записує підписану транзакцію виплати. Повертає `false`, якщо виплата вже не в черзі.
+pub async fn mark_payout_sent(
+    client: &Client,
+    withdrawal_hash: &[u8],
+    signed: &SignedPayout,
+) -> Result<bool, Error> {
+    let updated = client
+        .execute(
+            "UPDATE withdrawal_payouts
+             SET status = $2, payout_tx_hash = $3, nonce = $4, raw_transaction = $5
+             WHERE withdrawal_hash = $1 AND status = $6",
+            &[
+                &withdrawal_hash,
+                &PAYOUT_SENT,
+                &signed.tx_hash.as_bytes(),
+                &(signed.nonce as i64),
+                &signed.raw.as_ref(),
+                &PAYOUT_PENDING,
+            ],
+        )
+        .await?;
+    Ok(updated == 1)
+}
+
+/// Остаточний стан виплати: `confirmed` або `failed` з причиною.
+pub async fn finish_payout(
+    client: &Client,
+    withdrawal_hash: &[u8],
+    status: &str,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    client
+        .execute(
+            "UPDATE withdrawal_payouts SET status = $2, error = $3 WHERE withdrawal_hash = $1",
+            &[&withdrawal_hash, &status, &error],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Повертає виплату в чергу, коли її транзакцію витіснила інша з тим самим nonce.
+pub async fn reset_payout(client: &Client, withdrawal_hash: &[u8]) -> Result<(), Error> {
+    client
+        .execute(
+            "UPDATE withdrawal_payouts
+             SET status = $2, payout_tx_hash = NULL, nonce = NULL, raw_transaction = NULL
+             WHERE withdrawal_hash = $1 AND status = $3",
+            &[&withdrawal_hash, &PAYOUT_PENDING, &PAYOUT_SENT],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Збережені переглянуті блоки пошуку депозитів у мережі з їхніми хешами, від найновішого.
+pub async fn get_scanned_blocks(
+    client: &Client,
+    evm_chain_id: u64,
+) -> Result<Vec<(u64, H256)>, Error> {
+    let rows = client
+        .query(
+            "SELECT block_number, block_hash FROM deposit_scanned_blocks
+             WHERE evm_chain_id = $1
+             ORDER BY block_number DESC",
+            &[&(evm_chain_id as i64)],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let hash: Vec<u8> = row.get(1);
+            (row.get::<_, i64>(0) as u64, H256::from_slice(&hash))
+        })
+        .collect())
+}
+
+/// Записує останній переглянутий блок. Блоки, не нижчі за нього, належали витісненому
+/// ланцюгу і видаляються; з решти лишаються `keep` найновіших.
+pub async fn add_scanned_block(
+    client: &mut Client,
+    evm_chain_id: u64,
+    block_number: u64,
+    block_hash: H256,
+    keep: i64,
+) -> Result<(), Error> {
+    let db_tx = client.transaction().await?;
+    db_tx
+        .execute(
+            "DELETE FROM deposit_scanned_blocks WHERE evm_chain_id = $1 AND block_number >= $2",
+            &[&(evm_chain_id as i64), &(block_number as i64)],
+        )
+        .await?;
+    db_tx
+        .execute(
+            "INSERT INTO deposit_scanned_blocks (evm_chain_id, block_number, block_hash)
+             VALUES ($1, $2, $3)",
+            &[
+                &(evm_chain_id as i64),
+                &(block_number as i64),
+                &block_hash.as_bytes(),
+            ],
+        )
+        .await?;
+    db_tx
+        .execute(
+            "DELETE FROM deposit_scanned_blocks
+             WHERE evm_chain_id = $1 AND block_number NOT IN (
+                 SELECT block_number FROM deposit_scanned_blocks
+                 WHERE evm_chain_id = $1
+                 ORDER BY block_number DESC
+                 LIMIT $2
+             )",
+            &[&(evm_chain_id as i64), &keep],
+        )
+        .await?;
+    db_tx.commit().await
+}
+
+/// Зарахування депозитів мережі з блоків, не старіших за `from_block`.
+pub async fn get_deposit_credits(
+    client: &Client,
+    evm_chain_id: u64,
+    from_block: u64,
+) -> Result<Vec<DepositCredit>, Error> {
+    let rows = client
+        .query(
+            "SELECT c.replenishment_hash, t.source_transaction_hash, c.block_number
+             FROM deposit_credits c
+             JOIN transactions t ON t.transaction_hash = c.replenishment_hash
+             WHERE c.evm_chain_id = $1 AND c.block_number >= $2
+             ORDER BY c.block_number",
+            &[&(evm_chain_id as i64), &(from_block as i64)],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let replenishment_hash: Vec<u8> = row.get(0);
+            let source_hash: Vec<u8> = row.get(1);
+            DepositCredit {
+                replenishment_hash: H256::from_slice(&replenishment_hash),
+                source_transaction_hash: H256::from_slice(&source_hash),
+                block_number: row.get::<_, i64>(2) as u64,
+            }
+        })
+        .collect())
+}
+
+/// Ключ advisory-блокування: перші 8 байтів адреси відправника.
+fn sender_lock_key(sender_address: &[u8]) -> i64 {
+    let mut key = [0u8; 8];
+    key.copy_from_slice(&sender_address[..8]);
+    i64::from_be_bytes(key)
+}
+
+/// Вставка, що порушила унікальний індекс, означає, що паралельний запит (наприклад,
+/// клієнт і пошук депозитів з тим самим поповненням) встиг зберегти таку ж транзакцію.
+fn insert_error(e: Error) -> Status {
+    if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+        return Status::already_exists("Transaction already exists");
+    }
+    internal(e)
+}
+
+fn internal<E: std::fmt::Display>(e: E) -> Status {
+    eprintln!("Database error: {}", e);
+    Status::internal("Failed to save transaction")
+}
+
 fn print_current_directory() {
     match env::current_dir() {
         Ok(path) => println!("Поточна робоча директорія: {}", path.display()),
@@ -0,0 +1,370 @@
+// tonic::Status великий, але це і є відповідь gRPC-клієнту, тож не пакуємо його в Box
+#![allow(clippy::result_large_err)]
+
+use crate::assets;
+use crate::chains::{self, ChainClient};
+use config::Config;
+use ethers::providers::{JsonRpcClient, Middleware, Provider};
+use ethers::types::{Address, H256, U256};
+use osanwelib::generated::TransactionPb;
+use std::collections::HashMap;
+use std::error::Error;
+use tonic::Status;
+
+/// Секція `[replenishment]` у config.toml.
+#[derive(Debug, serde::Deserialize)]
+pub struct ReplenishmentSettings {
+    /// Адреса оператора, на яку користувачі переказують кошти в блокчейні
+    pub custody_address: String,
+}
+
+/// Перевіряє транзакції поповнення (тип 1) за даними вихідного EVM-блокчейну.
+pub struct ReplenishmentVerifier {
+    custody_address: Address,
+    chains: HashMap<u64, ChainClient>,
+}
+
+/// Завантажує секцію `[replenishment]` з config.toml та змінних оточення.
+pub fn load_settings() -> Result<ReplenishmentSettings, config::ConfigError> {
+    let settings = Config::builder()
+        .add_source(config::File::with_name("config"))
+        .add_source(config::Environment::with_prefix("APP"))
+        .build()?;
+
+    settings.get::<ReplenishmentSettings>("replenishment")
+}
+
+impl ReplenishmentVerifier {
+    pub fn new(
+        settings: &ReplenishmentSettings,
+        chains: HashMap<u64, ChainClient>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let custody_address: Address = settings.custody_address.parse()?;
+
+        Ok(Self {
+            custody_address,
+            chains,
+        })
+    }
+
+    /// Верифікатор без жодної мережі: відхиляє всі поповнення.
+    pub fn disabled() -> Self {
+        Self {
+            custody_address: Address::zero(),
+            chains: HashMap::new(),
+        }
+    }
+
+    /// Перевіряє, що `source_transaction_hash` — підтверджений переказ `amount`
+    /// потрібного токена з адреси `recipient_address` на адресу оператора.
+    pub async fn verify(&self, tx: &TransactionPb) -> Result<(), Status> {
+        let asset = match assets::get(tx.currency_id) {
+            Ok(Some(asset)) => asset,
+            Ok(None) => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown currency_id {}",
+                    tx.currency_id
+                )))
+            }
+            Err(e) => return Err(Status::internal(e.to_string())),
+        };
+
+        let evm_chain_id = asset.evm_chain_id.ok_or_else(|| {
+            Status::failed_precondition(format!(
+                "Replenishment of {} (non-EVM network) cannot be verified",
+                asset.symbol
+            ))
+        })?;
+        let chain = self.chains.get(&evm_chain_id).ok_or_else(|| {
+            Status::failed_precondition(format!(
+                "Replenishment from EVM chain {} is not configured",
+                evm_chain_id
+            ))
+        })?;
+
+        let token = match &asset.contract_address {
+            Some(contract) => Some(contract.parse::<Address>().map_err(|e| {
+                Status::internal(format!("Invalid contract address {}: {}", contract, e))
+            })?),
+            None => None,
+        };
+
+        verify_source_transfer(
+            &chain.provider,
+            chain.confirmations,
+            self.custody_address,
+            token,
+            tx,
+        )
+        .await
+    }
+}
+
+/// Перевірка однієї транзакції у вихідній мережі.
+/// `token == None` означає нативну монету мережі (ETH, BNB, ...), інакше — ERC-20 контракт.
+pub async fn verify_source_transfer<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    confirmations: u64,
+    custody_address: Address,
+    token: Option<Address>,
+    tx: &TransactionPb,
+) -> Result<(), Status> {
+    let source_hash = H256::from_slice(&tx.source_transaction_hash);
+    let depositor = Address::from_slice(&tx.recipient_address);
+    let amount = U256::from_big_endian(&tx.amount);
+
+    let source_tx = provider
+        .get_transaction(source_hash)
+        .await
+        .map_err(unavailable)?
+        .ok_or_else(|| {
+            Status::not_found(format!("Source transaction {:?} not found", source_hash))
+        })?;
+
+    let receipt = provider
+        .get_transaction_receipt(source_hash)
+        .await
+        .map_err(unavailable)?
+        .ok_or_else(|| {
+            Status::failed_precondition(format!(
+                "Source transaction {:?} is not mined yet",
+                source_hash
+            ))
+        })?;
+
+    if receipt.status != Some(1u64.into()) {
+        return Err(Status::invalid_argument(format!(
+            "Source transaction {:?} has failed",
+            source_hash
+        )));
+    }
+
+    let mined_in = receipt
+        .block_number
+        .ok_or_else(|| Status::failed_precondition("Source transaction is pending"))?;
+    let head = provider.get_block_number().await.map_err(unavailable)?;
+    let depth = head.saturating_sub(mined_in).as_u64() + 1;
+    if depth < confirmations {
+        return Err(Status::failed_precondition(format!(
+            "Source transaction has {} confirmations, {} required",
+            depth, confirmations
+        )));
+    }
+
+    // Кошти мають прийти від власника Osanwe-гаманця, інакше чужий депозит
+    // міг би зарахувати собі будь-хто, хто побачив хеш у блокчейні
+    if source_tx.from != depositor {
+        return Err(Status::invalid_argument(format!(
+            "Source transaction was sent by {:?}, not by recipient {:?}",
+            source_tx.from, depositor
+        )));
+    }
+
+    let transferred = match token {
+        None => {
+            if source_tx.to != Some(custody_address) {
+                return Err(Status::invalid_argument(
+                    "Source transaction is not a transfer to the custody address",
+                ));
+            }
+            source_tx.value
+        }
+        Some(token) => erc20_transferred(&receipt.logs, token, depositor, custody_address)?,
+    };
+
+    if transferred != amount {
+        return Err(Status::invalid_argument(format!(
+            "Source transaction transferred {}, but replenishment claims {}",
+            transferred, amount
+        )));
+    }
+
+    Ok(())
+}
+
+/// Сума всіх ERC-20 `Transfer(from, custody, value)` даного токена в логах транзакції.
+fn erc20_transferred(
+    logs: &[ethers::types::Log],
+    token: Address,
+    from: Address,
+    custody_address: Address,
+) -> Result<U256, Status> {
+    let transfer_topic = chains::erc20_transfer_topic();
+
+    let mut total = U256::zero();
+    for log in logs {
+        if log.address != token || log.topics.len() != 3 || log.topics[0] != transfer_topic {
+            continue;
+        }
+        let log_from = Address::from(log.topics[1]);
+        let log_to = Address::from(log.topics[2]);
+        if log_from != from || log_to != custody_address {
+            continue;
+        }
+        total = total
+            .checked_add(U256::from_big_endian(&log.data))
+            .ok_or_else(|| Status::invalid_argument("Overflow in ERC-20 transfer amount"))?;
+    }
+    Ok(total)
+}
+
+fn unavailable<E: std::fmt::Display>(e: E) -> Status {
+    Status::unavailable(format!("Source chain RPC error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::MockProvider;
+    use ethers::types::{Log, Transaction, TransactionReceipt, U64};
+    use tonic::Code;
+
+    const AMOUNT: u64 = 5_000_000;
+
+    fn custody() -> Address {
+        Address::repeat_byte(0xC0)
+    }
+
+    fn depositor() -> Address {
+        Address::repeat_byte(0xDE)
+    }
+
+    fn token() -> Address {
+        Address::repeat_byte(0x70)
+    }
+
+    fn replenishment() -> TransactionPb {
+        let mut amount = [0u8; 32];
+        U256::from(AMOUNT).to_big_endian(&mut amount);
+        TransactionPb {
+            transaction_hash: vec![0xAA; 32],
+            transaction_type: 1,
+            currency_id: 16842753,
+            amount: amount.to_vec(),
+            timestamp: 1700000000,
+            sender_address: Vec::new(),
+            sender_output_index: 0,
+            recipient_address: depositor().as_bytes().to_vec(),
+            sender_signature: Vec::new(),
+            source_transaction_hash: vec![0x53; 32],
+            signature_version: 0,
+            network_id: String::new(),
+            multisig: None,
+        }
+    }
+
+    fn source_tx(to: Address, value: U256) -> Transaction {
+        Transaction {
+            hash: H256::repeat_byte(0x53),
+            from: depositor(),
+            to: Some(to),
+            value,
+            ..Default::default()
+        }
+    }
+
+    fn receipt(block: u64, logs: Vec<Log>) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(0x53),
+            block_number: Some(U64::from(block)),
+            status: Some(U64::from(1)),
+            logs,
+            ..Default::default()
+        }
+    }
+
+    fn transfer_log(to: Address, value: u64) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        Log {
+            address: token(),
+            topics: vec![
+                chains::erc20_transfer_topic(),
+                H256::from(depositor()),
+                H256::from(to),
+            ],
+            data: data.to_vec().into(),
+            ..Default::default()
+        }
+    }
+
+    /// Відповіді MockProvider віддаються з кінця черги, тому додаємо їх у зворотному порядку
+    fn stand_in(tx: Transaction, receipt: TransactionReceipt, head: u64) -> Provider<MockProvider> {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(head)).unwrap();
+        mock.push(receipt).unwrap();
+        mock.push(tx).unwrap();
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_native_transfer_is_accepted() {
+        let provider = stand_in(
+            source_tx(custody(), U256::from(AMOUNT)),
+            receipt(100, Vec::new()),
+            111,
+        );
+        verify_source_transfer(&provider, 12, custody(), None, &replenishment())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_erc20_transfer_is_accepted() {
+        let provider = stand_in(
+            source_tx(token(), U256::zero()),
+            receipt(100, vec![transfer_log(custody(), AMOUNT)]),
+            100,
+        );
+        verify_source_transfer(&provider, 1, custody(), Some(token()), &replenishment())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_enough_confirmations() {
+        let provider = stand_in(
+            source_tx(custody(), U256::from(AMOUNT)),
+            receipt(100, Vec::new()),
+            105,
+        );
+        let err = verify_source_transfer(&provider, 12, custody(), None, &replenishment())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_amount_or_destination_is_rejected() {
+        let provider = stand_in(
+            source_tx(custody(), U256::from(AMOUNT - 1)),
+            receipt(100, Vec::new()),
+            200,
+        );
+        let err = verify_source_transfer(&provider, 12, custody(), None, &replenishment())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        // ERC-20 переказ на іншу адресу не зараховується
+        let provider = stand_in(
+            source_tx(token(), U256::zero()),
+            receipt(100, vec![transfer_log(Address::repeat_byte(0x01), AMOUNT)]),
+            200,
+        );
+        let err = verify_source_transfer(&provider, 1, custody(), Some(token()), &replenishment())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_missing_source_transaction() {
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Option<Transaction>, _>(None).unwrap();
+        let err = verify_source_transfer(&provider, 1, custody(), None, &replenishment())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,456 @@
+// tonic::Status великий, але це і є відповідь gRPC-клієнту, тож не пакуємо його в Box
+#![allow(clippy::result_large_err)]
+
+use config::Config;
+use ethers::types::U256;
+use ethers::utils::keccak256;
+use osanwelib::generated::TransactionPb;
+use osanwelib::tx;
+use tonic::Status;
+
+/// Довжини полів транзакції в байтах (див. transaction_pb.proto)
+const HASH_LEN: usize = 32;
+const AMOUNT_LEN: usize = 32;
+const ADDRESS_LEN: usize = 20;
+const SIGNATURE_LEN: usize = 65;
+
+/// Стан реєстру, потрібний для перевірки транзакції перед збереженням.
+#[derive(Debug, Default)]
+pub struct LedgerState {
+    /// Транзакція з таким хешем вже збережена
+    pub already_exists: bool,
+    /// Баланс відправника у валюті транзакції
+    pub sender_balance: U256,
+    /// Найбільший використаний `sender_output_index` відправника
+    pub last_sender_output_index: Option<u32>,
+    /// `source_transaction_hash` поповнення вже зараховано тому ж отримувачу в тій же валюті
+    pub source_hash_used: bool,
+}
+
+/// Ідентифікатор мережі сервера з `[network] id`; без нього — мережа розробки.
+pub fn load_network_id() -> String {
+    let network_id = Config::builder()
+        .add_source(config::File::with_name("config"))
+        .add_source(config::Environment::with_prefix("APP"))
+        .build()
+        .and_then(|settings| settings.get_string("network.id"));
+
+    match network_id {
+        Ok(network_id) => network_id,
+        Err(e) => {
+            eprintln!(
+                "No network id configured ({}), using {}",
+                e,
+                tx::DEFAULT_NETWORK_ID
+            );
+            tx::DEFAULT_NETWORK_ID.to_owned()
+        }
+    }
+}
+
+/// Переказ і виведення мають бути підписані поточною версією конверта для мережі
+/// `network_id` цього сервера — інакше підпис з тестового сервера діяв би на робочому.
+pub fn check_network(tx: &TransactionPb, network_id: &str) -> Result<(), Status> {
+    if tx.transaction_type != 2 && tx.transaction_type != 3 {
+        return Ok(());
+    }
+    if tx.signature_version != tx::SIGNATURE_VERSION {
+        return Err(Status::invalid_argument(format!(
+            "Unsupported signature_version {} (expected {})",
+            tx.signature_version,
+            tx::SIGNATURE_VERSION
+        )));
+    }
+    if tx.network_id != network_id {
+        return Err(Status::failed_precondition(format!(
+            "Transaction is signed for network '{}', this server serves '{}'",
+            tx.network_id, network_id
+        )));
+    }
+    Ok(())
+}
+
+/// Перевірки, які не потребують доступу до бази даних.
+pub fn validate_transaction(tx: &TransactionPb) -> Result<(), Status> {
+    check_format(tx)?;
+    check_currency(tx)?;
+    check_integrity(tx)
+}
+
+/// Перевіряє тип транзакції, довжини полів і ненульову суму.
+pub fn check_format(tx: &TransactionPb) -> Result<(), Status> {
+    check_len("transaction_hash", &tx.transaction_hash, HASH_LEN)?;
+    check_len("amount", &tx.amount, AMOUNT_LEN)?;
+    check_len("recipient_address", &tx.recipient_address, ADDRESS_LEN)?;
+
+    match tx.transaction_type {
+        1 => {
+            check_len(
+                "source_transaction_hash",
+                &tx.source_transaction_hash,
+                HASH_LEN,
+            )?;
+            check_empty("sender_address", &tx.sender_address)?;
+            check_empty("sender_signature", &tx.sender_signature)?;
+            if tx.multisig.is_some() {
+                return Err(Status::invalid_argument(
+                    "multisig must be empty for this transaction type",
+                ));
+            }
+        }
+        // Переказ і виведення: виведення відрізняється лише тим, що recipient_address —
+        // адреса в зовнішньому блокчейні
+        2 | 3 => {
+            check_len("sender_address", &tx.sender_address, ADDRESS_LEN)?;
+            match &tx.multisig {
+                // Мультипідписний відправник підписує не sender_signature, а multisig
+                Some(multisig) => {
+                    check_empty("sender_signature", &tx.sender_signature)?;
+                    for signer in &multisig.signers {
+                        check_len("multisig signer", signer, ADDRESS_LEN)?;
+                    }
+                    for signature in &multisig.signatures {
+                        check_len("multisig signature", signature, SIGNATURE_LEN)?;
+                    }
+                }
+                None => check_len("sender_signature", &tx.sender_signature, SIGNATURE_LEN)?,
+            }
+            check_empty("source_transaction_hash", &tx.source_transaction_hash)?;
+            if tx.sender_output_index == 0 {
+                return Err(Status::invalid_argument(
+                    "sender_output_index must start from 1",
+                ));
+            }
+        }
+        other => {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported transaction_type {}",
+                other
+            )))
+        }
+    }
+
+    if U256::from_big_endian(&tx.amount).is_zero() {
+        return Err(Status::invalid_argument("amount must be greater than zero"));
+    }
+
+    Ok(())
+}
+
+/// Перевіряє, що `currency_id` є в довіднику `CryptoAssets`, а для виведення — що це EVM-мережа.
+pub fn check_currency(tx: &TransactionPb) -> Result<(), Status> {
+    match crate::assets::get(tx.currency_id) {
+        Ok(Some(asset)) if tx.transaction_type == 3 && asset.evm_chain_id.is_none() => {
+            Err(Status::invalid_argument(format!(
+                "Withdrawal of {} is not supported: only EVM networks can be paid out",
+                asset.symbol
+            )))
+        }
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Status::invalid_argument(format!(
+            "Unknown currency_id {}",
+            tx.currency_id
+        ))),
+        Err(e) => Err(Status::internal(format!(
+            "Failed to look up currency_id {}: {}",
+            tx.currency_id, e
+        ))),
+    }
+}
+
+/// Ті самі правила, що й `tx::verify_transaction`: хеш і підпис відправника.
+pub fn check_integrity(tx: &TransactionPb) -> Result<(), Status> {
+    // Хеш перевіряємо окремо, щоб відрізнити пошкоджену транзакцію від чужого підпису
+    let computed_hash = keccak256(tx::tx_to_bytes(tx));
+    if computed_hash[..] != tx.transaction_hash[..] {
+        return Err(Status::invalid_argument(
+            "Invalid transaction hash: does not match keccak256(tx_to_bytes)",
+        ));
+    }
+
+    tx::verify_transaction(tx).map_err(|e| Status::unauthenticated(e.to_string()))
+}
+
+/// Правила реєстру: унікальний хеш, достатній баланс і наступний `sender_output_index`.
+pub fn check_ledger(tx: &TransactionPb, state: &LedgerState) -> Result<(), Status> {
+    if state.already_exists {
+        return Err(Status::already_exists("Transaction already exists"));
+    }
+
+    // Поповнення не має відправника в Osanwe, але кожну валюту й отримувача
+    // з однієї транзакції блокчейну можна зарахувати лише один раз
+    if tx.transaction_type == 1 {
+        if state.source_hash_used {
+            return Err(Status::already_exists(
+                "Source transaction has already been credited in this currency to this recipient",
+            ));
+        }
+        return Ok(());
+    }
+
+    let expected_index = state.last_sender_output_index.unwrap_or(0) + 1;
+    if tx.sender_output_index != expected_index {
+        return Err(Status::failed_precondition(format!(
+            "Invalid sender_output_index {}: expected {}",
+            tx.sender_output_index, expected_index
+        )));
+    }
+
+    let amount = U256::from_big_endian(&tx.amount);
+    if state.sender_balance < amount {
+        return Err(Status::failed_precondition(format!(
+            "Insufficient funds in currency {}: balance {}, requested {}",
+            tx.currency_id, state.sender_balance, amount
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_len(field: &str, value: &[u8], expected: usize) -> Result<(), Status> {
+    if value.len() != expected {
+        return Err(Status::invalid_argument(format!(
+            "Invalid {} length: expected {} bytes, got {}",
+            field,
+            expected,
+            value.len()
+        )));
+    }
+    Ok(())
+}
+
+fn check_empty(field: &str, value: &[u8]) -> Result<(), Status> {
+    if !value.is_empty() {
+        return Err(Status::invalid_argument(format!(
+            "{} must be empty for this transaction type",
+            field
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osanwelib::keys;
+    use tonic::Code;
+
+    const NETWORK_ID: &str = "osanwe-test";
+
+    fn signed_transfer() -> TransactionPb {
+        let (signing_key, address) = keys::generate_ethereum_keypair();
+        let mut amount = [0u8; 32];
+        U256::from(1000).to_big_endian(&mut amount);
+
+        let mut tx = TransactionPb {
+            transaction_hash: Vec::new(),
+            transaction_type: 2,
+            currency_id: 16842752,
+            amount: amount.to_vec(),
+            timestamp: 1700000000,
+            sender_address: address.as_bytes().to_vec(),
+            sender_output_index: 1,
+            recipient_address: vec![0xDD; 20],
+            sender_signature: Vec::new(),
+            source_transaction_hash: Vec::new(),
+            signature_version: tx::SIGNATURE_VERSION,
+            network_id: NETWORK_ID.to_owned(),
+            multisig: None,
+        };
+        tx.transaction_hash = keccak256(tx::tx_to_bytes(&tx)).to_vec();
+        tx.sender_signature = keys::sign_digest_with_private_key(
+            &signing_key.to_bytes(),
+            tx::eip712_digest(&tx).unwrap(),
+        )
+        .unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_foreign_network_is_rejected() {
+        let tx = signed_transfer();
+        check_network(&tx, NETWORK_ID).unwrap();
+        assert_eq!(
+            check_network(&tx, "osanwe-mainnet").unwrap_err().code(),
+            Code::FailedPrecondition
+        );
+
+        let mut unversioned = signed_transfer();
+        unversioned.signature_version = 0;
+        assert_eq!(
+            check_network(&unversioned, NETWORK_ID).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_multisig_transfer() {
+        let keys: Vec<_> = (0..2).map(|_| keys::generate_ethereum_keypair()).collect();
+        let mut signers: Vec<_> = keys.iter().map(|(_, address)| *address).collect();
+        signers.sort();
+
+        let mut tx = signed_transfer();
+        tx.sender_signature = Vec::new();
+        tx.sender_address = osanwelib::multisig::derive_address(2, &signers)
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        tx.transaction_hash = keccak256(tx::tx_to_bytes(&tx)).to_vec();
+        tx.multisig = Some(osanwelib::generated::MultisigAuthorization {
+            threshold: 2,
+            signers: signers.iter().map(|a| a.as_bytes().to_vec()).collect(),
+            signatures: Vec::new(),
+        });
+
+        let digest = tx::eip712_digest(&tx).unwrap();
+        for (signing_key, _) in &keys {
+            let signature =
+                keys::sign_digest_with_private_key(&signing_key.to_bytes(), digest).unwrap();
+            tx.multisig.as_mut().unwrap().signatures.push(signature);
+        }
+        check_format(&tx).unwrap();
+        check_integrity(&tx).unwrap();
+
+        // Без другого підпису поріг не досягнуто
+        let mut partial = tx.clone();
+        partial.multisig.as_mut().unwrap().signatures.pop();
+        check_format(&partial).unwrap();
+        assert_eq!(
+            check_integrity(&partial).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+
+        let mut with_sender_signature = tx.clone();
+        with_sender_signature.sender_signature = vec![0x01; 65];
+        assert_eq!(
+            check_format(&with_sender_signature).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_valid_transfer_passes() {
+        let tx = signed_transfer();
+        check_format(&tx).unwrap();
+        check_integrity(&tx).unwrap();
+
+        let state = LedgerState {
+            sender_balance: U256::from(1000),
+            ..Default::default()
+        };
+        check_ledger(&tx, &state).unwrap();
+    }
+
+    #[test]
+    fn test_format_errors_are_invalid_argument() {
+        let mut tx = signed_transfer();
+        tx.recipient_address = vec![0xDD; 19];
+        assert_eq!(check_format(&tx).unwrap_err().code(), Code::InvalidArgument);
+
+        let mut tx = signed_transfer();
+        tx.amount = vec![0; 32];
+        assert_eq!(check_format(&tx).unwrap_err().code(), Code::InvalidArgument);
+
+        let mut tx = signed_transfer();
+        tx.transaction_type = 7;
+        assert_eq!(check_format(&tx).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_integrity_errors() {
+        // Змінена сума ламає хеш
+        let mut tx = signed_transfer();
+        tx.amount[31] ^= 1;
+        assert_eq!(
+            check_integrity(&tx).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+
+        // Правильний хеш, але підпис іншого ключа
+        let mut tx = signed_transfer();
+        tx.sender_signature = signed_transfer().sender_signature;
+        assert_eq!(
+            check_integrity(&tx).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn test_ledger_rules() {
+        let tx = signed_transfer();
+
+        let duplicate = LedgerState {
+            already_exists: true,
+            sender_balance: U256::from(1000),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_ledger(&tx, &duplicate).unwrap_err().code(),
+            Code::AlreadyExists
+        );
+
+        let poor = LedgerState {
+            sender_balance: U256::from(999),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_ledger(&tx, &poor).unwrap_err().code(),
+            Code::FailedPrecondition
+        );
+
+        let reused_index = LedgerState {
+            sender_balance: U256::from(1000),
+            last_sender_output_index: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_ledger(&tx, &reused_index).unwrap_err().code(),
+            Code::FailedPrecondition
+        );
+    }
+
+    #[test]
+    fn test_source_hash_cannot_be_reused() {
+        let mut tx = signed_transfer();
+        tx.transaction_type = 1;
+        tx.sender_address = Vec::new();
+        tx.sender_signature = Vec::new();
+        tx.source_transaction_hash = vec![0x53; 32];
+
+        check_ledger(&tx, &LedgerState::default()).unwrap();
+
+        let used = LedgerState {
+            source_hash_used: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_ledger(&tx, &used).unwrap_err().code(),
+            Code::AlreadyExists
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_is_signed_like_a_transfer() {
+        let (signing_key, address) = keys::generate_ethereum_keypair();
+        let mut tx = signed_transfer();
+        tx.transaction_type = 3;
+        tx.sender_address = address.as_bytes().to_vec();
+        tx.transaction_hash = keccak256(tx::tx_to_bytes(&tx)).to_vec();
+        tx.sender_signature = keys::sign_digest_with_private_key(
+            &signing_key.to_bytes(),
+            tx::eip712_digest(&tx).unwrap(),
+        )
+        .unwrap();
+
+        check_format(&tx).unwrap();
+        check_integrity(&tx).unwrap();
+
+        // Підпис виведення не можна перевикористати як підпис переказу
+        tx.transaction_type = 2;
+        tx.transaction_hash = keccak256(tx::tx_to_bytes(&tx)).to_vec();
+        assert_eq!(
+            check_integrity(&tx).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+    }
+}